@@ -1,58 +1,58 @@
 use mev_engine::amm::jediswap::pool::JediswapPool;
-use mev_engine::amm::pool::{AutomatedMarketMaker, AMM};
+use mev_engine::amm::pool::{sync_amms_from_events, AutomatedMarketMaker, AMM};
 use mev_engine::amm::tenkswap::pool::TenkSwapPool;
-use starknet::core::types::Felt;
+use mev_engine::arbitrage::sizing::optimal_trade_size;
+use mev_engine::arbitrage::{ArbitrageScanner, StartToken};
+use mev_engine::provider::retry::RetryProvider;
+use starknet::core::types::{BlockId, Felt};
 use starknet::providers::jsonrpc::HttpTransport;
 use starknet::providers::{JsonRpcClient, Provider, Url};
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use std::u32;
-use tokio;
 
 fn create_rpc_provider(
     rpc_url: &str,
-) -> Result<Arc<JsonRpcClient<HttpTransport>>, Box<dyn std::error::Error>> {
+) -> Result<Arc<RetryProvider<JsonRpcClient<HttpTransport>>>, Box<dyn std::error::Error>> {
     let url = Url::parse(rpc_url)?;
-    let provider = JsonRpcClient::new(HttpTransport::new(url));
+    // Public nodes rate limit aggressively, so keep well under their limits.
+    let provider =
+        RetryProvider::new(JsonRpcClient::new(HttpTransport::new(url))).with_rate_limit(5.0, 10);
     Ok(Arc::new(provider))
 }
 
-async fn find_arbitrage<P>(pool1: &mut AMM, pool2: &mut AMM, provider: Arc<P>) -> bool
+async fn find_arbitrage<P>(
+    scanner: &ArbitrageScanner,
+    pools: &HashMap<Felt, AMM>,
+    provider: Arc<P>,
+) -> bool
 where
     P: Provider + Send + Sync,
 {
-    pool1.sync(provider.clone()).await.unwrap();
-    pool2.sync(provider.clone()).await.unwrap();
+    let opportunities = scanner.scan(pools, provider).await.unwrap();
 
-    let mut pool1_tokens = pool1.tokens();
-    let mut pool2_tokens = pool2.tokens();
-    pool1_tokens.sort();
-    pool2_tokens.sort();
-
-    assert!(
-        pool1_tokens == pool2_tokens,
-        "This strategy suppports only common"
-    );
-
-    let tokens = pool1_tokens;
-
-    let amount_in = Felt::from(100u32);
-    let amount_out = pool1
-        .simulate_swap(tokens[0], amount_in, provider.clone())
-        .await
-        .unwrap();
-
-    let final_amount = pool2
-        .simulate_swap(tokens[1], amount_out, provider)
-        .await
-        .unwrap();
-
-    if final_amount <= amount_in {
-        println!("No arbitrage found at, retrying soon...");
-        false
-    } else {
-        println!("Arbitrage opportunity found...");
-        true
+    match opportunities.first() {
+        Some(best) => {
+            // Trade as much of the start token as pays off best, up to 1000 units of it.
+            let sized = optimal_trade_size(
+                &best.cycle,
+                pools,
+                Felt::from(1_000_000_000_000_000_000_000u128),
+            )
+            .unwrap()
+            .unwrap_or_else(|| best.clone());
+            println!(
+                "Arbitrage opportunity found through {} pools, trading {} for a profit of {}",
+                best.cycle.hops.len(),
+                sized.amount_in,
+                sized.profit().unwrap_or_default()
+            );
+            true
+        }
+        None => {
+            println!("No arbitrage found, retrying soon...");
+            false
+        }
     }
 }
 
@@ -67,7 +67,7 @@ async fn main() {
     let rpc_url = "https://starknet-mainnet.public.blastapi.io/rpc/v0_7";
     let provider = create_rpc_provider(rpc_url).unwrap();
 
-    let mut tenkswap_pool = AMM::TenkSwapPool(
+    let tenkswap_pool = AMM::TenkSwapPool(
         TenkSwapPool::new_from_address(
             Felt::from_hex("0x17e9e62c04b50800d7c59454754fe31a2193c9c3c6c92c093f2ab0faadf8c87")
                 .unwrap(),
@@ -78,7 +78,7 @@ async fn main() {
         .unwrap(),
     );
 
-    let mut jediswap_pool = AMM::JediswapPool(
+    let jediswap_pool = AMM::JediswapPool(
         JediswapPool::new_from_address(
             Felt::from_hex("0x7e2a13b40fc1119ec55e0bcf9428eedaa581ab3c924561ad4e955f95da63138")
                 .unwrap(),
@@ -89,12 +89,45 @@ async fn main() {
         .unwrap(),
     );
 
+    // Sync both pools against the same block so their reserves are comparable, then keep
+    // them up to date from the events of every new block.
+    let mut pools = vec![tenkswap_pool, jediswap_pool];
+    let mut last_block = provider.block_number().await.unwrap();
+    for pool in pools.iter_mut() {
+        pool.sync(BlockId::Number(last_block), provider.clone())
+            .await
+            .unwrap();
+    }
+
+    // Start every cycle from the first token of the TenKSwap pool.
+    let scanner = ArbitrageScanner::new(vec![StartToken {
+        token: pools[0].tokens()[0],
+        amount_in: Felt::from(1_000_000_000_000_000u64),
+    }]);
+
     let mut summary = Summary {
         total_iterations: 0,
         total_opportunities: 0,
     };
     for _ in 0..3 {
-        let found = find_arbitrage(&mut tenkswap_pool, &mut jediswap_pool, provider.clone()).await;
+        let block = provider.block_number().await.unwrap();
+        if block > last_block {
+            sync_amms_from_events(
+                &mut pools,
+                BlockId::Number(last_block + 1),
+                BlockId::Number(block),
+                provider.clone(),
+            )
+            .await
+            .unwrap();
+            last_block = block;
+        }
+
+        let state = pools
+            .iter()
+            .map(|pool| (pool.address(), pool.clone()))
+            .collect::<HashMap<_, _>>();
+        let found = find_arbitrage(&scanner, &state, provider.clone()).await;
         if found {
             summary.total_opportunities += 1;
         }
@@ -4,7 +4,6 @@ use starknet::core::types::Felt;
 use starknet::providers::jsonrpc::HttpTransport;
 use starknet::providers::{JsonRpcClient, Url};
 use std::sync::Arc;
-use tokio;
 // use starknet_mev_client::amm::AutomatedMarketMaker;
 
 #[allow(unused)]
@@ -0,0 +1,212 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use starknet::{
+    core::types::{BlockId, EmittedEvent, EventFilter, Felt},
+    providers::Provider,
+};
+
+use crate::{
+    amm::{
+        factory::{check_event_selector, AutomatedMarketMakerFactory},
+        pool::{AutomatedMarketMaker, AMM},
+    },
+    errors::AMMError,
+    token_registry::TokenRegistry,
+    utils::get_events,
+};
+
+use super::{
+    get_data::{
+        get_pool_info, parse_pool_key, parse_position_updated, POOL_INITIALIZED_EVENT,
+        POSITION_UPDATED_EVENT,
+    },
+    pool::EkuboPool,
+};
+
+/// Discovers the pools of the Ekubo core, which plays the role of a factory.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EkuboFactory {
+    pub core_address: Felt,
+    pub creation_block: u64,
+    /// Cache of token metadata, shared with the other factories it is cloned into.
+    #[serde(skip)]
+    pub token_registry: TokenRegistry,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for EkuboFactory {
+    fn address(&self) -> Felt {
+        self.core_address
+    }
+
+    async fn fetch_all_pools<P>(&mut self, provider: Arc<P>) -> Result<Vec<AMM>, AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let block_id = BlockId::Number(provider.block_number().await?);
+        let filter = EventFilter {
+            from_block: Some(BlockId::Number(self.creation_block)),
+            to_block: Some(block_id),
+            address: Some(self.core_address),
+            keys: Some(self.amm_created_event_signature()),
+        };
+
+        let mut pools = vec![];
+        for event in get_events(provider.clone(), filter, 1000).await? {
+            // PoolInitialized { pool_key, initial_tick, sqrt_ratio }
+            let key = parse_pool_key(&event.data)
+                .map_err(|_| AMMError::UnrecognizedPoolCreatedEventLog)?;
+            pools.push(
+                get_pool_info(
+                    self.core_address,
+                    key,
+                    block_id,
+                    &self.token_registry,
+                    provider.clone(),
+                )
+                .await?,
+            );
+        }
+
+        self.populate_tick_data(pools.iter_mut(), self.creation_block, block_id, provider)
+            .await?;
+
+        Ok(pools.into_iter().map(AMM::EkuboPool).collect())
+    }
+
+    async fn populate_amm_data<P>(
+        &self,
+        amms: &mut [AMM],
+        block_id: BlockId,
+        middleware: Arc<P>,
+    ) -> Result<(), AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let mut pools = vec![];
+        for amm in amms.iter() {
+            if let AMM::EkuboPool(pool) = amm {
+                pools.push(
+                    get_pool_info(
+                        pool.core_address,
+                        pool.key.clone(),
+                        block_id,
+                        &self.token_registry,
+                        middleware.clone(),
+                    )
+                    .await?,
+                );
+            }
+        }
+
+        self.populate_tick_data(pools.iter_mut(), self.creation_block, block_id, middleware)
+            .await?;
+
+        let ekubo_amms = amms
+            .iter_mut()
+            .filter(|amm| matches!(amm, AMM::EkuboPool(_)));
+        for (amm, pool) in ekubo_amms.zip(pools) {
+            *amm = AMM::EkuboPool(pool);
+        }
+
+        Ok(())
+    }
+
+    fn amm_created_event_signature(&self) -> Vec<Vec<Felt>> {
+        vec![vec![POOL_INITIALIZED_EVENT]]
+    }
+
+    fn new_empty_amm_from_log(&self, log: EmittedEvent) -> Result<AMM, AMMError> {
+        check_event_selector(&log, POOL_INITIALIZED_EVENT)?;
+
+        // PoolInitialized { pool_key, initial_tick, sqrt_ratio }
+        let key =
+            parse_pool_key(&log.data).map_err(|_| AMMError::UnrecognizedPoolCreatedEventLog)?;
+
+        Ok(AMM::EkuboPool(EkuboPool {
+            core_address: log.from_address,
+            key,
+            ..Default::default()
+        }))
+    }
+}
+
+impl EkuboFactory {
+    pub fn new(core_address: Felt, creation_block: u64) -> EkuboFactory {
+        EkuboFactory {
+            core_address,
+            creation_block,
+            token_registry: TokenRegistry::default(),
+        }
+    }
+
+    pub fn with_token_registry(mut self, token_registry: TokenRegistry) -> EkuboFactory {
+        self.token_registry = token_registry;
+        self
+    }
+
+    /// Syncs pools restored from a checkpoint taken at `from_block - 1` up to `block_id`.
+    ///
+    /// The price state is read again, while the tick data the pools already hold is only
+    /// updated with the `PositionUpdated` events emitted since.
+    pub async fn sync_amm_data<P>(
+        &self,
+        amms: &mut [AMM],
+        from_block: u64,
+        block_id: BlockId,
+        provider: Arc<P>,
+    ) -> Result<(), AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let mut pools = vec![];
+        for amm in amms.iter_mut() {
+            if let AMM::EkuboPool(pool) = amm {
+                pool.sync(block_id, provider.clone()).await?;
+                pools.push(pool);
+            }
+        }
+
+        self.populate_tick_data(pools, from_block, block_id, provider)
+            .await
+    }
+
+    /// Applies every `PositionUpdated` event of the core in the block range to the matching
+    /// pools in a single pass.
+    pub async fn populate_tick_data<'a, P>(
+        &self,
+        pools: impl IntoIterator<Item = &'a mut EkuboPool>,
+        from_block: u64,
+        to_block: BlockId,
+        provider: Arc<P>,
+    ) -> Result<(), AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let mut pools_by_id: HashMap<Felt, &mut EkuboPool> = pools
+            .into_iter()
+            .map(|pool| (pool.address(), pool))
+            .collect();
+        let Some(core_address) = pools_by_id.values().next().map(|pool| pool.core_address) else {
+            return Ok(());
+        };
+
+        let filter = EventFilter {
+            from_block: Some(BlockId::Number(from_block)),
+            to_block: Some(to_block),
+            address: Some(core_address),
+            keys: Some(vec![vec![POSITION_UPDATED_EVENT]]),
+        };
+
+        for event in get_events(provider, filter, 1000).await? {
+            let (key, lower, upper, liquidity_delta) = parse_position_updated(&event.data)?;
+            if let Some(pool) = pools_by_id.get_mut(&key.id()) {
+                pool.update_position(lower, upper, liquidity_delta);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use std::sync::Arc;
+
+use num_bigint::BigUint;
+use starknet::{
+    core::types::{BlockId, Felt},
+    macros::selector,
+    providers::Provider,
+};
+
+use super::pool::{EkuboPool, PoolKey};
+use crate::{
+    errors::AMMError,
+    token_registry::TokenRegistry,
+    utils::{call_contract, parse_u128, parse_u256, read_integer, read_result, DEFAULT_BATCH_SIZE},
+};
+
+pub const POOL_INITIALIZED_EVENT: Felt = selector!("PoolInitialized");
+pub const POSITION_UPDATED_EVENT: Felt = selector!("PositionUpdated");
+pub const SWAPPED_EVENT: Felt = selector!("Swapped");
+
+pub async fn get_pool_info<P>(
+    core_address: Felt,
+    key: PoolKey,
+    block_id: BlockId,
+    registry: &TokenRegistry,
+    provider: Arc<P>,
+) -> Result<EkuboPool, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let decimals = registry
+        .get_decimals(
+            [key.token0, key.token1],
+            block_id,
+            DEFAULT_BATCH_SIZE,
+            provider.clone(),
+        )
+        .await?;
+    let (token0_decimals, token1_decimals) = (decimals[&key.token0], decimals[&key.token1]);
+
+    let (sqrt_ratio, tick, liquidity) =
+        get_pool_state(core_address, &key, block_id, provider).await?;
+
+    Ok(EkuboPool::new(
+        core_address,
+        key,
+        token0_decimals,
+        token1_decimals,
+        sqrt_ratio,
+        tick,
+        liquidity,
+    ))
+}
+
+/// Reads the current sqrt ratio, tick and in-range liquidity of a pool from the core.
+pub async fn get_pool_state<P>(
+    core_address: Felt,
+    key: &PoolKey,
+    block_id: BlockId,
+    provider: Arc<P>,
+) -> Result<(BigUint, i32, u128), AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    // PoolPrice { sqrt_ratio: u256, tick: i129 }
+    let price = call(
+        provider.clone(),
+        core_address,
+        "get_pool_price",
+        key.to_calldata(),
+        block_id,
+    )
+    .await?;
+    let liquidity = call(
+        provider,
+        core_address,
+        "get_pool_liquidity",
+        key.to_calldata(),
+        block_id,
+    )
+    .await?;
+
+    let [sqrt_ratio_low, sqrt_ratio_high, tick_mag, tick_sign] =
+        read_result(&price, core_address, "get_pool_price", block_id)?;
+
+    Ok((
+        parse_u256(sqrt_ratio_low, sqrt_ratio_high),
+        parse_i32(tick_mag, tick_sign)?,
+        read_integer(&liquidity, core_address, "get_pool_liquidity", block_id)?,
+    ))
+}
+
+async fn call<P>(
+    provider: Arc<P>,
+    address: Felt,
+    method: &str,
+    calldata: Vec<Felt>,
+    block_id: BlockId,
+) -> Result<Vec<Felt>, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let result = call_contract(provider, address, method, calldata, block_id).await?;
+
+    if result.is_empty() {
+        return Err(AMMError::UnexpectedCallResult {
+            address,
+            method: method.to_string(),
+            block_id,
+        });
+    }
+
+    Ok(result)
+}
+
+/// Decodes a `PoolKey` from the first five felts of `data`.
+pub fn parse_pool_key(data: &[Felt]) -> Result<PoolKey, AMMError> {
+    if data.len() < 5 {
+        return Err(AMMError::PoolDataError);
+    }
+
+    Ok(PoolKey {
+        token0: data[0],
+        token1: data[1],
+        fee: parse_u128(data[2])?,
+        tick_spacing: parse_u128(data[3])?,
+        extension: data[4],
+    })
+}
+
+/// Decodes the pool key, tick bounds and liquidity delta of a `PositionUpdated` event.
+///
+/// The event data is laid out as `locker, pool_key, salt, bounds, liquidity_delta, delta`.
+pub fn parse_position_updated(data: &[Felt]) -> Result<(PoolKey, i32, i32, i128), AMMError> {
+    if data.len() < 13 {
+        return Err(AMMError::PoolDataError);
+    }
+
+    let key = parse_pool_key(&data[1..6])?;
+    let lower = parse_i32(data[7], data[8])?;
+    let upper = parse_i32(data[9], data[10])?;
+    let liquidity_delta = parse_i129(data[11], data[12])?;
+
+    Ok((key, lower, upper, liquidity_delta))
+}
+
+/// Decodes Ekubo's `i129 { mag, sign }`, where `sign` is set for negative values.
+pub fn parse_i129(mag: Felt, sign: Felt) -> Result<i128, AMMError> {
+    let mag = i128::try_from(parse_u128(mag)?).map_err(|_| AMMError::PoolDataError)?;
+
+    Ok(if sign == Felt::ZERO { mag } else { -mag })
+}
+
+/// Decodes an `i129` that has to fit in an `i32`, as ticks do.
+pub fn parse_i32(mag: Felt, sign: Felt) -> Result<i32, AMMError> {
+    i32::try_from(parse_i129(mag, sign)?).map_err(|_| AMMError::PoolDataError)
+}
+
+/// Decodes the pool key and the sqrt ratio, tick and liquidity a `Swapped` event leaves the
+/// pool at.
+///
+/// The event data is laid out as `locker, pool_key, params, delta, sqrt_ratio_after,
+/// tick_after, liquidity_after`, so the pool state is read from the end.
+pub fn parse_swapped(data: &[Felt]) -> Result<(PoolKey, BigUint, i32, u128), AMMError> {
+    if data.len() < 21 {
+        return Err(AMMError::PoolDataError);
+    }
+
+    let key = parse_pool_key(&data[1..6])?;
+    let [sqrt_ratio_low, sqrt_ratio_high, tick_mag, tick_sign, liquidity] = data[data.len() - 5..]
+    else {
+        return Err(AMMError::PoolDataError);
+    };
+
+    Ok((
+        key,
+        parse_u256(sqrt_ratio_low, sqrt_ratio_high),
+        parse_i32(tick_mag, tick_sign)?,
+        parse_u128(liquidity)?,
+    ))
+}
@@ -0,0 +1,342 @@
+//! Swap math of the Ekubo core.
+//!
+//! Ekubo prices are `sqrt(price) * 2**128` (a 128.128 fixed point), ticks are powers of
+//! `1.000001` and fees are 0.128 fixed point fractions of the input amount.
+
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+use crate::errors::{ArithmeticError, SwapSimulationError};
+
+pub const MAX_TICK: i32 = 88722883;
+pub const MIN_TICK: i32 = -MAX_TICK;
+
+/// `2**128 / sqrt(1.000001)**(2**i)`, rounded up.
+const TICK_RATIO_MULTIPLIERS: [u128; 27] = [
+    0xfffff79c8499329c7cbb2510d893283b,
+    0xffffef390978c398134b4ff3764fe410,
+    0xffffde72140b00a354bd3dc828e976ca,
+    0xffffbce42c7be6c998ad6318193c0b19,
+    0xffff79c86a8f6150a32d9778eceef97c,
+    0xfffef3911b7cff24ba1b3dbb5f8f5975,
+    0xfffde72350725cc4ea8feece3b5f13c8,
+    0xfffbce4b06c196e9247ac87695d53c60,
+    0xfff79ca7a4d1bf1ee8556cea23cdbaa6,
+    0xffef3995a5b6a6267530f207142a5764,
+    0xffde7444b28145508125d10077ba83b9,
+    0xffbceceeb791747f10df216f2e53ec57,
+    0xff79eb706b9a64c6431d76e63531e92a,
+    0xfef41d1a5f2ae3a20676bec6f7f9459a,
+    0xfde95287d26d81bea159c37073122c74,
+    0xfbd701c7cbc4c8a6bb81efd232d1e4e8,
+    0xf7bf5211c72f5185f372aeb1d48f937e,
+    0xefc2bf59df33ecc28125cf78ec4f1680,
+    0xe08d35706200796273f0b3a981d90cfe,
+    0xc4f76b68947482dc198a48a54348c4ee,
+    0x978bcb9894317807e5fa4498eee7c0fb,
+    0x59b63684b86e9f486ec54727371ba6ca,
+    0x1f703399d88f6aa83a28b22d4a1f56e4,
+    0x3dc5dac7376e20fc8679758d1bcdcfc,
+    0xee7e32d61fdb0a5e622b820f681d1,
+    0xde2ee4bc381afa7089aa84bb66,
+    0xc0d55d4d7152c25fb13a,
+];
+
+fn one_x128() -> BigUint {
+    BigUint::one() << 128
+}
+
+fn max_u256() -> BigUint {
+    (BigUint::one() << 256) - 1u32
+}
+
+fn div(a: &BigUint, denominator: &BigUint, round_up: bool) -> BigUint {
+    let quotient = a / denominator;
+    if round_up && !(a % denominator).is_zero() {
+        quotient + 1u32
+    } else {
+        quotient
+    }
+}
+
+fn muldiv(a: &BigUint, b: &BigUint, denominator: &BigUint, round_up: bool) -> BigUint {
+    div(&(a * b), denominator, round_up)
+}
+
+/// The sqrt ratio of `MIN_TICK`.
+pub fn min_sqrt_ratio() -> BigUint {
+    tick_to_sqrt_ratio(MIN_TICK).expect("MIN_TICK is in range")
+}
+
+/// The sqrt ratio of `MAX_TICK`.
+pub fn max_sqrt_ratio() -> BigUint {
+    tick_to_sqrt_ratio(MAX_TICK).expect("MAX_TICK is in range")
+}
+
+/// Returns the 128.128 sqrt ratio of the given tick.
+pub fn tick_to_sqrt_ratio(tick: i32) -> Result<BigUint, SwapSimulationError> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(SwapSimulationError::InvalidTick);
+    }
+
+    let magnitude = tick.unsigned_abs();
+    let mut ratio = one_x128();
+
+    for (i, multiplier) in TICK_RATIO_MULTIPLIERS.iter().enumerate() {
+        if magnitude & (1 << i) != 0 {
+            ratio = (ratio * multiplier) >> 128;
+        }
+    }
+
+    if tick > 0 {
+        ratio = max_u256() / ratio;
+    }
+
+    Ok(ratio)
+}
+
+/// Returns the greatest tick whose sqrt ratio is less than or equal to `sqrt_ratio`.
+pub fn sqrt_ratio_to_tick(sqrt_ratio: &BigUint) -> Result<i32, ArithmeticError> {
+    if *sqrt_ratio < min_sqrt_ratio() || *sqrt_ratio > max_sqrt_ratio() {
+        return Err(ArithmeticError::SqrtPriceOverflow);
+    }
+
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        let ratio = tick_to_sqrt_ratio(mid).map_err(|_| ArithmeticError::SqrtPriceOverflow)?;
+        if ratio <= *sqrt_ratio {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    Ok(low)
+}
+
+/// Fee charged on `amount`, rounded up.
+pub fn compute_fee(amount: &BigUint, fee: u128) -> BigUint {
+    muldiv(amount, &BigUint::from(fee), &one_x128(), true)
+}
+
+/// Amount that has to be paid so that `after_fee` is left once the fee is taken.
+pub fn amount_before_fee(after_fee: &BigUint, fee: u128) -> BigUint {
+    div(&(after_fee << 128), &(one_x128() - fee), true)
+}
+
+/// Amount of token0 between two sqrt ratios for the given liquidity.
+pub fn amount0_delta(
+    sqrt_ratio_a: &BigUint,
+    sqrt_ratio_b: &BigUint,
+    liquidity: u128,
+    round_up: bool,
+) -> BigUint {
+    let (lower, upper) = if sqrt_ratio_a < sqrt_ratio_b {
+        (sqrt_ratio_a, sqrt_ratio_b)
+    } else {
+        (sqrt_ratio_b, sqrt_ratio_a)
+    };
+
+    if lower == upper || liquidity == 0 {
+        return BigUint::zero();
+    }
+
+    let result = muldiv(
+        &(BigUint::from(liquidity) << 128),
+        &(upper - lower),
+        upper,
+        round_up,
+    );
+    div(&result, lower, round_up)
+}
+
+/// Amount of token1 between two sqrt ratios for the given liquidity.
+pub fn amount1_delta(
+    sqrt_ratio_a: &BigUint,
+    sqrt_ratio_b: &BigUint,
+    liquidity: u128,
+    round_up: bool,
+) -> BigUint {
+    let (lower, upper) = if sqrt_ratio_a < sqrt_ratio_b {
+        (sqrt_ratio_a, sqrt_ratio_b)
+    } else {
+        (sqrt_ratio_b, sqrt_ratio_a)
+    };
+
+    if lower == upper || liquidity == 0 {
+        return BigUint::zero();
+    }
+
+    muldiv(
+        &BigUint::from(liquidity),
+        &(upper - lower),
+        &one_x128(),
+        round_up,
+    )
+}
+
+/// Sqrt ratio after adding (`is_output == false`) or removing `amount` of token0,
+/// or `None` if the ratio overflows.
+pub fn next_sqrt_ratio_from_amount0(
+    sqrt_ratio: &BigUint,
+    liquidity: u128,
+    amount: &BigUint,
+    is_output: bool,
+) -> Option<BigUint> {
+    if amount.is_zero() {
+        return Some(sqrt_ratio.clone());
+    }
+
+    let numerator = BigUint::from(liquidity) << 128;
+
+    let next = if is_output {
+        let product = amount * sqrt_ratio;
+        if product >= numerator {
+            return None;
+        }
+        muldiv(&numerator, sqrt_ratio, &(&numerator - product), true)
+    } else {
+        let denominator = &numerator / sqrt_ratio + amount;
+        div(&numerator, &denominator, true)
+    };
+
+    (next <= max_u256()).then_some(next)
+}
+
+/// Sqrt ratio after adding (`is_output == false`) or removing `amount` of token1,
+/// or `None` if the ratio overflows.
+pub fn next_sqrt_ratio_from_amount1(
+    sqrt_ratio: &BigUint,
+    liquidity: u128,
+    amount: &BigUint,
+    is_output: bool,
+) -> Option<BigUint> {
+    let liquidity = BigUint::from(liquidity);
+
+    if is_output {
+        let quotient = div(&(amount << 128), &liquidity, true);
+        if quotient > *sqrt_ratio {
+            return None;
+        }
+        Some(sqrt_ratio - quotient)
+    } else {
+        let next = sqrt_ratio + (amount << 128) / liquidity;
+        (next <= max_u256()).then_some(next)
+    }
+}
+
+/// Result of swapping within a single tick range.
+#[derive(Debug, Clone)]
+pub struct SwapResult {
+    /// Amount of the specified token used up by the step.
+    pub consumed_amount: BigUint,
+    /// Amount of the other token produced (exact input) or required (exact output).
+    pub calculated_amount: BigUint,
+    pub sqrt_ratio_next: BigUint,
+    pub fee_amount: BigUint,
+}
+
+/// Port of the core's `swap_result`: trades `amount` of the specified token, or as much
+/// as fits before `sqrt_ratio_limit`.
+pub fn swap_result(
+    sqrt_ratio: &BigUint,
+    liquidity: u128,
+    sqrt_ratio_limit: &BigUint,
+    amount: &BigUint,
+    is_exact_output: bool,
+    is_token1: bool,
+    fee: u128,
+) -> SwapResult {
+    if amount.is_zero() || sqrt_ratio == sqrt_ratio_limit || liquidity == 0 {
+        let sqrt_ratio_next = if amount.is_zero() || sqrt_ratio == sqrt_ratio_limit {
+            sqrt_ratio.clone()
+        } else {
+            sqrt_ratio_limit.clone()
+        };
+
+        return SwapResult {
+            consumed_amount: BigUint::zero(),
+            calculated_amount: BigUint::zero(),
+            sqrt_ratio_next,
+            fee_amount: BigUint::zero(),
+        };
+    }
+
+    let increasing = is_exact_output != is_token1;
+
+    let price_impact_amount = if is_exact_output {
+        amount.clone()
+    } else {
+        amount - compute_fee(amount, fee)
+    };
+
+    let sqrt_ratio_next = if is_token1 {
+        next_sqrt_ratio_from_amount1(sqrt_ratio, liquidity, &price_impact_amount, is_exact_output)
+    } else {
+        next_sqrt_ratio_from_amount0(sqrt_ratio, liquidity, &price_impact_amount, is_exact_output)
+    };
+
+    if let Some(sqrt_ratio_next) = sqrt_ratio_next {
+        let within_limit = if increasing {
+            sqrt_ratio_next <= *sqrt_ratio_limit
+        } else {
+            sqrt_ratio_next >= *sqrt_ratio_limit
+        };
+
+        if within_limit {
+            let calculated_amount_excluding_fee = if is_token1 {
+                amount0_delta(&sqrt_ratio_next, sqrt_ratio, liquidity, is_exact_output)
+            } else {
+                amount1_delta(&sqrt_ratio_next, sqrt_ratio, liquidity, is_exact_output)
+            };
+
+            return if is_exact_output {
+                let including_fee = amount_before_fee(&calculated_amount_excluding_fee, fee);
+                SwapResult {
+                    consumed_amount: amount.clone(),
+                    fee_amount: &including_fee - &calculated_amount_excluding_fee,
+                    calculated_amount: including_fee,
+                    sqrt_ratio_next,
+                }
+            } else {
+                SwapResult {
+                    consumed_amount: amount.clone(),
+                    calculated_amount: calculated_amount_excluding_fee,
+                    sqrt_ratio_next,
+                    fee_amount: amount - price_impact_amount,
+                }
+            };
+        }
+    }
+
+    let (specified_amount_delta, calculated_amount_delta) = if is_token1 {
+        (
+            amount1_delta(sqrt_ratio_limit, sqrt_ratio, liquidity, !is_exact_output),
+            amount0_delta(sqrt_ratio_limit, sqrt_ratio, liquidity, is_exact_output),
+        )
+    } else {
+        (
+            amount0_delta(sqrt_ratio_limit, sqrt_ratio, liquidity, !is_exact_output),
+            amount1_delta(sqrt_ratio_limit, sqrt_ratio, liquidity, is_exact_output),
+        )
+    };
+
+    if is_exact_output {
+        let including_fee = amount_before_fee(&calculated_amount_delta, fee);
+        SwapResult {
+            consumed_amount: specified_amount_delta,
+            fee_amount: &including_fee - &calculated_amount_delta,
+            calculated_amount: including_fee,
+            sqrt_ratio_next: sqrt_ratio_limit.clone(),
+        }
+    } else {
+        let including_fee = amount_before_fee(&specified_amount_delta, fee);
+        SwapResult {
+            fee_amount: &including_fee - &specified_amount_delta,
+            consumed_amount: including_fee,
+            calculated_amount: calculated_amount_delta,
+            sqrt_ratio_next: sqrt_ratio_limit.clone(),
+        }
+    }
+}
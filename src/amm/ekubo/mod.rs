@@ -0,0 +1,4 @@
+pub mod factory;
+pub mod get_data;
+pub mod math;
+pub mod pool;
@@ -0,0 +1,472 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use async_trait::async_trait;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use serde::{Deserialize, Serialize};
+use starknet::{
+    core::{
+        crypto::compute_hash_on_elements,
+        types::{BlockId, EmittedEvent, EventFilter, Felt},
+    },
+    providers::Provider,
+};
+use tracing::instrument;
+
+use super::{
+    get_data::{
+        get_pool_info, get_pool_state, parse_position_updated, parse_swapped,
+        POSITION_UPDATED_EVENT, SWAPPED_EVENT,
+    },
+    math::{
+        max_sqrt_ratio, min_sqrt_ratio, sqrt_ratio_to_tick, swap_result, tick_to_sqrt_ratio,
+        MAX_TICK, MIN_TICK,
+    },
+};
+use crate::{
+    amm::{pool::AutomatedMarketMaker, types::Price},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+    token_registry::TokenRegistry,
+    utils::get_events,
+};
+
+/// Identifies an Ekubo pool inside the core contract.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    pub token0: Felt,
+    pub token1: Felt,
+    /// Fee as a 0.128 fixed point fraction of the input amount.
+    pub fee: u128,
+    pub tick_spacing: u128,
+    pub extension: Felt,
+}
+
+impl PoolKey {
+    /// Pedersen hash of the key, used as the pool's `address()`.
+    pub fn id(&self) -> Felt {
+        compute_hash_on_elements(&self.to_calldata())
+    }
+
+    pub fn to_calldata(&self) -> Vec<Felt> {
+        vec![
+            self.token0,
+            self.token1,
+            Felt::from(self.fee),
+            Felt::from(self.tick_spacing),
+            self.extension,
+        ]
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EkuboPool {
+    pub core_address: Felt,
+    pub key: PoolKey,
+    /// Kept in the `TokenRegistry` when serialized.
+    #[serde(skip)]
+    pub token0_decimals: u8,
+    #[serde(skip)]
+    pub token1_decimals: u8,
+    pub sqrt_ratio: BigUint,
+    pub tick: i32,
+    pub liquidity: u128,
+    /// Liquidity delta of every initialized tick, applied when the price crosses it upwards.
+    pub ticks: BTreeMap<i32, i128>,
+}
+
+/// Pool state after a simulated swap.
+struct SwapOutcome {
+    amount_in: BigUint,
+    amount_out: BigUint,
+    sqrt_ratio: BigUint,
+    tick: i32,
+    liquidity: u128,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for EkuboPool {
+    fn address(&self) -> Felt {
+        self.key.id()
+    }
+
+    fn tokens(&self) -> Vec<Felt> {
+        vec![self.key.token0, self.key.token1]
+    }
+
+    #[instrument(skip(self, provider), level = "debug")]
+    async fn sync<P>(&mut self, block_id: BlockId, provider: Arc<P>) -> Result<(), AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        let (sqrt_ratio, tick, liquidity) =
+            get_pool_state(self.core_address, &self.key, block_id, provider).await?;
+        tracing::info!(?sqrt_ratio, tick, liquidity, pool_id = ?self.address(), "Ekubo sync");
+
+        self.sqrt_ratio = sqrt_ratio;
+        self.tick = tick;
+        self.liquidity = liquidity;
+
+        Ok(())
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<Felt> {
+        vec![SWAPPED_EVENT, POSITION_UPDATED_EVENT]
+    }
+
+    /// Events are emitted by the core for every pool, so the pool key of the event must match.
+    fn sync_from_event(&mut self, event: EmittedEvent) -> Result<(), AMMError> {
+        if event.from_address != self.core_address {
+            return Err(EventLogError::UnrelatedEvent.into());
+        }
+
+        if event.keys.first() == Some(&SWAPPED_EVENT) {
+            let (key, sqrt_ratio, tick, liquidity) = parse_swapped(&event.data)?;
+            if key != self.key {
+                return Err(EventLogError::UnrelatedEvent.into());
+            }
+
+            self.sqrt_ratio = sqrt_ratio;
+            self.tick = tick;
+            self.liquidity = liquidity;
+        } else if event.keys.first() == Some(&POSITION_UPDATED_EVENT) {
+            let (key, lower, upper, liquidity_delta) = parse_position_updated(&event.data)?;
+            if key != self.key {
+                return Err(EventLogError::UnrelatedEvent.into());
+            }
+
+            self.update_position(lower, upper, liquidity_delta);
+            if lower <= self.tick && self.tick < upper {
+                self.liquidity = self
+                    .liquidity
+                    .checked_add_signed(liquidity_delta)
+                    .ok_or(SwapSimulationError::LiquidityUnderflow)?;
+            }
+        } else {
+            return Err(EventLogError::InvalidEventSignature.into());
+        }
+
+        Ok(())
+    }
+
+    fn calculate_price(&self, base_token: Felt, quote_token: Felt) -> Result<f64, AMMError> {
+        Ok(self
+            .calculate_price_exact(base_token, quote_token)?
+            .to_f64())
+    }
+
+    fn calculate_price_exact(
+        &self,
+        base_token: Felt,
+        quote_token: Felt,
+    ) -> Result<Price, AMMError> {
+        if self.sqrt_ratio.is_zero() {
+            return Err(ArithmeticError::YIsZero.into());
+        }
+
+        let price_x256 = &self.sqrt_ratio * &self.sqrt_ratio;
+        let one_x256 = BigUint::one() << 256;
+        let decimals_0 = BigUint::from(10u32).pow(self.token0_decimals.into());
+        let decimals_1 = BigUint::from(10u32).pow(self.token1_decimals.into());
+
+        if self.key.token0 == base_token {
+            if self.key.token1 != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist.into());
+            }
+            Ok(Price {
+                numerator: price_x256 * decimals_0,
+                denominator: one_x256 * decimals_1,
+            })
+        } else if self.key.token1 == base_token {
+            if self.key.token0 != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist.into());
+            }
+            Ok(Price {
+                numerator: one_x256 * decimals_1,
+                denominator: price_x256 * decimals_0,
+            })
+        } else {
+            Err(ArithmeticError::BaseTokenDoesNotExist.into())
+        }
+    }
+
+    #[allow(unused)]
+    async fn simulate_swap<P>(
+        &self,
+        base_token: Felt,
+        amount_in: Felt,
+        provider: Arc<P>,
+    ) -> Result<Felt, AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let quote_token = if self.key.token0 == base_token {
+            self.key.token1
+        } else {
+            self.key.token0
+        };
+        let is_token1 = self.is_token1_in(base_token, quote_token)?;
+        let outcome = self.swap(amount_in.to_biguint(), false, is_token1)?;
+
+        Ok(Felt::from(outcome.amount_out))
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_in: Felt,
+    ) -> Result<Felt, AMMError> {
+        let is_token1 = self.is_token1_in(base_token, quote_token)?;
+        let outcome = self.swap(amount_in.to_biguint(), false, is_token1)?;
+
+        self.apply(&outcome);
+
+        Ok(Felt::from(outcome.amount_out))
+    }
+
+    fn simulate_swap_exact_out(
+        &self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_out: Felt,
+    ) -> Result<Felt, AMMError> {
+        let is_token1_in = self.is_token1_in(base_token, quote_token)?;
+        let outcome = self.swap(amount_out.to_biguint(), true, !is_token1_in)?;
+
+        if outcome.amount_out != amount_out.to_biguint() {
+            return Err(SwapSimulationError::LiquidityUnderflow.into());
+        }
+
+        Ok(Felt::from(outcome.amount_in))
+    }
+
+    fn simulate_swap_exact_out_mut(
+        &mut self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_out: Felt,
+    ) -> Result<Felt, AMMError> {
+        let is_token1_in = self.is_token1_in(base_token, quote_token)?;
+        let outcome = self.swap(amount_out.to_biguint(), true, !is_token1_in)?;
+
+        if outcome.amount_out != amount_out.to_biguint() {
+            return Err(SwapSimulationError::LiquidityUnderflow.into());
+        }
+
+        self.apply(&outcome);
+
+        Ok(Felt::from(outcome.amount_in))
+    }
+}
+
+impl EkuboPool {
+    pub fn new(
+        core_address: Felt,
+        key: PoolKey,
+        token0_decimals: u8,
+        token1_decimals: u8,
+        sqrt_ratio: BigUint,
+        tick: i32,
+        liquidity: u128,
+    ) -> EkuboPool {
+        EkuboPool {
+            core_address,
+            key,
+            token0_decimals,
+            token1_decimals,
+            sqrt_ratio,
+            tick,
+            liquidity,
+            ticks: BTreeMap::new(),
+        }
+    }
+
+    pub async fn new_from_key<P>(
+        core_address: Felt,
+        key: PoolKey,
+        creation_block: u64,
+        provider: Arc<P>,
+    ) -> Result<Self, AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        // Pin the state and the tick data to the same block.
+        let block_id = BlockId::Number(provider.block_number().await?);
+        let mut pool = get_pool_info(
+            core_address,
+            key,
+            block_id,
+            &TokenRegistry::default(),
+            provider.clone(),
+        )
+        .await?;
+        pool.populate_tick_data(creation_block, block_id, provider)
+            .await?;
+
+        Ok(pool)
+    }
+
+    /// Applies the core's `PositionUpdated` events for this pool in the block range to `ticks`.
+    pub async fn populate_tick_data<P>(
+        &mut self,
+        from_block: u64,
+        to_block: BlockId,
+        provider: Arc<P>,
+    ) -> Result<(), AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        let filter = EventFilter {
+            from_block: Some(BlockId::Number(from_block)),
+            to_block: Some(to_block),
+            address: Some(self.core_address),
+            keys: Some(vec![vec![POSITION_UPDATED_EVENT]]),
+        };
+
+        for event in get_events(provider, filter, 1000).await? {
+            let (key, lower, upper, liquidity_delta) = parse_position_updated(&event.data)?;
+            if key == self.key {
+                self.update_position(lower, upper, liquidity_delta);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies a liquidity delta to the ticks bounding a position.
+    ///
+    /// The active `liquidity` is not touched, it is read from the core on `sync`.
+    pub fn update_position(&mut self, lower: i32, upper: i32, liquidity_delta: i128) {
+        for (tick, delta) in [(lower, liquidity_delta), (upper, -liquidity_delta)] {
+            let entry = self.ticks.entry(tick).or_default();
+            *entry += delta;
+            if *entry == 0 {
+                self.ticks.remove(&tick);
+            }
+        }
+    }
+
+    fn is_token1_in(&self, base_token: Felt, quote_token: Felt) -> Result<bool, ArithmeticError> {
+        if self.key.token0 == base_token {
+            if self.key.token1 != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist);
+            }
+            Ok(false)
+        } else if self.key.token1 == base_token {
+            if self.key.token0 != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist);
+            }
+            Ok(true)
+        } else {
+            Err(ArithmeticError::BaseTokenDoesNotExist)
+        }
+    }
+
+    fn apply(&mut self, outcome: &SwapOutcome) {
+        self.sqrt_ratio = outcome.sqrt_ratio.clone();
+        self.tick = outcome.tick;
+        self.liquidity = outcome.liquidity;
+    }
+
+    /// Walks the initialized ticks the same way the core's `swap` does, without a price limit.
+    ///
+    /// `amount` is of token1 when `is_token1` is set, and is an output amount when
+    /// `is_exact_output` is set. Ticks are taken from `ticks` directly rather than from
+    /// the core's bitmap words, so a step never stops at a word boundary.
+    fn swap(
+        &self,
+        amount: BigUint,
+        is_exact_output: bool,
+        is_token1: bool,
+    ) -> Result<SwapOutcome, SwapSimulationError> {
+        let increasing = is_exact_output != is_token1;
+        let sqrt_ratio_limit = if increasing {
+            max_sqrt_ratio()
+        } else {
+            min_sqrt_ratio()
+        };
+
+        let mut amount_remaining = amount;
+        let mut calculated_amount = BigUint::zero();
+        let mut specified_amount = BigUint::zero();
+        let mut sqrt_ratio = self.sqrt_ratio.clone();
+        let mut tick = self.tick;
+        let mut liquidity = self.liquidity;
+
+        while !amount_remaining.is_zero() && sqrt_ratio != sqrt_ratio_limit {
+            let (next_tick, initialized) = if increasing {
+                match self.ticks.range(tick + 1..).next() {
+                    Some((next_tick, _)) => (*next_tick, true),
+                    None => (MAX_TICK, false),
+                }
+            } else {
+                match self.ticks.range(..=tick).next_back() {
+                    Some((next_tick, _)) => (*next_tick, true),
+                    None => (MIN_TICK, false),
+                }
+            };
+            let next_tick_sqrt_ratio = tick_to_sqrt_ratio(next_tick)?;
+
+            let step_limit = if increasing {
+                next_tick_sqrt_ratio.clone().min(sqrt_ratio_limit.clone())
+            } else {
+                next_tick_sqrt_ratio.clone().max(sqrt_ratio_limit.clone())
+            };
+
+            let step = swap_result(
+                &sqrt_ratio,
+                liquidity,
+                &step_limit,
+                &amount_remaining,
+                is_exact_output,
+                is_token1,
+                self.key.fee,
+            );
+
+            if step.consumed_amount >= amount_remaining {
+                amount_remaining = BigUint::zero();
+            } else {
+                amount_remaining -= &step.consumed_amount;
+            }
+            specified_amount += step.consumed_amount;
+            calculated_amount += step.calculated_amount;
+
+            if step.sqrt_ratio_next == next_tick_sqrt_ratio {
+                sqrt_ratio = step.sqrt_ratio_next;
+                tick = if increasing { next_tick } else { next_tick - 1 };
+
+                if initialized {
+                    let liquidity_delta = self.ticks[&next_tick];
+                    let liquidity_delta = if increasing {
+                        liquidity_delta
+                    } else {
+                        -liquidity_delta
+                    };
+                    liquidity = if liquidity_delta < 0 {
+                        liquidity.checked_sub(liquidity_delta.unsigned_abs())
+                    } else {
+                        liquidity.checked_add(liquidity_delta as u128)
+                    }
+                    .ok_or(SwapSimulationError::LiquidityUnderflow)?;
+                }
+            } else if sqrt_ratio != step.sqrt_ratio_next {
+                sqrt_ratio = step.sqrt_ratio_next;
+                tick = sqrt_ratio_to_tick(&sqrt_ratio)?;
+            }
+        }
+
+        let (amount_in, amount_out) = if is_exact_output {
+            (calculated_amount, specified_amount)
+        } else {
+            (specified_amount, calculated_amount)
+        };
+
+        Ok(SwapOutcome {
+            amount_in,
+            amount_out,
+            sqrt_ratio,
+            tick,
+            liquidity,
+        })
+    }
+}
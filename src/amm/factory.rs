@@ -1,15 +1,25 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use futures::stream::FuturesUnordered;
 use serde::{Deserialize, Serialize};
 use starknet::{
-    core::types::{BlockId, EventFilter, Felt},
+    core::types::{BlockId, EmittedEvent, EventFilter, Felt},
     providers::Provider,
 };
 
-use super::{jediswap::factory::JediswapFactory, pool::AMM, tenkswap::factory::TenKFactory};
-use crate::errors::AMMError;
+use super::{
+    ekubo::factory::EkuboFactory, jediswap::factory::JediswapFactory,
+    jediswap_v2::factory::JediswapV2Factory, myswap::factory::MySwapFactory, pool::AMM,
+    sithswap::factory::SithSwapFactory, tenkswap::factory::TenKFactory,
+};
+use crate::{
+    errors::{AMMError, EventLogError},
+    token_registry::TokenRegistry,
+    utils::get_events,
+};
+
+/// Number of events requested per `get_events` page when scanning for created pools.
+pub const EVENTS_CHUNK_SIZE: u64 = 1000;
 
 #[async_trait]
 pub trait AutomatedMarketMakerFactory {
@@ -22,11 +32,16 @@ pub trait AutomatedMarketMakerFactory {
 
     fn amm_created_event_signature(&self) -> Vec<Vec<Felt>>;
 
-    /// Populates all AMMs data via batched static calls.
+    /// Decodes a pool creation event into an `AMM` holding only what the event carries.
+    ///
+    /// The returned AMM has to be populated through `populate_amm_data` before it is used.
+    fn new_empty_amm_from_log(&self, log: EmittedEvent) -> Result<AMM, AMMError>;
+
+    /// Populates all AMMs data via batched static calls, reading every AMM at `block_id`.
     async fn populate_amm_data<P>(
         &self,
         amms: &mut [AMM],
-        block_number: Option<u64>,
+        block_id: BlockId,
         provider: Arc<P>,
     ) -> Result<(), AMMError>
     where
@@ -64,11 +79,17 @@ macro_rules! factory {
                 }
             }
 
+            fn new_empty_amm_from_log(&self, log: EmittedEvent) -> Result<AMM, AMMError> {
+                match self {
+                    $(Factory::$factory_type(factory) => factory.new_empty_amm_from_log(log),)+
+                }
+            }
+
 
             async fn populate_amm_data<P>(
                 &self,
                 amms: &mut [AMM],
-                block_number: Option<u64>,
+                block_id: BlockId,
                 provider: Arc<P>,
             ) -> Result<(), AMMError>
             where
@@ -76,13 +97,24 @@ macro_rules! factory {
             {
                 match self {
                     $(Factory::$factory_type(factory) => {
-                        factory.populate_amm_data(amms, block_number, provider).await
+                        factory.populate_amm_data(amms, block_id, provider).await
                     },)+
                 }
             }
         }
 
 
+        impl Factory {
+            /// Makes the factory cache token metadata in `token_registry`.
+            pub fn with_token_registry(self, token_registry: TokenRegistry) -> Factory {
+                match self {
+                    $(Factory::$factory_type(factory) => {
+                        Factory::$factory_type(factory.with_token_registry(token_registry))
+                    },)+
+                }
+            }
+        }
+
         impl PartialEq for Factory {
             fn eq(&self, other: &Self) -> bool {
                 self.address() == other.address()
@@ -93,10 +125,20 @@ macro_rules! factory {
     };
 }
 
-factory!(JediswapFactory, TenKFactory);
+factory!(
+    JediswapFactory,
+    TenKFactory,
+    JediswapV2Factory,
+    EkuboFactory,
+    MySwapFactory,
+    SithSwapFactory
+);
 
 impl Factory {
-    #[allow(unused)]
+    /// Returns an empty `AMM` for every pool the factory created in `from_block..=to_block`.
+    ///
+    /// The range is scanned in windows of `step` blocks, each paged through with
+    /// `EVENTS_CHUNK_SIZE` events per request.
     pub async fn get_all_pools_from_logs<P>(
         &self,
         mut from_block: u64,
@@ -105,41 +147,43 @@ impl Factory {
         provider: Arc<P>,
     ) -> Result<Vec<AMM>, AMMError>
     where
-        P: Provider,
+        P: Provider + Send + Sync,
     {
-        let factory_address = self.address();
         let amm_created_event_signature = self.amm_created_event_signature();
-        let mut futures = FuturesUnordered::new();
-
         let mut aggregated_amms: Vec<AMM> = vec![];
 
-        while from_block < to_block {
-            let provider = provider.clone();
-            let mut target_block = from_block + step - 1;
-            if target_block > to_block {
-                target_block = to_block;
-            }
+        // Factories without a creation event can only be enumerated through `fetch_all_pools`.
+        if amm_created_event_signature.is_empty() {
+            return Ok(aggregated_amms);
+        }
+
+        let step = step.max(1);
+        while from_block <= to_block {
+            let target_block = from_block.saturating_add(step - 1).min(to_block);
 
             let filter = EventFilter {
                 from_block: Some(BlockId::Number(from_block)),
-                to_block: Some(BlockId::Number(to_block)),
-                address: Some(factory_address),
-                keys: Some(self.amm_created_event_signature()),
+                to_block: Some(BlockId::Number(target_block)),
+                address: Some(self.address()),
+                keys: Some(amm_created_event_signature.clone()),
             };
 
-            futures.push(async move { provider.get_events(filter, None, 10).await });
+            for log in get_events(provider.clone(), filter, EVENTS_CHUNK_SIZE).await? {
+                aggregated_amms.push(self.new_empty_amm_from_log(log)?);
+            }
 
-            // from_block += step;
+            from_block = target_block + 1;
         }
 
-        // while let Some(result) = futures.next().await {
-        //     let logs = result.map_err(AMMError::TransportError)?;
-        //
-        //     for log in logs {
-        //         aggregated_amms.push(self.new_empty_amm_from_log(log).unwrap());
-        //     }
-        // }
-
         Ok(aggregated_amms)
     }
 }
+
+/// Checks that `log` carries the expected event selector as its first key.
+pub(crate) fn check_event_selector(log: &EmittedEvent, selector: Felt) -> Result<(), AMMError> {
+    if log.keys.first() == Some(&selector) {
+        Ok(())
+    } else {
+        Err(EventLogError::InvalidEventSignature.into())
+    }
+}
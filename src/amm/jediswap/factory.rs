@@ -1,22 +1,36 @@
-use std::{sync::Arc, time::Duration};
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use starknet::{core::types::Felt, providers::Provider};
+use starknet::{
+    core::types::{BlockId, EmittedEvent, Felt},
+    providers::Provider,
+};
 
 use crate::{
     amm::{
-        factory::AutomatedMarketMakerFactory,
-        pool::{AutomatedMarketMaker, AMM},
+        factory::{check_event_selector, AutomatedMarketMakerFactory},
+        pool::AMM,
     },
     errors::AMMError,
+    token_registry::TokenRegistry,
+    utils::{default_batch_size, DEFAULT_BATCH_SIZE},
 };
 
-use super::get_data::{get_all_pools, get_pool_info};
+use super::{
+    get_data::{batch_populate_pools, get_all_pools, get_pool_info, PAIR_CREATED_EVENT, PAIR_FEE},
+    pool::JediswapPool,
+};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct JediswapFactory {
     pub factory_address: Felt,
+    /// Maximum number of calls sent per JSON-RPC batch by `populate_amm_data`.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Cache of token metadata, shared with the other factories it is cloned into.
+    #[serde(skip)]
+    pub token_registry: TokenRegistry,
 }
 
 #[async_trait]
@@ -29,18 +43,18 @@ impl AutomatedMarketMakerFactory for JediswapFactory {
     where
         P: Provider + Sync + Send,
     {
-        let pool_addresses = get_all_pools(self, provider.clone()).await.unwrap();
+        let block_id = BlockId::Number(provider.block_number().await?);
+        let pool_addresses = get_all_pools(self, block_id, provider.clone()).await?;
         let mut all_pools = vec![];
-        let mut first_val = true;
 
         for pool_address in pool_addresses {
-            if first_val {
-                first_val = false;
-                continue;
-            }
-            let pool = get_pool_info(pool_address, provider.clone()).await.unwrap();
-
-            tokio::time::sleep(Duration::from_millis(200)).await;
+            let pool = get_pool_info(
+                pool_address,
+                block_id,
+                &self.token_registry,
+                provider.clone(),
+            )
+            .await?;
             all_pools.push(AMM::JediswapPool(pool));
         }
         Ok(all_pools)
@@ -49,27 +63,59 @@ impl AutomatedMarketMakerFactory for JediswapFactory {
     async fn populate_amm_data<P>(
         &self,
         amms: &mut [AMM],
-        _block_number: Option<u64>,
+        block_id: BlockId,
         middleware: Arc<P>,
     ) -> Result<(), AMMError>
     where
         P: Provider + Sync + Send,
     {
-        for amm in amms {
-            get_pool_info(amm.address(), middleware.clone())
-                .await
-                .unwrap();
-        }
-        Ok(())
+        batch_populate_pools(
+            amms,
+            block_id,
+            self.batch_size,
+            &self.token_registry,
+            middleware,
+        )
+        .await
     }
 
     fn amm_created_event_signature(&self) -> Vec<Vec<Felt>> {
-        vec![vec![Felt::ONE]]
+        vec![vec![PAIR_CREATED_EVENT]]
+    }
+
+    fn new_empty_amm_from_log(&self, log: EmittedEvent) -> Result<AMM, AMMError> {
+        check_event_selector(&log, PAIR_CREATED_EVENT)?;
+
+        // PairCreated { token0, token1, pair, total_pairs }
+        match log.data[..] {
+            [token_a, token_b, pool_address, ..] => Ok(AMM::JediswapPool(JediswapPool {
+                pool_address,
+                token_a,
+                token_b,
+                fee: PAIR_FEE,
+                ..Default::default()
+            })),
+            _ => Err(AMMError::UnrecognizedPoolCreatedEventLog),
+        }
     }
 }
 
 impl JediswapFactory {
     pub fn new(factory_address: Felt) -> JediswapFactory {
-        JediswapFactory { factory_address }
+        JediswapFactory {
+            factory_address,
+            batch_size: DEFAULT_BATCH_SIZE,
+            token_registry: TokenRegistry::default(),
+        }
+    }
+
+    pub fn with_token_registry(mut self, token_registry: TokenRegistry) -> JediswapFactory {
+        self.token_registry = token_registry;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> JediswapFactory {
+        self.batch_size = batch_size;
+        self
     }
 }
@@ -1,78 +1,155 @@
-use std::{io::Stderr, sync::Arc};
+use std::sync::Arc;
 
 use starknet::{
-    core::types::{Felt, StarknetError},
+    core::types::{BlockId, Felt},
+    macros::selector,
     providers::Provider,
 };
 
-use crate::{amm::factory::AutomatedMarketMakerFactory, utils::call_contract};
+use crate::{
+    amm::{factory::AutomatedMarketMakerFactory, pool::AMM},
+    errors::AMMError,
+    token_registry::TokenRegistry,
+    utils::{
+        batch_call, batch_get_pair_tokens, call_contract, function_call, parse_u128, read_result,
+        DEFAULT_BATCH_SIZE,
+    },
+};
 
 use super::{factory::JediswapFactory, pool::JediswapPool};
 
+pub const PAIR_CREATED_EVENT: Felt = selector!("PairCreated");
+pub const SYNC_EVENT: Felt = selector!("Sync");
+pub const SWAP_EVENT: Felt = selector!("Swap");
+pub const MINT_EVENT: Felt = selector!("Mint");
+pub const BURN_EVENT: Felt = selector!("Burn");
+
+/// Fee of every pair, 0.3% of the input. Pairs do not expose it, as it is fixed in the contract.
+pub const PAIR_FEE: u32 = 300;
+
 pub async fn get_pool_info<P>(
     pool_address: Felt,
+    block_id: BlockId,
+    registry: &TokenRegistry,
     provider: Arc<P>,
-) -> Result<JediswapPool, StarknetError>
+) -> Result<JediswapPool, AMMError>
 where
     P: Provider + Send + Sync,
 {
-    let token0 = call_contract(provider.clone(), pool_address, "token0", vec![])
-        .await
-        .unwrap();
-    let token_0_address = token0[0];
-
-    let token1 = call_contract(provider.clone(), pool_address, "token1", vec![])
-        .await
-        .unwrap();
-    let token_1_address = token1[0];
-
-    let token0_decimals = call_contract(provider.clone(), token_0_address, "decimals", vec![])
-        .await
-        .unwrap()[0];
-    let token0_decimals_parsed =
-        u8::from_le_bytes(token0_decimals.to_bytes_le()[0..1].try_into().unwrap());
+    let token0 = call_contract(provider.clone(), pool_address, "token0", vec![], block_id).await?;
+    let [token_0_address] = read_result(&token0, pool_address, "token0", block_id)?;
 
-    let token1_decimals = call_contract(provider.clone(), token_1_address, "decimals", vec![])
-        .await
-        .unwrap()[0];
+    let token1 = call_contract(provider.clone(), pool_address, "token1", vec![], block_id).await?;
+    let [token_1_address] = read_result(&token1, pool_address, "token1", block_id)?;
 
-    let token1_decimals_parsed =
-        u8::from_le_bytes(token1_decimals.to_bytes_le()[0..1].try_into().unwrap());
+    let decimals = registry
+        .get_decimals(
+            [token_0_address, token_1_address],
+            block_id,
+            DEFAULT_BATCH_SIZE,
+            provider.clone(),
+        )
+        .await?;
 
-    // print!(
-    //     " {:?} {:?} {:?} {:?}",
-    //     token_0_address, token_1_address, token0_decimals_parsed, token1_decimals_parsed
-    // );
-
-    let reserves_result = call_contract(provider.clone(), pool_address, "get_reserves", vec![])
-        .await
-        .unwrap();
-
-    let reserve_a = Felt::from_bytes_le(&reserves_result[0].to_bytes_le());
-    let reserve_b = Felt::from_bytes_le(&reserves_result[2].to_bytes_le());
+    // (reserve0: Uint256, reserve1: Uint256, block_timestamp_last)
+    let reserves = call_contract(provider, pool_address, "get_reserves", vec![], block_id).await?;
+    let [reserve_a, _, reserve_b] = read_result(&reserves, pool_address, "get_reserves", block_id)?;
 
     Ok(JediswapPool::new(
         pool_address,
         token_0_address,
         token_1_address,
-        token0_decimals_parsed,
-        token1_decimals_parsed,
+        decimals[&token_0_address],
+        decimals[&token_1_address],
         reserve_a,
         reserve_b,
-        0u32,
+        PAIR_FEE,
     ))
 }
 
+/// Returns the addresses of every pair deployed by the factory.
 pub async fn get_all_pools<P>(
-    factory: &mut JediswapFactory,
+    factory: &JediswapFactory,
+    block_id: BlockId,
     provider: Arc<P>,
-) -> Result<Vec<Felt>, Stderr>
+) -> Result<Vec<Felt>, AMMError>
 where
     P: Provider + Send + Sync,
 {
-    let all_pairs = call_contract(provider.clone(), factory.address(), "get_all_pairs", vec![])
-        .await
-        .unwrap();
-    println!("all pair addresses {:?}", all_pairs);
-    Ok(all_pairs)
+    let address = factory.address();
+    let all_pairs = call_contract(provider, address, "get_all_pairs", vec![], block_id).await?;
+
+    // Array<ContractAddress>, prefixed by its length.
+    match all_pairs.split_first() {
+        Some((len, pairs)) if parse_u128(*len).ok() == Some(pairs.len() as u128) => {
+            Ok(pairs.to_vec())
+        }
+        _ => Err(AMMError::UnexpectedCallResult {
+            address,
+            method: "get_all_pairs".to_string(),
+            block_id,
+        }),
+    }
+}
+
+/// Refreshes the tokens, decimals and reserves of every `JediswapPool` in `amms` through
+/// batched calls of at most `batch_size` requests, and sets their fee to `PAIR_FEE`.
+pub async fn batch_populate_pools<P>(
+    amms: &mut [AMM],
+    block_id: BlockId,
+    batch_size: usize,
+    registry: &TokenRegistry,
+    provider: Arc<P>,
+) -> Result<(), AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let mut pools = amms
+        .iter_mut()
+        .filter_map(|amm| match amm {
+            AMM::JediswapPool(pool) => Some(pool),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    // Pools only known by address still need their tokens.
+    let (unresolved, pairs): (Vec<usize>, Vec<Felt>) = pools
+        .iter()
+        .enumerate()
+        .filter(|(_, pool)| pool.token_a == Felt::ZERO)
+        .map(|(idx, pool)| (idx, pool.pool_address))
+        .unzip();
+    let tokens = batch_get_pair_tokens(provider.clone(), &pairs, block_id, batch_size).await?;
+    for (idx, (token_a, token_b)) in unresolved.into_iter().zip(tokens) {
+        pools[idx].token_a = token_a;
+        pools[idx].token_b = token_b;
+    }
+
+    let decimals = registry
+        .get_decimals(
+            pools.iter().flat_map(|pool| [pool.token_a, pool.token_b]),
+            block_id,
+            batch_size,
+            provider.clone(),
+        )
+        .await?;
+
+    let calls = pools
+        .iter()
+        .map(|pool| function_call(pool.pool_address, "get_reserves", vec![]))
+        .collect::<Result<Vec<_>, _>>()?;
+    let reserves = batch_call(provider, calls, block_id, batch_size).await?;
+
+    for (pool, reserves) in pools.into_iter().zip(reserves) {
+        // (reserve0: Uint256, reserve1: Uint256, block_timestamp_last)
+        let [reserve_a, _, reserve_b] =
+            read_result(&reserves, pool.pool_address, "get_reserves", block_id)?;
+        pool.token_a_decimals = decimals[&pool.token_a];
+        pool.token_b_decimals = decimals[&pool.token_b];
+        pool.reserve_a = reserve_a;
+        pool.reserve_b = reserve_b;
+        pool.fee = PAIR_FEE;
+    }
+
+    Ok(())
 }
@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use starknet::{
+    core::types::{BlockId, EmittedEvent, EventFilter, Felt},
+    providers::Provider,
+};
+
+use crate::{
+    amm::{
+        factory::{check_event_selector, AutomatedMarketMakerFactory},
+        pool::{AutomatedMarketMaker, AMM},
+    },
+    errors::AMMError,
+    token_registry::TokenRegistry,
+    utils::{call_contract, get_events, parse_u128},
+};
+
+use super::{
+    get_data::{get_pool_info, POOL_CREATED_EVENT},
+    pool::JediswapV2Pool,
+};
+
+/// Fee tiers enabled on the Jediswap V2 factory, in hundredths of a bip.
+pub const FEE_TIERS: [u32; 4] = [100, 500, 3000, 10000];
+/// Number of pools whose tick data is queried concurrently.
+pub const TICK_DATA_BATCH_SIZE: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JediswapV2Factory {
+    pub factory_address: Felt,
+    pub creation_block: u64,
+    /// Cache of token metadata, shared with the other factories it is cloned into.
+    #[serde(skip)]
+    pub token_registry: TokenRegistry,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for JediswapV2Factory {
+    fn address(&self) -> Felt {
+        self.factory_address
+    }
+
+    async fn fetch_all_pools<P>(&mut self, provider: Arc<P>) -> Result<Vec<AMM>, AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let block_id = BlockId::Number(provider.block_number().await?);
+        let filter = EventFilter {
+            from_block: Some(BlockId::Number(self.creation_block)),
+            to_block: Some(block_id),
+            address: Some(self.factory_address),
+            keys: Some(self.amm_created_event_signature()),
+        };
+
+        let mut all_pools = vec![];
+        for event in get_events(provider.clone(), filter, 1000).await? {
+            // PoolCreated { token0, token1, fee, tick_spacing, pool }
+            let pool_address = *event
+                .data
+                .get(4)
+                .ok_or(AMMError::UnrecognizedPoolCreatedEventLog)?;
+
+            all_pools.push(
+                get_pool_info(
+                    pool_address,
+                    block_id,
+                    &self.token_registry,
+                    provider.clone(),
+                )
+                .await?,
+            );
+        }
+
+        self.populate_tick_data(
+            all_pools.iter_mut(),
+            self.creation_block,
+            block_id,
+            provider,
+        )
+        .await?;
+
+        Ok(all_pools.into_iter().map(AMM::JediswapV2Pool).collect())
+    }
+
+    async fn populate_amm_data<P>(
+        &self,
+        amms: &mut [AMM],
+        block_id: BlockId,
+        middleware: Arc<P>,
+    ) -> Result<(), AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let mut pools = vec![];
+        for amm in amms.iter() {
+            pools.push(
+                get_pool_info(
+                    amm.address(),
+                    block_id,
+                    &self.token_registry,
+                    middleware.clone(),
+                )
+                .await?,
+            );
+        }
+
+        self.populate_tick_data(pools.iter_mut(), self.creation_block, block_id, middleware)
+            .await?;
+
+        for (amm, pool) in amms.iter_mut().zip(pools) {
+            *amm = AMM::JediswapV2Pool(pool);
+        }
+
+        Ok(())
+    }
+
+    fn amm_created_event_signature(&self) -> Vec<Vec<Felt>> {
+        vec![vec![POOL_CREATED_EVENT]]
+    }
+
+    fn new_empty_amm_from_log(&self, log: EmittedEvent) -> Result<AMM, AMMError> {
+        check_event_selector(&log, POOL_CREATED_EVENT)?;
+
+        // PoolCreated { token0, token1, fee, tick_spacing, pool }
+        match log.data[..] {
+            [token_a, token_b, fee, tick_spacing, pool_address, ..] => {
+                Ok(AMM::JediswapV2Pool(JediswapV2Pool {
+                    pool_address,
+                    token_a,
+                    token_b,
+                    fee: u32::try_from(parse_u128(fee)?)
+                        .map_err(|_| AMMError::UnrecognizedPoolCreatedEventLog)?,
+                    tick_spacing: i32::try_from(parse_u128(tick_spacing)?)
+                        .map_err(|_| AMMError::UnrecognizedPoolCreatedEventLog)?,
+                    ..Default::default()
+                }))
+            }
+            _ => Err(AMMError::UnrecognizedPoolCreatedEventLog),
+        }
+    }
+}
+
+impl JediswapV2Factory {
+    pub fn new(factory_address: Felt, creation_block: u64) -> JediswapV2Factory {
+        JediswapV2Factory {
+            factory_address,
+            creation_block,
+            token_registry: TokenRegistry::default(),
+        }
+    }
+
+    pub fn with_token_registry(mut self, token_registry: TokenRegistry) -> JediswapV2Factory {
+        self.token_registry = token_registry;
+        self
+    }
+
+    /// Syncs pools restored from a checkpoint taken at `from_block - 1` up to `block_id`.
+    ///
+    /// The price state is read again, while the tick data the pools already hold is only
+    /// updated with the `Mint` and `Burn` events emitted since.
+    pub async fn sync_amm_data<P>(
+        &self,
+        amms: &mut [AMM],
+        from_block: u64,
+        block_id: BlockId,
+        provider: Arc<P>,
+    ) -> Result<(), AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let mut pools = vec![];
+        for amm in amms.iter_mut() {
+            if let AMM::JediswapV2Pool(pool) = amm {
+                pool.sync(block_id, provider.clone()).await?;
+                pools.push(pool);
+            }
+        }
+
+        self.populate_tick_data(pools, from_block, block_id, provider)
+            .await
+    }
+
+    /// Applies the `Mint` and `Burn` events of each pool in the block range to its ticks.
+    ///
+    /// Events are queried by pool address, for `TICK_DATA_BATCH_SIZE` pools at a time.
+    pub async fn populate_tick_data<'a, P>(
+        &self,
+        pools: impl IntoIterator<Item = &'a mut JediswapV2Pool>,
+        from_block: u64,
+        to_block: BlockId,
+        provider: Arc<P>,
+    ) -> Result<(), AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let mut pools: Vec<&mut JediswapV2Pool> = pools.into_iter().collect();
+
+        for batch in pools.chunks_mut(TICK_DATA_BATCH_SIZE) {
+            try_join_all(
+                batch
+                    .iter_mut()
+                    .map(|pool| pool.populate_tick_data(from_block, to_block, provider.clone())),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the pools deployed for a token pair across every fee tier.
+    pub async fn get_pools_for_pair<P>(
+        &self,
+        token_a: Felt,
+        token_b: Felt,
+        provider: Arc<P>,
+    ) -> Result<Vec<AMM>, AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let mut pools = vec![];
+        let block_id = BlockId::Number(provider.block_number().await?);
+
+        for fee in FEE_TIERS {
+            let pool_address = call_contract(
+                provider.clone(),
+                self.factory_address,
+                "get_pool",
+                vec![token_a, token_b, Felt::from(fee)],
+                block_id,
+            )
+            .await?
+            .first()
+            .copied()
+            .unwrap_or(Felt::ZERO);
+
+            if pool_address == Felt::ZERO {
+                continue;
+            }
+
+            let pool = JediswapV2Pool::new_from_address(
+                pool_address,
+                self.creation_block,
+                provider.clone(),
+            )
+            .await?;
+            pools.push(AMM::JediswapV2Pool(pool));
+        }
+
+        Ok(pools)
+    }
+}
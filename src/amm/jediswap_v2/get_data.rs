@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use num_bigint::BigUint;
+use starknet::{
+    core::types::{BlockId, EmittedEvent, Felt},
+    macros::selector,
+    providers::Provider,
+};
+
+use super::pool::JediswapV2Pool;
+use crate::{
+    errors::AMMError,
+    token_registry::TokenRegistry,
+    utils::{call_contract, parse_u128, parse_u256, read_integer, read_result, DEFAULT_BATCH_SIZE},
+};
+
+pub const POOL_CREATED_EVENT: Felt = selector!("PoolCreated");
+pub const MINT_EVENT: Felt = selector!("Mint");
+pub const BURN_EVENT: Felt = selector!("Burn");
+pub const SWAP_EVENT: Felt = selector!("Swap");
+
+pub async fn get_pool_info<P>(
+    pool_address: Felt,
+    block_id: BlockId,
+    registry: &TokenRegistry,
+    provider: Arc<P>,
+) -> Result<JediswapV2Pool, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let token_0_address = call(provider.clone(), pool_address, "get_token0", block_id).await?[0];
+    let token_1_address = call(provider.clone(), pool_address, "get_token1", block_id).await?[0];
+
+    let decimals = registry
+        .get_decimals(
+            [token_0_address, token_1_address],
+            block_id,
+            DEFAULT_BATCH_SIZE,
+            provider.clone(),
+        )
+        .await?;
+
+    let fee = call(provider.clone(), pool_address, "get_fee", block_id).await?;
+    let tick_spacing = call(provider.clone(), pool_address, "get_tick_spacing", block_id).await?;
+
+    let (sqrt_price, tick, liquidity) = get_slot_state(pool_address, block_id, provider).await?;
+
+    Ok(JediswapV2Pool::new(
+        pool_address,
+        token_0_address,
+        token_1_address,
+        decimals[&token_0_address],
+        decimals[&token_1_address],
+        liquidity,
+        sqrt_price,
+        read_integer(&fee, pool_address, "get_fee", block_id)?,
+        tick,
+        read_integer(&tick_spacing, pool_address, "get_tick_spacing", block_id)?,
+    ))
+}
+
+/// Reads the current sqrt price, tick and in-range liquidity of a pool.
+pub async fn get_slot_state<P>(
+    pool_address: Felt,
+    block_id: BlockId,
+    provider: Arc<P>,
+) -> Result<(BigUint, i32, u128), AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let sqrt_price = call(
+        provider.clone(),
+        pool_address,
+        "get_sqrt_price_X96",
+        block_id,
+    )
+    .await?;
+    let tick = call(provider.clone(), pool_address, "get_tick", block_id).await?;
+    let liquidity = call(provider, pool_address, "get_liquidity", block_id).await?;
+
+    let [sqrt_price_low, sqrt_price_high] =
+        read_result(&sqrt_price, pool_address, "get_sqrt_price_X96", block_id)?;
+    let [tick_mag, tick_sign] = read_result(&tick, pool_address, "get_tick", block_id)?;
+
+    Ok((
+        parse_u256(sqrt_price_low, sqrt_price_high),
+        parse_i32(tick_mag, tick_sign)?,
+        read_integer(&liquidity, pool_address, "get_liquidity", block_id)?,
+    ))
+}
+
+async fn call<P>(
+    provider: Arc<P>,
+    address: Felt,
+    method: &str,
+    block_id: BlockId,
+) -> Result<Vec<Felt>, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let result = call_contract(provider, address, method, vec![], block_id).await?;
+
+    if result.is_empty() {
+        return Err(AMMError::UnexpectedCallResult {
+            address,
+            method: method.to_string(),
+            block_id,
+        });
+    }
+
+    Ok(result)
+}
+
+/// Decodes Jediswap's signed `i32 { mag, sign }`, where `sign` is set for negative values.
+pub fn parse_i32(mag: Felt, sign: Felt) -> Result<i32, AMMError> {
+    let mag = parse_u128(mag)?;
+    let mag = i32::try_from(mag).map_err(|_| AMMError::PoolDataError)?;
+
+    Ok(if sign == Felt::ZERO { mag } else { -mag })
+}
+
+/// Decodes the tick bounds and signed liquidity delta of a `Mint` or `Burn` event.
+///
+/// Returns `None` for any other event.
+pub fn parse_position_event(event: &EmittedEvent) -> Result<Option<(i32, i32, i128)>, AMMError> {
+    let selector = event.keys.first().copied();
+
+    // Mint { sender, owner, tick_lower, tick_upper, amount, amount0, amount1 }
+    // Burn { owner, tick_lower, tick_upper, amount, amount0, amount1 }
+    let (offset, sign) = if selector == Some(MINT_EVENT) {
+        (2, 1)
+    } else if selector == Some(BURN_EVENT) {
+        (1, -1)
+    } else {
+        return Ok(None);
+    };
+
+    if event.data.len() < offset + 5 {
+        return Err(AMMError::PoolDataError);
+    }
+
+    let tick_lower = parse_i32(event.data[offset], event.data[offset + 1])?;
+    let tick_upper = parse_i32(event.data[offset + 2], event.data[offset + 3])?;
+    let amount = parse_u128(event.data[offset + 4])?;
+    let amount = i128::try_from(amount).map_err(|_| AMMError::PoolDataError)?;
+
+    Ok(Some((tick_lower, tick_upper, sign * amount)))
+}
+
+/// Decodes the sqrt price, tick and in-range liquidity a `Swap` event leaves the pool at.
+///
+/// `Swap { sender, recipient, amount0: i256, amount1: i256, sqrt_price_X96: u256, liquidity, tick }`
+/// is read from the end, so it does not depend on how the addresses are laid out.
+pub fn parse_swap_event(data: &[Felt]) -> Result<(BigUint, i32, u128), AMMError> {
+    let [sqrt_price_low, sqrt_price_high, liquidity, tick_mag, tick_sign] = data
+        .len()
+        .checked_sub(5)
+        .and_then(|start| data[start..].try_into().ok())
+        .ok_or(AMMError::PoolDataError)?;
+
+    Ok((
+        parse_u256(sqrt_price_low, sqrt_price_high),
+        parse_i32(tick_mag, tick_sign)?,
+        parse_u128(liquidity)?,
+    ))
+}
@@ -0,0 +1,473 @@
+//! Concentrated liquidity math shared by the Jediswap V2 pool.
+//!
+//! Jediswap V2 is a Cairo port of Uniswap V3, so these are straight ports of `TickMath`,
+//! `SqrtPriceMath`, `SwapMath` and `TickBitmap`, carried out on `BigUint` with the same
+//! 256-bit overflow checks and rounding directions as the on-chain contracts.
+
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+use crate::errors::{ArithmeticError, SwapSimulationError};
+
+pub const MIN_TICK: i32 = -887272;
+pub const MAX_TICK: i32 = -MIN_TICK;
+
+/// Fees are expressed in hundredths of a basis point of the input amount.
+pub const FEE_DENOMINATOR: u32 = 1_000_000;
+
+const TICK_RATIO_MULTIPLIERS: [u128; 19] = [
+    0xfff97272373d413259a46990580e213a,
+    0xfff2e50f5f656932ef12357cf3c7fdcc,
+    0xffe5caca7e10e4e61c3624eaa0941cd0,
+    0xffcb9843d60f6159c9db58835c926644,
+    0xff973b41fa98c081472e6896dfb254c0,
+    0xff2ea16466c96a3843ec78b326b52861,
+    0xfe5dee046a99a2a811c461f1969c3053,
+    0xfcbe86c7900a88aedcffc83b479aa3a4,
+    0xf987a7253ac413176f2b074cf7815e54,
+    0xf3392b0822b70005940c7a398e4b70f3,
+    0xe7159475a2c29b7443b29c7fa6e889d9,
+    0xd097f3bdfd2022b8845ad8f792aa5825,
+    0xa9f746462d870fdf8a65dc1f90e061e5,
+    0x70d869a156d2a1b890bb3df62baf32f7,
+    0x31be135f97d08fd981231505542fcfa6,
+    0x9aa508b5b7a84e1c677de54f3e99bc9,
+    0x5d6af8dedb81196699c329225ee604,
+    0x2216e584f5fa1ea926041bedfe98,
+    0x48a170391f7dc42444e8fa2,
+];
+
+/// The sqrt price of `MIN_TICK` as a Q64.96.
+pub fn min_sqrt_ratio() -> BigUint {
+    BigUint::from(4295128739u64)
+}
+
+/// The sqrt price of `MAX_TICK` as a Q64.96.
+pub fn max_sqrt_ratio() -> BigUint {
+    BigUint::parse_bytes(b"1461446703485210103287273052203988822378723970342", 10)
+        .expect("valid constant")
+}
+
+fn q96() -> BigUint {
+    BigUint::one() << 96
+}
+
+fn max_u160() -> BigUint {
+    (BigUint::one() << 160) - 1u32
+}
+
+fn max_u256() -> BigUint {
+    (BigUint::one() << 256) - 1u32
+}
+
+fn mul_div(a: &BigUint, b: &BigUint, denominator: &BigUint) -> BigUint {
+    a * b / denominator
+}
+
+fn mul_div_rounding_up(a: &BigUint, b: &BigUint, denominator: &BigUint) -> BigUint {
+    div_rounding_up(&(a * b), denominator)
+}
+
+fn div_rounding_up(a: &BigUint, denominator: &BigUint) -> BigUint {
+    let quotient = a / denominator;
+    if (a % denominator).is_zero() {
+        quotient
+    } else {
+        quotient + 1u32
+    }
+}
+
+/// Returns the sqrt price as a Q64.96 for the given tick.
+pub fn get_sqrt_ratio_at_tick(tick: i32) -> Result<BigUint, SwapSimulationError> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(SwapSimulationError::InvalidTick);
+    }
+
+    let abs_tick = tick.unsigned_abs();
+    let mut ratio = if abs_tick & 0x1 != 0 {
+        BigUint::from(0xfffcb933bd6fad37aa2d162d1a594001u128)
+    } else {
+        BigUint::one() << 128
+    };
+
+    for (i, multiplier) in TICK_RATIO_MULTIPLIERS.iter().enumerate() {
+        if abs_tick & (0x2 << i) != 0 {
+            ratio = (ratio * multiplier) >> 128;
+        }
+    }
+
+    if tick > 0 {
+        ratio = max_u256() / ratio;
+    }
+
+    let round_up = !(&ratio % (1u64 << 32)).is_zero();
+    let sqrt_price = ratio >> 32;
+
+    Ok(if round_up {
+        sqrt_price + 1u32
+    } else {
+        sqrt_price
+    })
+}
+
+/// Returns the greatest tick whose sqrt price is less than or equal to `sqrt_price`.
+pub fn get_tick_at_sqrt_ratio(sqrt_price: &BigUint) -> Result<i32, ArithmeticError> {
+    if *sqrt_price < min_sqrt_ratio() || *sqrt_price >= max_sqrt_ratio() {
+        return Err(ArithmeticError::SqrtPriceOverflow);
+    }
+
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        let ratio = get_sqrt_ratio_at_tick(mid).map_err(|_| ArithmeticError::SqrtPriceOverflow)?;
+        if ratio <= *sqrt_price {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    Ok(low)
+}
+
+fn get_next_sqrt_price_from_amount_0_rounding_up(
+    sqrt_price: &BigUint,
+    liquidity: u128,
+    amount: &BigUint,
+    add: bool,
+) -> Result<BigUint, ArithmeticError> {
+    if amount.is_zero() {
+        return Ok(sqrt_price.clone());
+    }
+
+    let numerator_1 = BigUint::from(liquidity) << 96;
+    let product = amount * sqrt_price;
+
+    if add {
+        if product <= max_u256() {
+            let denominator = &numerator_1 + &product;
+            if denominator <= max_u256() {
+                return Ok(mul_div_rounding_up(&numerator_1, sqrt_price, &denominator));
+            }
+        }
+
+        Ok(div_rounding_up(
+            &numerator_1,
+            &(&numerator_1 / sqrt_price + amount),
+        ))
+    } else {
+        if product > max_u256() || numerator_1 <= product {
+            return Err(ArithmeticError::SqrtPriceOverflow);
+        }
+
+        let denominator = &numerator_1 - &product;
+        let next = mul_div_rounding_up(&numerator_1, sqrt_price, &denominator);
+        if next > max_u160() {
+            return Err(ArithmeticError::SqrtPriceOverflow);
+        }
+
+        Ok(next)
+    }
+}
+
+fn get_next_sqrt_price_from_amount_1_rounding_down(
+    sqrt_price: &BigUint,
+    liquidity: u128,
+    amount: &BigUint,
+    add: bool,
+) -> Result<BigUint, ArithmeticError> {
+    let liquidity = BigUint::from(liquidity);
+
+    if add {
+        let quotient = mul_div(amount, &q96(), &liquidity);
+        let next = sqrt_price + quotient;
+        if next > max_u160() {
+            return Err(ArithmeticError::SqrtPriceOverflow);
+        }
+
+        Ok(next)
+    } else {
+        let quotient = mul_div_rounding_up(amount, &q96(), &liquidity);
+        if *sqrt_price <= quotient {
+            return Err(ArithmeticError::SqrtPriceOverflow);
+        }
+
+        Ok(sqrt_price - quotient)
+    }
+}
+
+fn get_next_sqrt_price_from_input(
+    sqrt_price: &BigUint,
+    liquidity: u128,
+    amount_in: &BigUint,
+    zero_for_one: bool,
+) -> Result<BigUint, ArithmeticError> {
+    if sqrt_price.is_zero() || liquidity == 0 {
+        return Err(ArithmeticError::SqrtPriceOverflow);
+    }
+
+    if zero_for_one {
+        get_next_sqrt_price_from_amount_0_rounding_up(sqrt_price, liquidity, amount_in, true)
+    } else {
+        get_next_sqrt_price_from_amount_1_rounding_down(sqrt_price, liquidity, amount_in, true)
+    }
+}
+
+fn get_next_sqrt_price_from_output(
+    sqrt_price: &BigUint,
+    liquidity: u128,
+    amount_out: &BigUint,
+    zero_for_one: bool,
+) -> Result<BigUint, ArithmeticError> {
+    if sqrt_price.is_zero() || liquidity == 0 {
+        return Err(ArithmeticError::SqrtPriceOverflow);
+    }
+
+    if zero_for_one {
+        get_next_sqrt_price_from_amount_1_rounding_down(sqrt_price, liquidity, amount_out, false)
+    } else {
+        get_next_sqrt_price_from_amount_0_rounding_up(sqrt_price, liquidity, amount_out, false)
+    }
+}
+
+/// Amount of token0 between two sqrt prices for the given liquidity.
+pub fn get_amount_0_delta(
+    sqrt_ratio_a: &BigUint,
+    sqrt_ratio_b: &BigUint,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<BigUint, ArithmeticError> {
+    let (sqrt_ratio_a, sqrt_ratio_b) = if sqrt_ratio_a > sqrt_ratio_b {
+        (sqrt_ratio_b, sqrt_ratio_a)
+    } else {
+        (sqrt_ratio_a, sqrt_ratio_b)
+    };
+
+    if sqrt_ratio_a.is_zero() {
+        return Err(ArithmeticError::SqrtPriceOverflow);
+    }
+
+    let numerator_1 = BigUint::from(liquidity) << 96;
+    let numerator_2 = sqrt_ratio_b - sqrt_ratio_a;
+
+    Ok(if round_up {
+        div_rounding_up(
+            &mul_div_rounding_up(&numerator_1, &numerator_2, sqrt_ratio_b),
+            sqrt_ratio_a,
+        )
+    } else {
+        mul_div(&numerator_1, &numerator_2, sqrt_ratio_b) / sqrt_ratio_a
+    })
+}
+
+/// Amount of token1 between two sqrt prices for the given liquidity.
+pub fn get_amount_1_delta(
+    sqrt_ratio_a: &BigUint,
+    sqrt_ratio_b: &BigUint,
+    liquidity: u128,
+    round_up: bool,
+) -> BigUint {
+    let (sqrt_ratio_a, sqrt_ratio_b) = if sqrt_ratio_a > sqrt_ratio_b {
+        (sqrt_ratio_b, sqrt_ratio_a)
+    } else {
+        (sqrt_ratio_a, sqrt_ratio_b)
+    };
+
+    let liquidity = BigUint::from(liquidity);
+    let difference = sqrt_ratio_b - sqrt_ratio_a;
+
+    if round_up {
+        mul_div_rounding_up(&liquidity, &difference, &q96())
+    } else {
+        mul_div(&liquidity, &difference, &q96())
+    }
+}
+
+/// Result of swapping within a single tick range.
+#[derive(Debug, Clone)]
+pub struct SwapStep {
+    pub sqrt_price_next: BigUint,
+    pub amount_in: BigUint,
+    pub amount_out: BigUint,
+    pub fee_amount: BigUint,
+}
+
+/// Computes the result of swapping `amount_remaining` between `sqrt_price_current` and
+/// `sqrt_price_target`. `exact_input` selects whether `amount_remaining` is an input or
+/// an output amount. Fails with `InvalidFee` unless `fee_pips` is below `FEE_DENOMINATOR`.
+pub fn compute_swap_step(
+    sqrt_price_current: &BigUint,
+    sqrt_price_target: &BigUint,
+    liquidity: u128,
+    amount_remaining: &BigUint,
+    exact_input: bool,
+    fee_pips: u32,
+) -> Result<SwapStep, SwapSimulationError> {
+    if fee_pips >= FEE_DENOMINATOR {
+        return Err(SwapSimulationError::InvalidFee(fee_pips));
+    }
+
+    let zero_for_one = sqrt_price_current >= sqrt_price_target;
+    let fee_pips_big = BigUint::from(fee_pips);
+    let fee_complement = BigUint::from(FEE_DENOMINATOR - fee_pips);
+
+    let mut amount_in = BigUint::zero();
+    let mut amount_out = BigUint::zero();
+
+    let sqrt_price_next = if exact_input {
+        let amount_remaining_less_fee = mul_div(
+            amount_remaining,
+            &fee_complement,
+            &BigUint::from(FEE_DENOMINATOR),
+        );
+        amount_in = if zero_for_one {
+            get_amount_0_delta(sqrt_price_target, sqrt_price_current, liquidity, true)?
+        } else {
+            get_amount_1_delta(sqrt_price_current, sqrt_price_target, liquidity, true)
+        };
+
+        if amount_remaining_less_fee >= amount_in {
+            sqrt_price_target.clone()
+        } else {
+            get_next_sqrt_price_from_input(
+                sqrt_price_current,
+                liquidity,
+                &amount_remaining_less_fee,
+                zero_for_one,
+            )?
+        }
+    } else {
+        amount_out = if zero_for_one {
+            get_amount_1_delta(sqrt_price_target, sqrt_price_current, liquidity, false)
+        } else {
+            get_amount_0_delta(sqrt_price_current, sqrt_price_target, liquidity, false)?
+        };
+
+        if *amount_remaining >= amount_out {
+            sqrt_price_target.clone()
+        } else {
+            get_next_sqrt_price_from_output(
+                sqrt_price_current,
+                liquidity,
+                amount_remaining,
+                zero_for_one,
+            )?
+        }
+    };
+
+    let max = *sqrt_price_target == sqrt_price_next;
+
+    if zero_for_one {
+        if !max || !exact_input {
+            amount_in = get_amount_0_delta(&sqrt_price_next, sqrt_price_current, liquidity, true)?;
+        }
+        if !max || exact_input {
+            amount_out = get_amount_1_delta(&sqrt_price_next, sqrt_price_current, liquidity, false);
+        }
+    } else {
+        if !max || !exact_input {
+            amount_in = get_amount_1_delta(sqrt_price_current, &sqrt_price_next, liquidity, true);
+        }
+        if !max || exact_input {
+            amount_out =
+                get_amount_0_delta(sqrt_price_current, &sqrt_price_next, liquidity, false)?;
+        }
+    }
+
+    if !exact_input && amount_out > *amount_remaining {
+        amount_out = amount_remaining.clone();
+    }
+
+    let fee_amount = if exact_input && sqrt_price_next != *sqrt_price_target {
+        amount_remaining - &amount_in
+    } else {
+        mul_div_rounding_up(&amount_in, &fee_pips_big, &fee_complement)
+    };
+
+    Ok(SwapStep {
+        sqrt_price_next,
+        amount_in,
+        amount_out,
+        fee_amount,
+    })
+}
+
+/// Adds a signed liquidity delta to an unsigned liquidity value.
+pub fn add_delta(liquidity: u128, delta: i128) -> Result<u128, SwapSimulationError> {
+    if delta < 0 {
+        liquidity.checked_sub(delta.unsigned_abs())
+    } else {
+        liquidity.checked_add(delta as u128)
+    }
+    .ok_or(SwapSimulationError::LiquidityUnderflow)
+}
+
+fn compress(tick: i32, tick_spacing: i32) -> i32 {
+    let compressed = tick / tick_spacing;
+    if tick < 0 && tick % tick_spacing != 0 {
+        compressed - 1
+    } else {
+        compressed
+    }
+}
+
+fn position(compressed: i32) -> (i16, u8) {
+    ((compressed >> 8) as i16, (compressed & 0xff) as u8)
+}
+
+/// Flips the initialized state of `tick` in the bitmap.
+pub fn flip_tick(tick_bitmap: &mut HashMap<i16, BigUint>, tick: i32, tick_spacing: i32) {
+    let (word_pos, bit_pos) = position(tick / tick_spacing);
+    let word = tick_bitmap.entry(word_pos).or_default();
+    *word ^= BigUint::one() << bit_pos;
+
+    if word.is_zero() {
+        tick_bitmap.remove(&word_pos);
+    }
+}
+
+/// Returns the next initialized tick contained in the same word as `tick`, or the word
+/// boundary if there is none, and whether the returned tick is initialized.
+pub fn next_initialized_tick_within_one_word(
+    tick_bitmap: &HashMap<i16, BigUint>,
+    tick: i32,
+    tick_spacing: i32,
+    lte: bool,
+) -> (i32, bool) {
+    let compressed = compress(tick, tick_spacing);
+    let zero = BigUint::zero();
+
+    if lte {
+        let (word_pos, bit_pos) = position(compressed);
+        let mask = (BigUint::one() << (bit_pos as u32 + 1)) - 1u32;
+        let masked = tick_bitmap.get(&word_pos).unwrap_or(&zero) & mask;
+
+        if masked.is_zero() {
+            ((compressed - bit_pos as i32) * tick_spacing, false)
+        } else {
+            let most_significant_bit = (masked.bits() - 1) as i32;
+            (
+                (compressed - (bit_pos as i32 - most_significant_bit)) * tick_spacing,
+                true,
+            )
+        }
+    } else {
+        let (word_pos, bit_pos) = position(compressed + 1);
+        let masked = tick_bitmap.get(&word_pos).unwrap_or(&zero) >> bit_pos;
+
+        if masked.is_zero() {
+            (
+                (compressed + 1 + (255 - bit_pos as i32)) * tick_spacing,
+                false,
+            )
+        } else {
+            let least_significant_bit = masked.trailing_zeros().unwrap_or(0) as i32;
+            (
+                (compressed + 1 + least_significant_bit) * tick_spacing,
+                true,
+            )
+        }
+    }
+}
@@ -0,0 +1,475 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use serde::{Deserialize, Serialize};
+use starknet::{
+    core::types::{BlockId, EmittedEvent, EventFilter, Felt},
+    providers::Provider,
+};
+use tracing::instrument;
+
+use super::{
+    get_data::{
+        get_pool_info, get_slot_state, parse_position_event, parse_swap_event, BURN_EVENT,
+        MINT_EVENT, SWAP_EVENT,
+    },
+    math::{
+        add_delta, compute_swap_step, flip_tick, get_sqrt_ratio_at_tick, get_tick_at_sqrt_ratio,
+        max_sqrt_ratio, min_sqrt_ratio, next_initialized_tick_within_one_word, MAX_TICK, MIN_TICK,
+    },
+};
+use crate::{
+    amm::{pool::AutomatedMarketMaker, types::Price},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+    token_registry::TokenRegistry,
+    utils::get_events,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TickInfo {
+    pub liquidity_gross: u128,
+    pub liquidity_net: i128,
+    pub initialized: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct JediswapV2Pool {
+    pub pool_address: Felt,
+    pub token_a: Felt,
+    pub token_b: Felt,
+    /// Kept in the `TokenRegistry` when serialized.
+    #[serde(skip)]
+    pub token_a_decimals: u8,
+    #[serde(skip)]
+    pub token_b_decimals: u8,
+    pub liquidity: u128,
+    pub sqrt_price: BigUint,
+    pub fee: u32,
+    pub tick: i32,
+    pub tick_spacing: i32,
+    pub tick_bitmap: HashMap<i16, BigUint>,
+    pub ticks: HashMap<i32, TickInfo>,
+}
+
+/// Pool state after a simulated swap.
+struct SwapResult {
+    amount_in: BigUint,
+    amount_out: BigUint,
+    sqrt_price: BigUint,
+    tick: i32,
+    liquidity: u128,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for JediswapV2Pool {
+    fn address(&self) -> Felt {
+        self.pool_address
+    }
+
+    fn tokens(&self) -> Vec<Felt> {
+        vec![self.token_a, self.token_b]
+    }
+
+    #[instrument(skip(self, provider), level = "debug")]
+    async fn sync<P>(&mut self, block_id: BlockId, provider: Arc<P>) -> Result<(), AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        let (sqrt_price, tick, liquidity) =
+            get_slot_state(self.pool_address, block_id, provider).await?;
+        tracing::info!(?sqrt_price, tick, liquidity, address = ?self.address(), "JediswapV2 sync");
+
+        self.sqrt_price = sqrt_price;
+        self.tick = tick;
+        self.liquidity = liquidity;
+
+        Ok(())
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<Felt> {
+        vec![SWAP_EVENT, MINT_EVENT, BURN_EVENT]
+    }
+
+    /// Takes the price, tick and liquidity a `Swap` leaves the pool at, and applies the
+    /// liquidity delta of a `Mint` or `Burn` to the ticks and, when in range, to `liquidity`.
+    fn sync_from_event(&mut self, event: EmittedEvent) -> Result<(), AMMError> {
+        if event.from_address != self.pool_address {
+            return Err(EventLogError::UnrelatedEvent.into());
+        }
+
+        if event.keys.first() == Some(&SWAP_EVENT) {
+            let (sqrt_price, tick, liquidity) = parse_swap_event(&event.data)?;
+            self.sqrt_price = sqrt_price;
+            self.tick = tick;
+            self.liquidity = liquidity;
+        } else if let Some((tick_lower, tick_upper, liquidity_delta)) =
+            parse_position_event(&event)?
+        {
+            self.modify_position(tick_lower, tick_upper, liquidity_delta)?;
+            if tick_lower <= self.tick && self.tick < tick_upper {
+                self.liquidity = add_delta(self.liquidity, liquidity_delta)?;
+            }
+        } else {
+            return Err(EventLogError::InvalidEventSignature.into());
+        }
+
+        Ok(())
+    }
+
+    fn calculate_price(&self, base_token: Felt, quote_token: Felt) -> Result<f64, AMMError> {
+        Ok(self
+            .calculate_price_exact(base_token, quote_token)?
+            .to_f64())
+    }
+
+    fn calculate_price_exact(
+        &self,
+        base_token: Felt,
+        quote_token: Felt,
+    ) -> Result<Price, AMMError> {
+        if self.sqrt_price.is_zero() {
+            return Err(ArithmeticError::YIsZero.into());
+        }
+
+        let price_x192 = &self.sqrt_price * &self.sqrt_price;
+        let q192 = BigUint::one() << 192;
+        let decimals_a = BigUint::from(10u32).pow(self.token_a_decimals.into());
+        let decimals_b = BigUint::from(10u32).pow(self.token_b_decimals.into());
+
+        if self.token_a == base_token {
+            if self.token_b != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist.into());
+            }
+            Ok(Price {
+                numerator: price_x192 * decimals_a,
+                denominator: q192 * decimals_b,
+            })
+        } else if self.token_b == base_token {
+            if self.token_a != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist.into());
+            }
+            Ok(Price {
+                numerator: q192 * decimals_b,
+                denominator: price_x192 * decimals_a,
+            })
+        } else {
+            Err(ArithmeticError::BaseTokenDoesNotExist.into())
+        }
+    }
+
+    #[allow(unused)]
+    async fn simulate_swap<P>(
+        &self,
+        base_token: Felt,
+        amount_in: Felt,
+        provider: Arc<P>,
+    ) -> Result<Felt, AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let quote_token = if self.token_a == base_token {
+            self.token_b
+        } else {
+            self.token_a
+        };
+        let zero_for_one = self.zero_for_one(base_token, quote_token)?;
+        let result = self.swap(zero_for_one, amount_in.to_biguint(), true)?;
+
+        Ok(Felt::from(result.amount_out))
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_in: Felt,
+    ) -> Result<Felt, AMMError> {
+        let zero_for_one = self.zero_for_one(base_token, quote_token)?;
+        let result = self.swap(zero_for_one, amount_in.to_biguint(), true)?;
+
+        self.apply(&result);
+
+        Ok(Felt::from(result.amount_out))
+    }
+
+    fn simulate_swap_exact_out(
+        &self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_out: Felt,
+    ) -> Result<Felt, AMMError> {
+        let zero_for_one = self.zero_for_one(base_token, quote_token)?;
+        let result = self.swap(zero_for_one, amount_out.to_biguint(), false)?;
+
+        Ok(Felt::from(result.amount_in))
+    }
+
+    fn simulate_swap_exact_out_mut(
+        &mut self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_out: Felt,
+    ) -> Result<Felt, AMMError> {
+        let zero_for_one = self.zero_for_one(base_token, quote_token)?;
+        let result = self.swap(zero_for_one, amount_out.to_biguint(), false)?;
+
+        self.apply(&result);
+
+        Ok(Felt::from(result.amount_in))
+    }
+}
+
+impl JediswapV2Pool {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pool_address: Felt,
+        token_a: Felt,
+        token_b: Felt,
+        token_a_decimals: u8,
+        token_b_decimals: u8,
+        liquidity: u128,
+        sqrt_price: BigUint,
+        fee: u32,
+        tick: i32,
+        tick_spacing: i32,
+    ) -> JediswapV2Pool {
+        JediswapV2Pool {
+            pool_address,
+            token_a,
+            token_b,
+            token_a_decimals,
+            token_b_decimals,
+            liquidity,
+            sqrt_price,
+            fee,
+            tick,
+            tick_spacing,
+            tick_bitmap: HashMap::new(),
+            ticks: HashMap::new(),
+        }
+    }
+
+    pub async fn new_from_address<P>(
+        pool_address: Felt,
+        creation_block: u64,
+        provider: Arc<P>,
+    ) -> Result<Self, AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        // Pin the state and the tick data to the same block.
+        let block_id = BlockId::Number(provider.block_number().await?);
+        let mut pool = get_pool_info(
+            pool_address,
+            block_id,
+            &TokenRegistry::default(),
+            provider.clone(),
+        )
+        .await?;
+        pool.populate_tick_data(creation_block, block_id, provider)
+            .await?;
+
+        Ok(pool)
+    }
+
+    /// Applies the pool's `Mint` and `Burn` events in the block range to `ticks` and `tick_bitmap`.
+    pub async fn populate_tick_data<P>(
+        &mut self,
+        from_block: u64,
+        to_block: BlockId,
+        provider: Arc<P>,
+    ) -> Result<(), AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        let filter = EventFilter {
+            from_block: Some(BlockId::Number(from_block)),
+            to_block: Some(to_block),
+            address: Some(self.pool_address),
+            keys: Some(vec![vec![MINT_EVENT, BURN_EVENT]]),
+        };
+
+        for event in get_events(provider, filter, 1000).await? {
+            if let Some((tick_lower, tick_upper, liquidity_delta)) = parse_position_event(&event)? {
+                self.modify_position(tick_lower, tick_upper, liquidity_delta)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies a liquidity delta to the ticks bounding a position.
+    ///
+    /// The active `liquidity` is not touched, it is read from the pool on `sync`.
+    pub fn modify_position(
+        &mut self,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity_delta: i128,
+    ) -> Result<(), SwapSimulationError> {
+        if liquidity_delta == 0 {
+            return Ok(());
+        }
+
+        self.update_tick(tick_lower, liquidity_delta, false)?;
+        self.update_tick(tick_upper, liquidity_delta, true)?;
+
+        Ok(())
+    }
+
+    fn update_tick(
+        &mut self,
+        tick: i32,
+        liquidity_delta: i128,
+        upper: bool,
+    ) -> Result<(), SwapSimulationError> {
+        let info = self.ticks.entry(tick).or_default();
+
+        let liquidity_gross_before = info.liquidity_gross;
+        let liquidity_gross_after = add_delta(liquidity_gross_before, liquidity_delta)?;
+        let flipped = (liquidity_gross_after == 0) != (liquidity_gross_before == 0);
+
+        info.liquidity_gross = liquidity_gross_after;
+        info.liquidity_net = if upper {
+            info.liquidity_net - liquidity_delta
+        } else {
+            info.liquidity_net + liquidity_delta
+        };
+        info.initialized = liquidity_gross_after != 0;
+
+        if liquidity_gross_after == 0 {
+            self.ticks.remove(&tick);
+        }
+
+        if flipped {
+            flip_tick(&mut self.tick_bitmap, tick, self.tick_spacing);
+        }
+
+        Ok(())
+    }
+
+    fn zero_for_one(&self, base_token: Felt, quote_token: Felt) -> Result<bool, ArithmeticError> {
+        if self.token_a == base_token {
+            if self.token_b != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist);
+            }
+            Ok(true)
+        } else if self.token_b == base_token {
+            if self.token_a != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist);
+            }
+            Ok(false)
+        } else {
+            Err(ArithmeticError::BaseTokenDoesNotExist)
+        }
+    }
+
+    fn apply(&mut self, result: &SwapResult) {
+        self.sqrt_price = result.sqrt_price.clone();
+        self.tick = result.tick;
+        self.liquidity = result.liquidity;
+    }
+
+    /// Walks the initialized ticks the same way the pool's `swap` does, without a price limit.
+    ///
+    /// Fails with `LiquidityUnderflow` when the pool runs out of liquidity before
+    /// `amount_specified` is filled, rather than returning a partial fill.
+    fn swap(
+        &self,
+        zero_for_one: bool,
+        amount_specified: BigUint,
+        exact_input: bool,
+    ) -> Result<SwapResult, SwapSimulationError> {
+        let sqrt_price_limit = if zero_for_one {
+            min_sqrt_ratio() + 1u32
+        } else {
+            max_sqrt_ratio() - 1u32
+        };
+
+        let mut amount_remaining = amount_specified;
+        let mut amount_in = BigUint::zero();
+        let mut amount_out = BigUint::zero();
+        let mut sqrt_price = self.sqrt_price.clone();
+        let mut tick = self.tick;
+        let mut liquidity = self.liquidity;
+
+        while !amount_remaining.is_zero() && sqrt_price != sqrt_price_limit {
+            let sqrt_price_start = sqrt_price.clone();
+
+            let (tick_next, initialized) = next_initialized_tick_within_one_word(
+                &self.tick_bitmap,
+                tick,
+                self.tick_spacing,
+                zero_for_one,
+            );
+            let tick_next = tick_next.clamp(MIN_TICK, MAX_TICK);
+            let sqrt_price_next = get_sqrt_ratio_at_tick(tick_next)?;
+
+            let sqrt_price_target = if (zero_for_one && sqrt_price_next < sqrt_price_limit)
+                || (!zero_for_one && sqrt_price_next > sqrt_price_limit)
+            {
+                sqrt_price_limit.clone()
+            } else {
+                sqrt_price_next.clone()
+            };
+
+            let step = compute_swap_step(
+                &sqrt_price,
+                &sqrt_price_target,
+                liquidity,
+                &amount_remaining,
+                exact_input,
+                self.fee,
+            )?;
+            sqrt_price = step.sqrt_price_next;
+
+            if exact_input {
+                amount_remaining -= &step.amount_in + &step.fee_amount;
+                amount_in += step.amount_in + step.fee_amount;
+                amount_out += step.amount_out;
+            } else {
+                amount_remaining -= &step.amount_out;
+                amount_in += step.amount_in + step.fee_amount;
+                amount_out += step.amount_out;
+            }
+
+            if sqrt_price == sqrt_price_next {
+                if initialized {
+                    let liquidity_net = self
+                        .ticks
+                        .get(&tick_next)
+                        .ok_or(SwapSimulationError::InvalidTick)?
+                        .liquidity_net;
+                    let liquidity_net = if zero_for_one {
+                        -liquidity_net
+                    } else {
+                        liquidity_net
+                    };
+                    liquidity = add_delta(liquidity, liquidity_net)?;
+                }
+
+                tick = if zero_for_one {
+                    tick_next - 1
+                } else {
+                    tick_next
+                };
+            } else if sqrt_price != sqrt_price_start {
+                tick = get_tick_at_sqrt_ratio(&sqrt_price)?;
+            }
+        }
+
+        if !amount_remaining.is_zero() {
+            return Err(SwapSimulationError::LiquidityUnderflow);
+        }
+
+        Ok(SwapResult {
+            amount_in,
+            amount_out,
+            sqrt_price,
+            tick,
+            liquidity,
+        })
+    }
+}
@@ -1,5 +1,9 @@
+pub mod ekubo;
 pub mod factory;
 pub mod jediswap;
+pub mod jediswap_v2;
+pub mod myswap;
 pub mod pool;
+pub mod sithswap;
 pub mod tenkswap;
 pub mod types;
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use starknet::{
+    core::types::{BlockId, EmittedEvent, Felt},
+    providers::Provider,
+};
+
+use crate::{
+    amm::{factory::AutomatedMarketMakerFactory, pool::AMM},
+    errors::AMMError,
+    token_registry::TokenRegistry,
+    utils::{default_batch_size, DEFAULT_BATCH_SIZE},
+};
+
+use super::get_data::{batch_populate_pools, get_pool_info, get_total_number_of_pools};
+
+/// Enumerates the pools of the mySwap V1 contract by id.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MySwapFactory {
+    pub contract_address: Felt,
+    /// Maximum number of calls sent per JSON-RPC batch by `populate_amm_data`.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Cache of token metadata, shared with the other factories it is cloned into.
+    #[serde(skip)]
+    pub token_registry: TokenRegistry,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for MySwapFactory {
+    fn address(&self) -> Felt {
+        self.contract_address
+    }
+
+    async fn fetch_all_pools<P>(&mut self, provider: Arc<P>) -> Result<Vec<AMM>, AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let block_id = BlockId::Number(provider.block_number().await?);
+        let total_pools =
+            get_total_number_of_pools(self.contract_address, block_id, provider.clone()).await?;
+
+        let mut all_pools = vec![];
+        for pool_id in 1..=total_pools {
+            let pool = get_pool_info(
+                self.contract_address,
+                pool_id,
+                block_id,
+                &self.token_registry,
+                provider.clone(),
+            )
+            .await?;
+            all_pools.push(AMM::MySwapPool(pool));
+        }
+
+        Ok(all_pools)
+    }
+
+    /// mySwap V1 emits no event when a pool is created, new pools are found by enumerating ids.
+    fn amm_created_event_signature(&self) -> Vec<Vec<Felt>> {
+        vec![]
+    }
+
+    fn new_empty_amm_from_log(&self, _log: EmittedEvent) -> Result<AMM, AMMError> {
+        Err(AMMError::UnrecognizedPoolCreatedEventLog)
+    }
+
+    async fn populate_amm_data<P>(
+        &self,
+        amms: &mut [AMM],
+        block_id: BlockId,
+        middleware: Arc<P>,
+    ) -> Result<(), AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        batch_populate_pools(
+            amms,
+            block_id,
+            self.batch_size,
+            &self.token_registry,
+            middleware,
+        )
+        .await
+    }
+}
+
+impl MySwapFactory {
+    pub fn new(contract_address: Felt) -> MySwapFactory {
+        MySwapFactory {
+            contract_address,
+            batch_size: DEFAULT_BATCH_SIZE,
+            token_registry: TokenRegistry::default(),
+        }
+    }
+
+    pub fn with_token_registry(mut self, token_registry: TokenRegistry) -> MySwapFactory {
+        self.token_registry = token_registry;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> MySwapFactory {
+        self.batch_size = batch_size;
+        self
+    }
+}
@@ -0,0 +1,206 @@
+use std::sync::Arc;
+
+use starknet::{
+    core::types::{BlockId, Felt},
+    providers::Provider,
+};
+
+use super::pool::MySwapPool;
+use crate::{
+    amm::pool::AMM,
+    errors::AMMError,
+    token_registry::TokenRegistry,
+    utils::{
+        batch_call, call_contract, function_call, parse_u128, parse_u256, read_integer,
+        DEFAULT_BATCH_SIZE,
+    },
+};
+
+/// Fields of mySwap's `Pool` struct that the simulation needs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoolStruct {
+    pub token_a: Felt,
+    pub reserve_a: Felt,
+    pub token_b: Felt,
+    pub reserve_b: Felt,
+    pub fee: u32,
+}
+
+pub async fn get_pool_info<P>(
+    contract_address: Felt,
+    pool_id: u64,
+    block_id: BlockId,
+    registry: &TokenRegistry,
+    provider: Arc<P>,
+) -> Result<MySwapPool, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let pool = get_pool_struct(contract_address, pool_id, block_id, provider.clone()).await?;
+
+    let decimals = registry
+        .get_decimals(
+            [pool.token_a, pool.token_b],
+            block_id,
+            DEFAULT_BATCH_SIZE,
+            provider,
+        )
+        .await?;
+
+    Ok(MySwapPool::new(
+        contract_address,
+        pool_id,
+        pool.token_a,
+        pool.token_b,
+        decimals[&pool.token_a],
+        decimals[&pool.token_b],
+        pool.reserve_a,
+        pool.reserve_b,
+        pool.fee,
+    ))
+}
+
+/// Reads and decodes the `Pool` struct stored under `pool_id`.
+pub async fn get_pool_struct<P>(
+    contract_address: Felt,
+    pool_id: u64,
+    block_id: BlockId,
+    provider: Arc<P>,
+) -> Result<PoolStruct, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let data = call(
+        provider,
+        contract_address,
+        "get_pool",
+        vec![Felt::from(pool_id)],
+        block_id,
+    )
+    .await?;
+
+    parse_pool_struct(&data)
+}
+
+/// Returns the number of pools in the contract. Pool ids run from 1 to this value.
+pub async fn get_total_number_of_pools<P>(
+    contract_address: Felt,
+    block_id: BlockId,
+    provider: Arc<P>,
+) -> Result<u64, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let total = call(
+        provider,
+        contract_address,
+        "get_total_number_of_pools",
+        vec![],
+        block_id,
+    )
+    .await?;
+
+    read_integer(
+        &total,
+        contract_address,
+        "get_total_number_of_pools",
+        block_id,
+    )
+}
+
+/// Decodes `Pool { name, token_a_address, token_a_reserves: Uint256, token_b_address,
+/// token_b_reserves: Uint256, fee_percentage, cfmm_type, liq_token }`.
+pub fn parse_pool_struct(data: &[Felt]) -> Result<PoolStruct, AMMError> {
+    if data.len() < 8 {
+        return Err(AMMError::PoolDataError);
+    }
+
+    Ok(PoolStruct {
+        token_a: data[1],
+        reserve_a: Felt::from(parse_u256(data[2], data[3])),
+        token_b: data[4],
+        reserve_b: Felt::from(parse_u256(data[5], data[6])),
+        fee: u32::try_from(parse_u128(data[7])?).map_err(|_| AMMError::PoolDataError)?,
+    })
+}
+
+async fn call<P>(
+    provider: Arc<P>,
+    address: Felt,
+    method: &str,
+    calldata: Vec<Felt>,
+    block_id: BlockId,
+) -> Result<Vec<Felt>, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let result = call_contract(provider, address, method, calldata, block_id).await?;
+
+    if result.is_empty() {
+        return Err(AMMError::UnexpectedCallResult {
+            address,
+            method: method.to_string(),
+            block_id,
+        });
+    }
+
+    Ok(result)
+}
+
+/// Refreshes the tokens, decimals, fee and reserves of every `MySwapPool` in `amms` through
+/// batched calls of at most `batch_size` requests.
+pub async fn batch_populate_pools<P>(
+    amms: &mut [AMM],
+    block_id: BlockId,
+    batch_size: usize,
+    registry: &TokenRegistry,
+    provider: Arc<P>,
+) -> Result<(), AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let mut pools = amms
+        .iter_mut()
+        .filter_map(|amm| match amm {
+            AMM::MySwapPool(pool) => Some(pool),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let calls = pools
+        .iter()
+        .map(|pool| {
+            function_call(
+                pool.contract_address,
+                "get_pool",
+                vec![Felt::from(pool.pool_id)],
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let results = batch_call(provider.clone(), calls, block_id, batch_size).await?;
+    let structs = results
+        .iter()
+        .map(|data| parse_pool_struct(data))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let decimals = registry
+        .get_decimals(
+            structs.iter().flat_map(|pool| [pool.token_a, pool.token_b]),
+            block_id,
+            batch_size,
+            provider,
+        )
+        .await?;
+
+    for (pool, data) in pools.iter_mut().zip(structs) {
+        pool.token_a = data.token_a;
+        pool.token_b = data.token_b;
+        pool.token_a_decimals = decimals[&data.token_a];
+        pool.token_b_decimals = decimals[&data.token_b];
+        pool.reserve_a = data.reserve_a;
+        pool.reserve_b = data.reserve_b;
+        pool.fee = data.fee;
+    }
+
+    Ok(())
+}
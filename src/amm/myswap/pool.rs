@@ -0,0 +1,310 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use num_bigint::BigUint;
+use num_traits::Zero;
+use serde::{Deserialize, Serialize};
+use starknet::{
+    core::{
+        crypto::compute_hash_on_elements,
+        types::{BlockId, EmittedEvent, Felt},
+    },
+    providers::Provider,
+};
+use tracing::instrument;
+
+use super::get_data::{get_pool_info, get_pool_struct};
+use crate::{
+    amm::{pool::AutomatedMarketMaker, types::Price},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+    token_registry::TokenRegistry,
+};
+
+/// `fee_percentage` is expressed in thousandths of a percent, so 300 is a 0.3% fee.
+pub const FEE_DENOMINATOR: u32 = 100_000;
+
+/// A constant product pool of the mySwap V1 contract, which holds every pool and addresses
+/// them by id.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MySwapPool {
+    pub contract_address: Felt,
+    pub pool_id: u64,
+    pub token_a: Felt,
+    pub token_b: Felt,
+    /// Kept in the `TokenRegistry` when serialized.
+    #[serde(skip)]
+    pub token_a_decimals: u8,
+    #[serde(skip)]
+    pub token_b_decimals: u8,
+    pub reserve_a: Felt,
+    pub reserve_b: Felt,
+    pub fee: u32,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for MySwapPool {
+    /// Pools share the mySwap contract, so they are identified by the Pedersen hash of the
+    /// contract address and the pool id.
+    fn address(&self) -> Felt {
+        compute_hash_on_elements(&[self.contract_address, Felt::from(self.pool_id)])
+    }
+
+    fn tokens(&self) -> Vec<Felt> {
+        vec![self.token_a, self.token_b]
+    }
+
+    #[instrument(skip(self, provider), level = "debug")]
+    async fn sync<P>(&mut self, block_id: BlockId, provider: Arc<P>) -> Result<(), AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        let pool = get_pool_struct(self.contract_address, self.pool_id, block_id, provider).await?;
+        tracing::info!(reserve_a = ?pool.reserve_a, reserve_b = ?pool.reserve_b, pool_id = self.pool_id, "mySwap sync");
+
+        self.reserve_a = pool.reserve_a;
+        self.reserve_b = pool.reserve_b;
+        self.fee = pool.fee;
+
+        Ok(())
+    }
+
+    /// The mySwap contract emits no event carrying the reserves of a pool, so it is only
+    /// refreshed through `sync`.
+    fn sync_on_event_signatures(&self) -> Vec<Felt> {
+        vec![]
+    }
+
+    fn sync_from_event(&mut self, _event: EmittedEvent) -> Result<(), AMMError> {
+        Err(EventLogError::InvalidEventSignature.into())
+    }
+
+    fn calculate_price(&self, base_token: Felt, quote_token: Felt) -> Result<f64, AMMError> {
+        Ok(self
+            .calculate_price_exact(base_token, quote_token)?
+            .to_f64())
+    }
+
+    fn calculate_price_exact(
+        &self,
+        base_token: Felt,
+        quote_token: Felt,
+    ) -> Result<Price, AMMError> {
+        let (reserve_in, reserve_out) = self.reserves_for(base_token, quote_token)?;
+        if self.token_a == base_token {
+            Ok(Price::from_reserves(
+                reserve_in,
+                self.token_a_decimals,
+                reserve_out,
+                self.token_b_decimals,
+            )?)
+        } else {
+            Ok(Price::from_reserves(
+                reserve_in,
+                self.token_b_decimals,
+                reserve_out,
+                self.token_a_decimals,
+            )?)
+        }
+    }
+
+    #[allow(unused)]
+    async fn simulate_swap<P>(
+        &self,
+        base_token: Felt,
+        amount_in: Felt,
+        provider: Arc<P>,
+    ) -> Result<Felt, AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        if self.token_a == base_token {
+            Ok(self.get_amount_out(amount_in, self.reserve_a, self.reserve_b))
+        } else if self.token_b == base_token {
+            Ok(self.get_amount_out(amount_in, self.reserve_b, self.reserve_a))
+        } else {
+            Err(ArithmeticError::BaseTokenDoesNotExist.into())
+        }
+    }
+
+    /// Locally simulates a swap in the AMM.
+    /// Mutates the AMM state to the state of the AMM after swapping.
+    /// Returns the amount received for `amount_in` of `token_in`.
+    fn simulate_swap_mut(
+        &mut self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_in: Felt,
+    ) -> Result<Felt, AMMError> {
+        let (reserve_in, reserve_out) = self.reserves_for(base_token, quote_token)?;
+        let amount_out = self.get_amount_out(amount_in, reserve_in, reserve_out);
+        self.apply_swap(base_token, amount_in, amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn simulate_swap_exact_out(
+        &self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_out: Felt,
+    ) -> Result<Felt, AMMError> {
+        let (reserve_in, reserve_out) = self.reserves_for(base_token, quote_token)?;
+        Ok(self.get_amount_in(amount_out, reserve_in, reserve_out)?)
+    }
+
+    fn simulate_swap_exact_out_mut(
+        &mut self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_out: Felt,
+    ) -> Result<Felt, AMMError> {
+        let amount_in = self.simulate_swap_exact_out(base_token, quote_token, amount_out)?;
+        self.apply_swap(base_token, amount_in, amount_out);
+
+        Ok(amount_in)
+    }
+}
+
+impl MySwapPool {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        contract_address: Felt,
+        pool_id: u64,
+        token_a: Felt,
+        token_b: Felt,
+        token_a_decimals: u8,
+        token_b_decimals: u8,
+        reserve_a: Felt,
+        reserve_b: Felt,
+        fee: u32,
+    ) -> MySwapPool {
+        MySwapPool {
+            contract_address,
+            pool_id,
+            token_a,
+            token_b,
+            token_a_decimals,
+            token_b_decimals,
+            reserve_a,
+            reserve_b,
+            fee,
+        }
+    }
+
+    pub async fn new_from_id<P>(
+        contract_address: Felt,
+        pool_id: u64,
+        provider: Arc<P>,
+    ) -> Result<Self, AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        let block_number = provider.block_number().await?;
+        get_pool_info(
+            contract_address,
+            pool_id,
+            BlockId::Number(block_number),
+            &TokenRegistry::default(),
+            provider,
+        )
+        .await
+    }
+
+    /// Returns the output amount of the contract's `swap` for `amount_in`.
+    ///
+    /// Unlike the Uniswap V2 pairs, mySwap first takes the fee out of the input, rounded down,
+    /// and prices the remainder on the constant product curve. The fee stays in the pool.
+    pub fn get_amount_out(&self, amount_in: Felt, reserve_in: Felt, reserve_out: Felt) -> Felt {
+        let amount_in = amount_in.to_biguint();
+        let reserve_in = reserve_in.to_biguint();
+        let reserve_out = reserve_out.to_biguint();
+
+        if amount_in.is_zero() || reserve_in.is_zero() || reserve_out.is_zero() {
+            return Felt::ZERO;
+        }
+
+        let amount_in_after_fee = self.amount_after_fee(&amount_in);
+        let result = &reserve_out * &amount_in_after_fee / (reserve_in + &amount_in_after_fee);
+
+        Felt::from(result)
+    }
+
+    /// Returns the smallest input for which `get_amount_out` yields at least `amount_out`.
+    pub fn get_amount_in(
+        &self,
+        amount_out: Felt,
+        reserve_in: Felt,
+        reserve_out: Felt,
+    ) -> Result<Felt, SwapSimulationError> {
+        let amount_out = amount_out.to_biguint();
+        let reserve_in = reserve_in.to_biguint();
+        let reserve_out = reserve_out.to_biguint();
+
+        if amount_out.is_zero() {
+            return Ok(Felt::ZERO);
+        }
+
+        if reserve_in.is_zero() || amount_out >= reserve_out {
+            return Err(SwapSimulationError::LiquidityUnderflow);
+        }
+
+        // Smallest post-fee input that buys `amount_out` on the curve.
+        let after_fee_needed = div_ceil(&reserve_in * &amount_out, &reserve_out - &amount_out);
+
+        // Invert the rounded-down fee, then settle on the smallest input that still covers it.
+        let denominator = BigUint::from(FEE_DENOMINATOR);
+        let mut amount_in = div_ceil(
+            &after_fee_needed * &denominator,
+            &denominator - BigUint::from(self.fee),
+        );
+        while self.amount_after_fee(&amount_in) < after_fee_needed {
+            amount_in += 1u32;
+        }
+        while !amount_in.is_zero()
+            && self.amount_after_fee(&(&amount_in - 1u32)) >= after_fee_needed
+        {
+            amount_in -= 1u32;
+        }
+
+        Ok(Felt::from(amount_in))
+    }
+
+    fn amount_after_fee(&self, amount_in: &BigUint) -> BigUint {
+        let fee = amount_in * self.fee / FEE_DENOMINATOR;
+        amount_in - fee
+    }
+
+    fn reserves_for(
+        &self,
+        base_token: Felt,
+        quote_token: Felt,
+    ) -> Result<(Felt, Felt), ArithmeticError> {
+        if self.token_a == base_token {
+            if self.token_b != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist);
+            }
+            Ok((self.reserve_a, self.reserve_b))
+        } else if self.token_b == base_token {
+            if self.token_a != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist);
+            }
+            Ok((self.reserve_b, self.reserve_a))
+        } else {
+            Err(ArithmeticError::BaseTokenDoesNotExist)
+        }
+    }
+
+    fn apply_swap(&mut self, base_token: Felt, amount_in: Felt, amount_out: Felt) {
+        if self.token_a == base_token {
+            self.reserve_a += amount_in;
+            self.reserve_b -= amount_out;
+        } else {
+            self.reserve_b += amount_in;
+            self.reserve_a -= amount_out;
+        }
+    }
+}
+
+fn div_ceil(numerator: BigUint, denominator: BigUint) -> BigUint {
+    (numerator + &denominator - 1u32) / denominator
+}
@@ -1,14 +1,24 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use starknet::{
-    core::types::{Felt, StarknetError},
+    core::types::{BlockId, EmittedEvent, EventFilter, Felt},
     providers::Provider,
 };
 
-use super::jediswap::pool::JediswapPool;
-use crate::amm::tenkswap::pool::TenkSwapPool;
+use super::{
+    ekubo::pool::EkuboPool, jediswap::pool::JediswapPool, jediswap_v2::pool::JediswapV2Pool,
+    myswap::pool::MySwapPool, sithswap::pool::SithSwapPool, types::Price,
+};
+use crate::{
+    amm::{ekubo::get_data::parse_pool_key, tenkswap::pool::TenkSwapPool},
+    errors::AMMError,
+    utils::get_events,
+};
 
 #[async_trait]
 pub trait AutomatedMarketMaker {
@@ -18,12 +28,23 @@ pub trait AutomatedMarketMaker {
     /// Returns a vector of tokens in the AMM.
     fn tokens(&self) -> Vec<Felt>;
 
-    async fn sync<P>(&mut self, provider: Arc<P>) -> Result<(), StarknetError>
+    /// Refreshes the AMM's state from the chain as of `block_id`.
+    async fn sync<P>(&mut self, block_id: BlockId, provider: Arc<P>) -> Result<(), AMMError>
     where
         P: Provider + Send + Sync;
 
+    /// Returns the selectors of the events consumed by `sync_from_event`.
+    fn sync_on_event_signatures(&self) -> Vec<Felt>;
+
+    /// Applies a state-changing event emitted for the AMM, without querying the chain.
+    fn sync_from_event(&mut self, event: EmittedEvent) -> Result<(), AMMError>;
+
     /// Calculates a f64 representation of base token price in the AMM.
-    fn calculate_price(&self, base_token: Felt, quote_token: Felt) -> Result<f64, StarknetError>;
+    fn calculate_price(&self, base_token: Felt, quote_token: Felt) -> Result<f64, AMMError>;
+
+    /// Calculates the exact base token price in the AMM, adjusted for token decimals.
+    fn calculate_price_exact(&self, base_token: Felt, quote_token: Felt)
+        -> Result<Price, AMMError>;
 
     /// Locally simulates a swap in the AMM.
     ///
@@ -33,7 +54,7 @@ pub trait AutomatedMarketMaker {
         base_token: Felt,
         amount_in: Felt,
         provider: Arc<P>,
-    ) -> Result<Felt, StarknetError>
+    ) -> Result<Felt, AMMError>
     where
         P: Provider + Send + Sync;
 
@@ -45,9 +66,29 @@ pub trait AutomatedMarketMaker {
         base_token: Felt,
         quote_token: Felt,
         amount_in: Felt,
-    ) -> Result<Felt, StarknetError>;
+    ) -> Result<Felt, AMMError>;
 
-    // async fn populate_data<P>(&mut self, middleware: Arc<P>) -> Result<(), StarknetError>
+    /// Locally simulates an exact output swap in the AMM.
+    ///
+    /// Returns the amount of `base_token` required to receive exactly `amount_out` of `quote_token`.
+    fn simulate_swap_exact_out(
+        &self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_out: Felt,
+    ) -> Result<Felt, AMMError>;
+
+    /// Locally simulates an exact output swap in the AMM.
+    /// Mutates the AMM state to the state of the AMM after swapping.
+    /// Returns the amount of `base_token` required to receive exactly `amount_out` of `quote_token`.
+    fn simulate_swap_exact_out_mut(
+        &mut self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_out: Felt,
+    ) -> Result<Felt, AMMError>;
+
+    // async fn populate_data<P>(&mut self, middleware: Arc<P>) -> Result<(), AMMError>
     // where
     //     P: Provider + Sync + Send;
 }
@@ -73,37 +114,67 @@ macro_rules! amm {
                 }
             }
 
-            async fn sync<P>(&mut self, middleware: Arc<P>) -> Result<(), StarknetError>
+            async fn sync<P>(&mut self, block_id: BlockId, middleware: Arc<P>) -> Result<(), AMMError>
             where
                 P: Provider + Send + Sync,
             {
                 match self {
-                    $(AMM::$pool_type(pool) => pool.sync(middleware).await,)+
+                    $(AMM::$pool_type(pool) => pool.sync(block_id, middleware).await,)+
+                }
+            }
+
+            fn sync_on_event_signatures(&self) -> Vec<Felt> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.sync_on_event_signatures(),)+
+                }
+            }
+
+            fn sync_from_event(&mut self, event: EmittedEvent) -> Result<(), AMMError> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.sync_from_event(event),)+
                 }
             }
 
 
-            async fn simulate_swap<P>(&self, base_token: Felt, amount_in: Felt, provider: Arc<P>) -> Result<Felt, StarknetError> where P: Provider + Send + Sync {
+            async fn simulate_swap<P>(&self, base_token: Felt, amount_in: Felt, provider: Arc<P>) -> Result<Felt, AMMError> where P: Provider + Send + Sync {
                 match self {
                     $(AMM::$pool_type(pool) => pool.simulate_swap(base_token, amount_in, provider).await,)+
                 }
             }
 
-            fn simulate_swap_mut(&mut self, base_token: Felt, quote_token: Felt, amount_in: Felt) -> Result<Felt, StarknetError> {
+            fn simulate_swap_mut(&mut self, base_token: Felt, quote_token: Felt, amount_in: Felt) -> Result<Felt, AMMError> {
                 match self {
                     $(AMM::$pool_type(pool) => pool.simulate_swap_mut(base_token, quote_token, amount_in),)+
                 }
             }
 
+            fn simulate_swap_exact_out(&self, base_token: Felt, quote_token: Felt, amount_out: Felt) -> Result<Felt, AMMError> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.simulate_swap_exact_out(base_token, quote_token, amount_out),)+
+                }
+            }
+
+            fn simulate_swap_exact_out_mut(&mut self, base_token: Felt, quote_token: Felt, amount_out: Felt) -> Result<Felt, AMMError> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.simulate_swap_exact_out_mut(base_token, quote_token, amount_out),)+
+                }
+            }
+
 
-            fn calculate_price(&self, base_token: Felt, quote_token: Felt) -> Result<f64, StarknetError> {
+            fn calculate_price(&self, base_token: Felt, quote_token: Felt) -> Result<f64, AMMError> {
                 match self {
                     $(AMM::$pool_type(pool) => pool.calculate_price(base_token, quote_token),)+
                 }
             }
 
+            fn calculate_price_exact(&self, base_token: Felt, quote_token: Felt) -> Result<Price, AMMError> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.calculate_price_exact(base_token, quote_token),)+
+                }
+            }
+
 
-            // async fn populate_data<P>(&mut self, middleware: Arc<P>) -> Result<(), StarknetError>
+            // async fn populate_data<P>(&mut self, middleware: Arc<P>) -> Result<(), AMMError>
             // where
             //     P: Provider + Send + Sync,
             // {
@@ -124,4 +195,162 @@ macro_rules! amm {
     };
 }
 
-amm!(JediswapPool, TenkSwapPool);
+amm!(
+    JediswapPool,
+    TenkSwapPool,
+    JediswapV2Pool,
+    EkuboPool,
+    MySwapPool,
+    SithSwapPool
+);
+
+/// Applies the events emitted between `from_block` and `to_block` to `amms` in place.
+///
+/// Every AMM's events are fetched through a single `get_events` query filtered on the union of
+/// their `sync_on_event_signatures`. Returns the addresses of the AMMs that were updated, in the
+/// order they were first touched.
+pub async fn sync_amms_from_events<P>(
+    amms: &mut [AMM],
+    from_block: BlockId,
+    to_block: BlockId,
+    provider: Arc<P>,
+) -> Result<Vec<Felt>, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let signatures = sync_on_event_signatures(amms.iter());
+    if signatures.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let filter = EventFilter {
+        from_block: Some(from_block),
+        to_block: Some(to_block),
+        address: None,
+        keys: Some(vec![signatures]),
+    };
+    let events = get_events(provider, filter, 1000).await?;
+
+    apply_events(amms.iter_mut(), &events)
+}
+
+/// Returns the union of the `sync_on_event_signatures` of `amms`.
+pub fn sync_on_event_signatures<'a>(amms: impl IntoIterator<Item = &'a AMM>) -> Vec<Felt> {
+    let mut signatures = vec![];
+    for amm in amms {
+        for signature in amm.sync_on_event_signatures() {
+            if !signatures.contains(&signature) {
+                signatures.push(signature);
+            }
+        }
+    }
+
+    signatures
+}
+
+/// Applies each event to the AMM it was emitted for, skipping events that no AMM in `amms`
+/// syncs on.
+///
+/// Returns the addresses of the AMMs that were updated, in the order they were first touched.
+pub fn apply_events<'a>(
+    amms: impl IntoIterator<Item = &'a mut AMM>,
+    events: &[EmittedEvent],
+) -> Result<Vec<Felt>, AMMError> {
+    Ok(apply_events_with_snapshots(amms, events)?
+        .iter()
+        .map(|amm| amm.address())
+        .collect())
+}
+
+/// Same as `apply_events`, but returns the state every updated AMM had before its first event,
+/// so the update can be reverted.
+///
+/// When an event fails to apply, the AMMs already updated are restored before the error is
+/// returned, so that `amms` is left as it was.
+pub fn apply_events_with_snapshots<'a>(
+    amms: impl IntoIterator<Item = &'a mut AMM>,
+    events: &[EmittedEvent],
+) -> Result<Vec<AMM>, AMMError> {
+    let (snapshots, mut errors) = apply_events_inner(amms, events, false);
+    match errors.pop() {
+        Some(err) => Err(err),
+        None => Ok(snapshots),
+    }
+}
+
+/// Same as `apply_events_with_snapshots`, but skips the events that fail to apply, leaving
+/// their AMM as it was before them, and returns their errors along with the snapshots.
+pub fn apply_events_skipping_failures<'a>(
+    amms: impl IntoIterator<Item = &'a mut AMM>,
+    events: &[EmittedEvent],
+) -> (Vec<AMM>, Vec<AMMError>) {
+    apply_events_inner(amms, events, true)
+}
+
+fn apply_events_inner<'a>(
+    amms: impl IntoIterator<Item = &'a mut AMM>,
+    events: &[EmittedEvent],
+    skip_failures: bool,
+) -> (Vec<AMM>, Vec<AMMError>) {
+    let mut by_address = HashMap::new();
+    let mut ekubo_cores = HashSet::new();
+    for amm in amms {
+        if let AMM::EkuboPool(pool) = amm {
+            ekubo_cores.insert(pool.core_address);
+        }
+        by_address.insert(amm.address(), amm);
+    }
+
+    let mut snapshots = vec![];
+    let mut errors = vec![];
+    let mut seen = HashSet::new();
+    for event in events {
+        // Ekubo pools live inside the core and are addressed by their pool key instead.
+        let address = if ekubo_cores.contains(&event.from_address) {
+            match event.data.get(1..6).map(parse_pool_key) {
+                Some(Ok(key)) => key.id(),
+                _ => continue,
+            }
+        } else {
+            event.from_address
+        };
+
+        let Some(amm) = by_address.get_mut(&address) else {
+            continue;
+        };
+        if !event
+            .keys
+            .first()
+            .is_some_and(|selector| amm.sync_on_event_signatures().contains(selector))
+        {
+            continue;
+        }
+
+        // The state before the event, kept when it is the first one of the AMM or when a
+        // failure only reverts this event.
+        let previous = (skip_failures || !seen.contains(&address)).then(|| amm.clone());
+        match amm.sync_from_event(event.clone()) {
+            Ok(()) => {
+                if seen.insert(address) {
+                    snapshots.extend(previous);
+                }
+            }
+            Err(err) => {
+                if let Some(previous) = previous {
+                    **amm = previous;
+                }
+                errors.push(err);
+                if !skip_failures {
+                    for snapshot in snapshots {
+                        if let Some(amm) = by_address.get_mut(&snapshot.address()) {
+                            **amm = snapshot;
+                        }
+                    }
+                    return (vec![], errors);
+                }
+            }
+        }
+    }
+
+    (snapshots, errors)
+}
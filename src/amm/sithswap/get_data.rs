@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use starknet::{
+    core::types::{BlockId, Felt},
+    macros::selector,
+    providers::Provider,
+};
+
+use super::pool::SithSwapPool;
+use crate::{
+    amm::{pool::AMM, types::Reserves},
+    errors::AMMError,
+    token_registry::TokenRegistry,
+    utils::{
+        batch_call, batch_get_pair_tokens, call_contract, function_call, parse_u256, read_integer,
+        read_result, DEFAULT_BATCH_SIZE,
+    },
+};
+
+pub const PAIR_CREATED_EVENT: Felt = selector!("PairCreated");
+pub const SYNC_EVENT: Felt = selector!("Sync");
+
+pub async fn get_pool_info<P>(
+    pool_address: Felt,
+    block_id: BlockId,
+    registry: &TokenRegistry,
+    provider: Arc<P>,
+) -> Result<SithSwapPool, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let token_0_address = call(provider.clone(), pool_address, "token0", block_id).await?[0];
+    let token_1_address = call(provider.clone(), pool_address, "token1", block_id).await?[0];
+
+    let decimals = registry
+        .get_decimals(
+            [token_0_address, token_1_address],
+            block_id,
+            DEFAULT_BATCH_SIZE,
+            provider.clone(),
+        )
+        .await?;
+
+    let stable = call(provider.clone(), pool_address, "stable", block_id).await?[0];
+    let fee = call(provider.clone(), pool_address, "getFee", block_id).await?;
+    let fee = read_integer(&fee, pool_address, "getFee", block_id)?;
+
+    let Reserves {
+        reserve_a,
+        reserve_b,
+    } = get_reserves(pool_address, block_id, provider).await?;
+
+    Ok(SithSwapPool::new(
+        pool_address,
+        token_0_address,
+        token_1_address,
+        decimals[&token_0_address],
+        decimals[&token_1_address],
+        reserve_a,
+        reserve_b,
+        stable != Felt::ZERO,
+        fee,
+    ))
+}
+
+/// Reads `getReserves`, which returns both reserves as `Uint256` followed by the last timestamp.
+pub async fn get_reserves<P>(
+    pool_address: Felt,
+    block_id: BlockId,
+    provider: Arc<P>,
+) -> Result<Reserves, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let result = call(provider, pool_address, "getReserves", block_id).await?;
+    let [reserve_a_low, reserve_a_high, reserve_b_low, reserve_b_high] =
+        read_result(&result, pool_address, "getReserves", block_id)?;
+
+    Ok(Reserves {
+        reserve_a: Felt::from(parse_u256(reserve_a_low, reserve_a_high)),
+        reserve_b: Felt::from(parse_u256(reserve_b_low, reserve_b_high)),
+    })
+}
+
+async fn call<P>(
+    provider: Arc<P>,
+    address: Felt,
+    method: &str,
+    block_id: BlockId,
+) -> Result<Vec<Felt>, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let result = call_contract(provider, address, method, vec![], block_id).await?;
+
+    if result.is_empty() {
+        return Err(AMMError::UnexpectedCallResult {
+            address,
+            method: method.to_string(),
+            block_id,
+        });
+    }
+
+    Ok(result)
+}
+
+/// Refreshes the tokens, decimals, curve, fee and reserves of every `SithSwapPool` in `amms`
+/// through batched calls of at most `batch_size` requests.
+pub async fn batch_populate_pools<P>(
+    amms: &mut [AMM],
+    block_id: BlockId,
+    batch_size: usize,
+    registry: &TokenRegistry,
+    provider: Arc<P>,
+) -> Result<(), AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let mut pools = amms
+        .iter_mut()
+        .filter_map(|amm| match amm {
+            AMM::SithSwapPool(pool) => Some(pool),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    // Pools only known by address still need their tokens.
+    let (unresolved, pairs): (Vec<usize>, Vec<Felt>) = pools
+        .iter()
+        .enumerate()
+        .filter(|(_, pool)| pool.token_a == Felt::ZERO)
+        .map(|(idx, pool)| (idx, pool.pool_address))
+        .unzip();
+    let tokens = batch_get_pair_tokens(provider.clone(), &pairs, block_id, batch_size).await?;
+    for (idx, (token_a, token_b)) in unresolved.into_iter().zip(tokens) {
+        pools[idx].token_a = token_a;
+        pools[idx].token_b = token_b;
+    }
+
+    let decimals = registry
+        .get_decimals(
+            pools.iter().flat_map(|pool| [pool.token_a, pool.token_b]),
+            block_id,
+            batch_size,
+            provider.clone(),
+        )
+        .await?;
+
+    let calls = pools
+        .iter()
+        .flat_map(|pool| {
+            ["stable", "getFee", "getReserves"]
+                .map(|method| function_call(pool.pool_address, method, vec![]))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let results = batch_call(provider, calls, block_id, batch_size).await?;
+
+    for (pool, results) in pools.into_iter().zip(results.chunks(3)) {
+        let [stable, fee, reserves] = results else {
+            return Err(AMMError::PoolDataError);
+        };
+        let address = pool.pool_address;
+        let [stable] = read_result(stable, address, "stable", block_id)?;
+        let [reserve_a_low, reserve_a_high, reserve_b_low, reserve_b_high] =
+            read_result(reserves, address, "getReserves", block_id)?;
+
+        pool.token_a_decimals = decimals[&pool.token_a];
+        pool.token_b_decimals = decimals[&pool.token_b];
+        pool.stable = stable != Felt::ZERO;
+        pool.fee = read_integer(fee, address, "getFee", block_id)?;
+        pool.reserve_a = Felt::from(parse_u256(reserve_a_low, reserve_a_high));
+        pool.reserve_b = Felt::from(parse_u256(reserve_b_low, reserve_b_high));
+    }
+
+    Ok(())
+}
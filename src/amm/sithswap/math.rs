@@ -0,0 +1,191 @@
+//! Solidly pair math shared by SithSwap's stable and volatile pools.
+//!
+//! Stable pairs trade on `x³y + y³x = k` over reserves scaled to 18 decimals, volatile pairs on
+//! `xy = k`. Every intermediate truncates to 18 decimals exactly like the pair contract, so the
+//! results match on-chain output to the unit.
+
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+use crate::errors::ArithmeticError;
+
+/// Fees are expressed in basis points of the input amount.
+pub const FEE_DENOMINATOR: u32 = 10_000;
+
+/// Maximum number of Newton iterations `get_y` runs before giving up, as in the pair contract.
+const MAX_ITERATIONS: usize = 255;
+
+fn e18() -> BigUint {
+    BigUint::from(10u32).pow(18)
+}
+
+fn unit(decimals: u8) -> BigUint {
+    BigUint::from(10u32).pow(decimals.into())
+}
+
+/// `x0·y³ + x0³·y`, in 18 decimal fixed point.
+fn f(x0: &BigUint, y: &BigUint) -> BigUint {
+    let e18 = e18();
+    let y3 = y * y / &e18 * y / &e18;
+    let x03 = x0 * x0 / &e18 * x0 / &e18;
+    x0 * y3 / &e18 + x03 * y / &e18
+}
+
+/// Derivative of `f` with respect to `y`.
+fn d(x0: &BigUint, y: &BigUint) -> BigUint {
+    let e18 = e18();
+    let x03 = x0 * x0 / &e18 * x0 / &e18;
+    BigUint::from(3u32) * x0 * (y * y / &e18) / &e18 + x03
+}
+
+/// Solves `f(x0, y) = xy` for `y` with Newton's method, starting from `y`.
+pub fn get_y(x0: &BigUint, xy: &BigUint, mut y: BigUint) -> Result<BigUint, ArithmeticError> {
+    let e18 = e18();
+
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y.clone();
+        let k = f(x0, &y);
+        let derivative = d(x0, &y);
+        if derivative.is_zero() {
+            return Err(ArithmeticError::YIsZero);
+        }
+
+        if k < *xy {
+            y += (xy - k) * &e18 / derivative;
+        } else {
+            let dy = (k - xy) * &e18 / derivative;
+            if dy > y {
+                return Err(ArithmeticError::RoundingError);
+            }
+            y -= dy;
+        }
+
+        let delta = if y > y_prev {
+            &y - &y_prev
+        } else {
+            &y_prev - &y
+        };
+        if delta <= BigUint::one() {
+            return Ok(y);
+        }
+    }
+
+    Err(ArithmeticError::RoundingError)
+}
+
+/// The pair's invariant for the given raw reserves.
+pub fn k(
+    reserve_0: &BigUint,
+    reserve_1: &BigUint,
+    decimals_0: u8,
+    decimals_1: u8,
+    stable: bool,
+) -> BigUint {
+    if !stable {
+        return reserve_0 * reserve_1;
+    }
+
+    let e18 = e18();
+    let x = reserve_0 * &e18 / unit(decimals_0);
+    let y = reserve_1 * &e18 / unit(decimals_1);
+    let a = &x * &y / &e18;
+    let b = &x * &x / &e18 + &y * &y / &e18;
+    a * b / e18
+}
+
+/// Returns the input left once the pair's fee has been taken, as the pair does before pricing.
+pub fn amount_after_fee(amount_in: &BigUint, fee: u32) -> BigUint {
+    amount_in - amount_in * fee / FEE_DENOMINATOR
+}
+
+/// Output of the pair's `getAmountOut` for `amount_in` of the input token.
+///
+/// Reserves and decimals are given in input/output order.
+pub fn get_amount_out(
+    amount_in: &BigUint,
+    reserve_in: &BigUint,
+    reserve_out: &BigUint,
+    decimals_in: u8,
+    decimals_out: u8,
+    stable: bool,
+    fee: u32,
+) -> Result<BigUint, ArithmeticError> {
+    if amount_in.is_zero() || reserve_in.is_zero() || reserve_out.is_zero() {
+        return Ok(BigUint::zero());
+    }
+
+    let amount_in = amount_after_fee(amount_in, fee);
+
+    if !stable {
+        return Ok(&amount_in * reserve_out / (reserve_in + &amount_in));
+    }
+
+    let e18 = e18();
+    let (unit_in, unit_out) = (unit(decimals_in), unit(decimals_out));
+    let xy = k(reserve_in, reserve_out, decimals_in, decimals_out, true);
+    let reserve_in = reserve_in * &e18 / &unit_in;
+    let reserve_out = reserve_out * &e18 / &unit_out;
+    let amount_in = amount_in * &e18 / &unit_in;
+
+    let y = get_y(&(amount_in + reserve_in), &xy, reserve_out.clone())?;
+    if y > reserve_out {
+        return Ok(BigUint::zero());
+    }
+
+    Ok((reserve_out - y) * unit_out / e18)
+}
+
+/// Smallest input for which `get_amount_out` yields at least `amount_out`.
+///
+/// Returns `None` when the pair cannot pay out `amount_out`.
+pub fn get_amount_in(
+    amount_out: &BigUint,
+    reserve_in: &BigUint,
+    reserve_out: &BigUint,
+    decimals_in: u8,
+    decimals_out: u8,
+    stable: bool,
+    fee: u32,
+) -> Result<Option<BigUint>, ArithmeticError> {
+    if amount_out.is_zero() {
+        return Ok(Some(BigUint::zero()));
+    }
+    if reserve_in.is_zero() || amount_out >= reserve_out {
+        return Ok(None);
+    }
+
+    let amount_out_of = |amount_in: &BigUint| {
+        get_amount_out(
+            amount_in,
+            reserve_in,
+            reserve_out,
+            decimals_in,
+            decimals_out,
+            stable,
+            fee,
+        )
+    };
+
+    // Grow an upper bound from the curve-free estimate, then bisect down to the exact minimum,
+    // which stays exact under every truncation of the forward computation.
+    let mut high = (amount_out * reserve_in / (reserve_out - amount_out)).max(BigUint::one());
+    let mut low = BigUint::zero();
+    while amount_out_of(&high)? < *amount_out {
+        if high.bits() > 256 {
+            return Ok(None);
+        }
+        low = high.clone();
+        high <<= 1;
+    }
+
+    while &high - &low > BigUint::one() {
+        let mid = (&low + &high) >> 1;
+        if amount_out_of(&mid)? >= *amount_out {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+
+    Ok(Some(high))
+}
@@ -0,0 +1,285 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use starknet::{
+    core::types::{BlockId, EmittedEvent, Felt},
+    providers::Provider,
+};
+use tracing::instrument;
+
+use super::{
+    get_data::{get_pool_info, get_reserves, SYNC_EVENT},
+    math,
+};
+use crate::{
+    amm::{
+        pool::AutomatedMarketMaker,
+        types::{Price, Reserves},
+    },
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+    token_registry::TokenRegistry,
+    utils::parse_u256,
+};
+
+/// A Solidly-style SithSwap pair, trading on the stable or the volatile curve.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SithSwapPool {
+    pub pool_address: Felt,
+    pub token_a: Felt,
+    pub token_b: Felt,
+    /// Kept in the `TokenRegistry` when serialized.
+    #[serde(skip)]
+    pub token_a_decimals: u8,
+    #[serde(skip)]
+    pub token_b_decimals: u8,
+    pub reserve_a: Felt,
+    pub reserve_b: Felt,
+    pub stable: bool,
+    /// Fee in basis points of the input amount.
+    pub fee: u32,
+}
+
+/// Reserves and decimals of a pair, in input/output order.
+struct Side {
+    reserve_in: Felt,
+    reserve_out: Felt,
+    decimals_in: u8,
+    decimals_out: u8,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for SithSwapPool {
+    fn address(&self) -> Felt {
+        self.pool_address
+    }
+
+    fn tokens(&self) -> Vec<Felt> {
+        vec![self.token_a, self.token_b]
+    }
+
+    #[instrument(skip(self, provider), level = "debug")]
+    async fn sync<P>(&mut self, block_id: BlockId, provider: Arc<P>) -> Result<(), AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        let Reserves {
+            reserve_a,
+            reserve_b,
+        } = get_reserves(self.pool_address, block_id, provider).await?;
+        tracing::info!(?reserve_a, ?reserve_b, address = ?self.address(), "SithSwap sync");
+
+        self.reserve_a = reserve_a;
+        self.reserve_b = reserve_b;
+
+        Ok(())
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<Felt> {
+        vec![SYNC_EVENT]
+    }
+
+    fn sync_from_event(&mut self, event: EmittedEvent) -> Result<(), AMMError> {
+        if event.from_address != self.pool_address {
+            return Err(EventLogError::UnrelatedEvent.into());
+        }
+        if event.keys.first() != Some(&SYNC_EVENT) {
+            return Err(EventLogError::InvalidEventSignature.into());
+        }
+
+        // Sync { reserve0: Uint256, reserve1: Uint256 }
+        let [reserve_a_low, reserve_a_high, reserve_b_low, reserve_b_high, ..] = event.data[..]
+        else {
+            return Err(EventLogError::InvalidEventData.into());
+        };
+        self.reserve_a = Felt::from(parse_u256(reserve_a_low, reserve_a_high));
+        self.reserve_b = Felt::from(parse_u256(reserve_b_low, reserve_b_high));
+
+        Ok(())
+    }
+
+    /// Spot price from the reserves. For stable pairs this is only an approximation of the
+    /// marginal price on the curve.
+    fn calculate_price(&self, base_token: Felt, quote_token: Felt) -> Result<f64, AMMError> {
+        Ok(self
+            .calculate_price_exact(base_token, quote_token)?
+            .to_f64())
+    }
+
+    fn calculate_price_exact(
+        &self,
+        base_token: Felt,
+        quote_token: Felt,
+    ) -> Result<Price, AMMError> {
+        let side = self.side(base_token, quote_token)?;
+        Ok(Price::from_reserves(
+            side.reserve_in,
+            side.decimals_in,
+            side.reserve_out,
+            side.decimals_out,
+        )?)
+    }
+
+    #[allow(unused)]
+    async fn simulate_swap<P>(
+        &self,
+        base_token: Felt,
+        amount_in: Felt,
+        provider: Arc<P>,
+    ) -> Result<Felt, AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let quote_token = if self.token_a == base_token {
+            self.token_b
+        } else {
+            self.token_a
+        };
+        let side = self.side(base_token, quote_token)?;
+
+        Ok(self.get_amount_out(amount_in, &side)?)
+    }
+
+    /// Locally simulates a swap in the AMM.
+    /// Mutates the AMM state to the state of the AMM after swapping.
+    /// Returns the amount received for `amount_in` of `token_in`.
+    fn simulate_swap_mut(
+        &mut self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_in: Felt,
+    ) -> Result<Felt, AMMError> {
+        let side = self.side(base_token, quote_token)?;
+        let amount_out = self.get_amount_out(amount_in, &side)?;
+        self.apply_swap(base_token, amount_in, amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn simulate_swap_exact_out(
+        &self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_out: Felt,
+    ) -> Result<Felt, AMMError> {
+        let side = self.side(base_token, quote_token)?;
+        Ok(self.get_amount_in(amount_out, &side)?)
+    }
+
+    fn simulate_swap_exact_out_mut(
+        &mut self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_out: Felt,
+    ) -> Result<Felt, AMMError> {
+        let amount_in = self.simulate_swap_exact_out(base_token, quote_token, amount_out)?;
+        self.apply_swap(base_token, amount_in, amount_out);
+
+        Ok(amount_in)
+    }
+}
+
+impl SithSwapPool {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pool_address: Felt,
+        token_a: Felt,
+        token_b: Felt,
+        token_a_decimals: u8,
+        token_b_decimals: u8,
+        reserve_a: Felt,
+        reserve_b: Felt,
+        stable: bool,
+        fee: u32,
+    ) -> SithSwapPool {
+        SithSwapPool {
+            pool_address,
+            token_a,
+            token_b,
+            token_a_decimals,
+            token_b_decimals,
+            reserve_a,
+            reserve_b,
+            stable,
+            fee,
+        }
+    }
+
+    pub async fn new_from_address<P>(pool_address: Felt, provider: Arc<P>) -> Result<Self, AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        let block_number = provider.block_number().await?;
+        get_pool_info(
+            pool_address,
+            BlockId::Number(block_number),
+            &TokenRegistry::default(),
+            provider,
+        )
+        .await
+    }
+
+    fn get_amount_out(&self, amount_in: Felt, side: &Side) -> Result<Felt, SwapSimulationError> {
+        let amount_out = math::get_amount_out(
+            &amount_in.to_biguint(),
+            &side.reserve_in.to_biguint(),
+            &side.reserve_out.to_biguint(),
+            side.decimals_in,
+            side.decimals_out,
+            self.stable,
+            self.fee,
+        )?;
+
+        Ok(Felt::from(amount_out))
+    }
+
+    fn get_amount_in(&self, amount_out: Felt, side: &Side) -> Result<Felt, SwapSimulationError> {
+        math::get_amount_in(
+            &amount_out.to_biguint(),
+            &side.reserve_in.to_biguint(),
+            &side.reserve_out.to_biguint(),
+            side.decimals_in,
+            side.decimals_out,
+            self.stable,
+            self.fee,
+        )?
+        .map(Felt::from)
+        .ok_or(SwapSimulationError::LiquidityUnderflow)
+    }
+
+    fn side(&self, base_token: Felt, quote_token: Felt) -> Result<Side, ArithmeticError> {
+        if self.token_a == base_token {
+            if self.token_b != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist);
+            }
+            Ok(Side {
+                reserve_in: self.reserve_a,
+                reserve_out: self.reserve_b,
+                decimals_in: self.token_a_decimals,
+                decimals_out: self.token_b_decimals,
+            })
+        } else if self.token_b == base_token {
+            if self.token_a != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist);
+            }
+            Ok(Side {
+                reserve_in: self.reserve_b,
+                reserve_out: self.reserve_a,
+                decimals_in: self.token_b_decimals,
+                decimals_out: self.token_a_decimals,
+            })
+        } else {
+            Err(ArithmeticError::BaseTokenDoesNotExist)
+        }
+    }
+
+    fn apply_swap(&mut self, base_token: Felt, amount_in: Felt, amount_out: Felt) {
+        if self.token_a == base_token {
+            self.reserve_a += amount_in;
+            self.reserve_b -= amount_out;
+        } else {
+            self.reserve_b += amount_in;
+            self.reserve_a -= amount_out;
+        }
+    }
+}
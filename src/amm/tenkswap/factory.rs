@@ -0,0 +1,144 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use starknet::{
+    core::types::{BlockId, EmittedEvent, Felt},
+    providers::Provider,
+};
+use std::sync::Arc;
+
+use crate::{
+    amm::{
+        factory::{check_event_selector, AutomatedMarketMakerFactory},
+        pool::AMM,
+    },
+    errors::AMMError,
+    token_registry::TokenRegistry,
+    utils::{call_contract, default_batch_size, read_integer, read_result, DEFAULT_BATCH_SIZE},
+};
+
+use super::{
+    get_data::{batch_populate_pools, get_pool_info, PAIR_CREATED_EVENT, PAIR_FEE},
+    pool::TenkSwapPool,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TenKFactory {
+    pub factory_address: Felt,
+    /// Maximum number of calls sent per JSON-RPC batch by `populate_amm_data`.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Cache of token metadata, shared with the other factories it is cloned into.
+    #[serde(skip)]
+    pub token_registry: TokenRegistry,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for TenKFactory {
+    fn address(&self) -> Felt {
+        self.factory_address
+    }
+
+    async fn fetch_all_pools<P>(&mut self, provider: Arc<P>) -> Result<Vec<AMM>, AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let block_id = BlockId::Number(provider.block_number().await?);
+        let pools_length = call_contract(
+            provider.clone(),
+            self.factory_address,
+            "allPairsLength",
+            vec![],
+            block_id,
+        )
+        .await?;
+        let pools_length: u128 = read_integer(
+            &pools_length,
+            self.factory_address,
+            "allPairsLength",
+            block_id,
+        )?;
+        let mut all_pools = vec![];
+
+        for idx in 0..pools_length {
+            let pool_address = call_contract(
+                provider.clone(),
+                self.factory_address,
+                "allPairs",
+                vec![Felt::from(idx)],
+                block_id,
+            )
+            .await?;
+            let [pool_address] =
+                read_result(&pool_address, self.factory_address, "allPairs", block_id)?;
+
+            let pool = get_pool_info(
+                pool_address,
+                block_id,
+                &self.token_registry,
+                provider.clone(),
+            )
+            .await?;
+            all_pools.push(AMM::TenkSwapPool(pool));
+        }
+        Ok(all_pools)
+    }
+
+    fn amm_created_event_signature(&self) -> Vec<Vec<Felt>> {
+        vec![vec![PAIR_CREATED_EVENT]]
+    }
+
+    fn new_empty_amm_from_log(&self, log: EmittedEvent) -> Result<AMM, AMMError> {
+        check_event_selector(&log, PAIR_CREATED_EVENT)?;
+
+        // PairCreated { token0, token1, pair, total_pairs }
+        match log.data[..] {
+            [token_a, token_b, pool_address, ..] => Ok(AMM::TenkSwapPool(TenkSwapPool {
+                pool_address,
+                token_a,
+                token_b,
+                fee: PAIR_FEE,
+                ..Default::default()
+            })),
+            _ => Err(AMMError::UnrecognizedPoolCreatedEventLog),
+        }
+    }
+
+    async fn populate_amm_data<P>(
+        &self,
+        amms: &mut [AMM],
+        block_id: BlockId,
+        middleware: Arc<P>,
+    ) -> Result<(), AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        batch_populate_pools(
+            amms,
+            block_id,
+            self.batch_size,
+            &self.token_registry,
+            middleware,
+        )
+        .await
+    }
+}
+
+impl TenKFactory {
+    pub fn new(factory_address: Felt) -> TenKFactory {
+        TenKFactory {
+            factory_address,
+            batch_size: DEFAULT_BATCH_SIZE,
+            token_registry: TokenRegistry::default(),
+        }
+    }
+
+    pub fn with_token_registry(mut self, token_registry: TokenRegistry) -> TenKFactory {
+        self.token_registry = token_registry;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> TenKFactory {
+        self.batch_size = batch_size;
+        self
+    }
+}
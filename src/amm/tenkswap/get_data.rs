@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use starknet::{
+    core::types::{BlockId, Felt},
+    macros::selector,
+    providers::Provider,
+};
+
+use super::pool::TenkSwapPool;
+use crate::{
+    amm::pool::AMM,
+    errors::AMMError,
+    token_registry::TokenRegistry,
+    utils::{
+        batch_call, batch_get_pair_tokens, call_contract, function_call, read_result,
+        DEFAULT_BATCH_SIZE,
+    },
+};
+
+pub const PAIR_CREATED_EVENT: Felt = selector!("PairCreated");
+pub const SYNC_EVENT: Felt = selector!("Sync");
+pub const SWAP_EVENT: Felt = selector!("Swap");
+pub const MINT_EVENT: Felt = selector!("Mint");
+pub const BURN_EVENT: Felt = selector!("Burn");
+
+/// Fee of every pair, 0.3% of the input. Pairs do not expose it, as it is fixed in the contract.
+pub const PAIR_FEE: u32 = 300;
+
+pub async fn get_pool_info<P>(
+    pool_address: Felt,
+    block_id: BlockId,
+    registry: &TokenRegistry,
+    provider: Arc<P>,
+) -> Result<TenkSwapPool, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let token0 = call_contract(provider.clone(), pool_address, "token0", vec![], block_id).await?;
+    let [token_0_address] = read_result(&token0, pool_address, "token0", block_id)?;
+
+    let token1 = call_contract(provider.clone(), pool_address, "token1", vec![], block_id).await?;
+    let [token_1_address] = read_result(&token1, pool_address, "token1", block_id)?;
+
+    let decimals = registry
+        .get_decimals(
+            [token_0_address, token_1_address],
+            block_id,
+            DEFAULT_BATCH_SIZE,
+            provider.clone(),
+        )
+        .await?;
+
+    let reserves = call_contract(provider, pool_address, "getReserves", vec![], block_id).await?;
+    let [reserve_a, reserve_b] = read_result(&reserves, pool_address, "getReserves", block_id)?;
+
+    Ok(TenkSwapPool::new(
+        pool_address,
+        token_0_address,
+        token_1_address,
+        decimals[&token_0_address],
+        decimals[&token_1_address],
+        reserve_a,
+        reserve_b,
+        PAIR_FEE,
+    ))
+}
+
+/// Refreshes the tokens, decimals and reserves of every `TenkSwapPool` in `amms` through
+/// batched calls of at most `batch_size` requests, and sets their fee to `PAIR_FEE`.
+pub async fn batch_populate_pools<P>(
+    amms: &mut [AMM],
+    block_id: BlockId,
+    batch_size: usize,
+    registry: &TokenRegistry,
+    provider: Arc<P>,
+) -> Result<(), AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let mut pools = amms
+        .iter_mut()
+        .filter_map(|amm| match amm {
+            AMM::TenkSwapPool(pool) => Some(pool),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    // Pools only known by address still need their tokens.
+    let (unresolved, pairs): (Vec<usize>, Vec<Felt>) = pools
+        .iter()
+        .enumerate()
+        .filter(|(_, pool)| pool.token_a == Felt::ZERO)
+        .map(|(idx, pool)| (idx, pool.pool_address))
+        .unzip();
+    let tokens = batch_get_pair_tokens(provider.clone(), &pairs, block_id, batch_size).await?;
+    for (idx, (token_a, token_b)) in unresolved.into_iter().zip(tokens) {
+        pools[idx].token_a = token_a;
+        pools[idx].token_b = token_b;
+    }
+
+    let decimals = registry
+        .get_decimals(
+            pools.iter().flat_map(|pool| [pool.token_a, pool.token_b]),
+            block_id,
+            batch_size,
+            provider.clone(),
+        )
+        .await?;
+
+    let calls = pools
+        .iter()
+        .map(|pool| function_call(pool.pool_address, "getReserves", vec![]))
+        .collect::<Result<Vec<_>, _>>()?;
+    let reserves = batch_call(provider, calls, block_id, batch_size).await?;
+
+    for (pool, reserves) in pools.into_iter().zip(reserves) {
+        let [reserve_a, reserve_b] =
+            read_result(&reserves, pool.pool_address, "getReserves", block_id)?;
+        pool.token_a_decimals = decimals[&pool.token_a];
+        pool.token_b_decimals = decimals[&pool.token_b];
+        pool.reserve_a = reserve_a;
+        pool.reserve_b = reserve_b;
+        pool.fee = PAIR_FEE;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,369 @@
+use core::f64;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use starknet::{
+    core::types::{BlockId, EmittedEvent, Felt},
+    providers::Provider,
+};
+use tracing::instrument;
+
+use crate::{
+    amm::{
+        pool::AutomatedMarketMaker,
+        types::{fee_factor, Price, Reserves},
+    },
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+    token_registry::TokenRegistry,
+    utils::{call_contract, read_result},
+};
+
+use super::get_data::{get_pool_info, BURN_EVENT, MINT_EVENT, SWAP_EVENT, SYNC_EVENT};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TenkSwapPool {
+    pub pool_address: Felt,
+    pub token_a: Felt,
+    pub token_b: Felt,
+    /// Kept in the `TokenRegistry` when serialized.
+    #[serde(skip)]
+    pub token_a_decimals: u8,
+    #[serde(skip)]
+    pub token_b_decimals: u8,
+    pub reserve_a: Felt,
+    pub reserve_b: Felt,
+    pub fee: u32,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for TenkSwapPool {
+    fn address(&self) -> Felt {
+        self.pool_address
+    }
+
+    fn tokens(&self) -> Vec<Felt> {
+        vec![self.token_a, self.token_b]
+    }
+
+    fn calculate_price(&self, base_token: Felt, quote_token: Felt) -> Result<f64, AMMError> {
+        Ok(self
+            .calculate_price_exact(base_token, quote_token)?
+            .to_f64())
+    }
+
+    fn calculate_price_exact(
+        &self,
+        base_token: Felt,
+        quote_token: Felt,
+    ) -> Result<Price, AMMError> {
+        if self.token_a == base_token {
+            if self.token_b != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist.into());
+            }
+            Ok(Price::from_reserves(
+                self.reserve_a,
+                self.token_a_decimals,
+                self.reserve_b,
+                self.token_b_decimals,
+            )?)
+        } else if self.token_b == base_token {
+            if self.token_a != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist.into());
+            }
+            Ok(Price::from_reserves(
+                self.reserve_b,
+                self.token_b_decimals,
+                self.reserve_a,
+                self.token_a_decimals,
+            )?)
+        } else {
+            Err(ArithmeticError::BaseTokenDoesNotExist.into())
+        }
+    }
+
+    #[allow(unused)]
+    async fn simulate_swap<P>(
+        &self,
+        base_token: Felt,
+        amount_in: Felt,
+        provider: Arc<P>,
+    ) -> Result<Felt, AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        if self.token_a == base_token {
+            Ok(self.get_amount_out(amount_in, self.reserve_a, self.reserve_b))
+        } else if self.token_b == base_token {
+            Ok(self.get_amount_out(amount_in, self.reserve_b, self.reserve_a))
+        } else {
+            Err(ArithmeticError::BaseTokenDoesNotExist.into())
+        }
+    }
+
+    /// Locally simulates a swap in the AMM.
+    /// Mutates the AMM state to the state of the AMM after swapping.
+    /// Returns the amount received for `amount_in` of `token_in`.
+    fn simulate_swap_mut(
+        &mut self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_in: Felt,
+    ) -> Result<Felt, AMMError> {
+        if self.token_a == base_token {
+            if self.token_b != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist.into());
+            }
+            let amount_out = self.get_amount_out(amount_in, self.reserve_a, self.reserve_b);
+            self.reserve_a += amount_in;
+            self.reserve_b -= amount_out;
+
+            Ok(amount_out)
+        } else if self.token_b == base_token {
+            if self.token_a != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist.into());
+            }
+            let amount_out = self.get_amount_out(amount_in, self.reserve_b, self.reserve_a);
+            self.reserve_b += amount_in;
+            self.reserve_a -= amount_out;
+
+            Ok(amount_out)
+        } else {
+            Err(ArithmeticError::BaseTokenDoesNotExist.into())
+        }
+    }
+
+    fn simulate_swap_exact_out(
+        &self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_out: Felt,
+    ) -> Result<Felt, AMMError> {
+        if self.token_a == base_token {
+            if self.token_b != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist.into());
+            }
+            Ok(self.get_amount_in(amount_out, self.reserve_a, self.reserve_b)?)
+        } else if self.token_b == base_token {
+            if self.token_a != quote_token {
+                return Err(ArithmeticError::QuoteTokenDoesNotExist.into());
+            }
+            Ok(self.get_amount_in(amount_out, self.reserve_b, self.reserve_a)?)
+        } else {
+            Err(ArithmeticError::BaseTokenDoesNotExist.into())
+        }
+    }
+
+    fn simulate_swap_exact_out_mut(
+        &mut self,
+        base_token: Felt,
+        quote_token: Felt,
+        amount_out: Felt,
+    ) -> Result<Felt, AMMError> {
+        let amount_in = self.simulate_swap_exact_out(base_token, quote_token, amount_out)?;
+
+        if self.token_a == base_token {
+            self.reserve_a += amount_in;
+            self.reserve_b -= amount_out;
+        } else {
+            self.reserve_b += amount_in;
+            self.reserve_a -= amount_out;
+        }
+
+        Ok(amount_in)
+    }
+
+    #[instrument(skip(self, provider), level = "debug")]
+    async fn sync<P>(&mut self, block_id: BlockId, provider: Arc<P>) -> Result<(), AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        let Reserves {
+            reserve_a,
+            reserve_b,
+        } = self.get_reserves(block_id, provider.clone()).await?;
+        tracing::info!(?reserve_a, ?reserve_b, address = ?self.address(), "UniswapV2 sync");
+
+        self.reserve_a = reserve_a;
+        self.reserve_b = reserve_b;
+
+        Ok(())
+    }
+
+    /// `Sync` carries the reserves after every `Swap`, `Mint` and `Burn`, so it is the only
+    /// event needed to track the pair.
+    fn sync_on_event_signatures(&self) -> Vec<Felt> {
+        vec![SYNC_EVENT]
+    }
+
+    /// Sets the reserves from a `Sync` event, or applies the amounts of a `Swap`, `Mint` or
+    /// `Burn` event to them. The pair emits `Sync` next to each of the latter, so a caller
+    /// consuming both would count every change twice.
+    fn sync_from_event(&mut self, event: EmittedEvent) -> Result<(), AMMError> {
+        if event.from_address != self.pool_address {
+            return Err(EventLogError::UnrelatedEvent.into());
+        }
+
+        // 10KSwap pairs emit every amount as a single felt.
+        let selector = event.keys.first().copied();
+        if selector == Some(SYNC_EVENT) {
+            // Sync { reserve0, reserve1 }
+            let [reserve_a, reserve_b] = read_felts(&event.data, 0)?;
+            self.reserve_a = reserve_a;
+            self.reserve_b = reserve_b;
+        } else if selector == Some(SWAP_EVENT) {
+            // Swap { sender, amount0In, amount1In, amount0Out, amount1Out, to }
+            let [amount_a_in, amount_b_in, amount_a_out, amount_b_out] =
+                read_felts(&event.data, 1)?;
+            self.reserve_a = self.reserve_a + amount_a_in - amount_a_out;
+            self.reserve_b = self.reserve_b + amount_b_in - amount_b_out;
+        } else if selector == Some(MINT_EVENT) {
+            // Mint { sender, amount0, amount1 }
+            let [amount_a, amount_b] = read_felts(&event.data, 1)?;
+            self.reserve_a += amount_a;
+            self.reserve_b += amount_b;
+        } else if selector == Some(BURN_EVENT) {
+            // Burn { sender, amount0, amount1, to }
+            let [amount_a, amount_b] = read_felts(&event.data, 1)?;
+            self.reserve_a -= amount_a;
+            self.reserve_b -= amount_b;
+        } else {
+            return Err(EventLogError::InvalidEventSignature.into());
+        }
+
+        Ok(())
+    }
+}
+
+impl TenkSwapPool {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pool_address: Felt,
+        token_a: Felt,
+        token_b: Felt,
+        token_a_decimals: u8,
+        token_b_decimals: u8,
+        reserve_a: Felt,
+        reserve_b: Felt,
+        fee: u32,
+    ) -> TenkSwapPool {
+        TenkSwapPool {
+            pool_address,
+            token_a,
+            token_b,
+            token_a_decimals,
+            token_b_decimals,
+            reserve_a,
+            reserve_b,
+            fee,
+        }
+    }
+
+    /// Returns the output amount of the pair's `swap` for `amount_in`, matching the router's
+    /// `getAmountOut` (fee taken on the input, result rounded down).
+    pub fn get_amount_out(&self, amount_in: Felt, reserve_in: Felt, reserve_out: Felt) -> Felt {
+        let amount_in = BigUint::from_bytes_be(&amount_in.to_bytes_be());
+        let reserve_in = BigUint::from_bytes_be(&reserve_in.to_bytes_be());
+        let reserve_out = BigUint::from_bytes_be(&reserve_out.to_bytes_be());
+
+        if amount_in == BigUint::from(0u32)
+            || reserve_in == BigUint::from(0u32)
+            || reserve_out == BigUint::from(0u32)
+        {
+            return Felt::ZERO;
+        }
+
+        let fee = fee_factor(self.fee);
+        let amount_in_with_fee = &amount_in * &fee;
+        let numerator = &amount_in_with_fee * &reserve_out;
+        let denominator = &reserve_in * BigUint::from(1000u32) + &amount_in_with_fee;
+
+        let result = &numerator / &denominator;
+
+        Felt::from_bytes_be_slice(&result.to_bytes_be())
+    }
+
+    /// Returns the input amount required by the pair's `swap` to receive `amount_out`, matching
+    /// the router's `getAmountIn` (fee taken on the input, result rounded up).
+    pub fn get_amount_in(
+        &self,
+        amount_out: Felt,
+        reserve_in: Felt,
+        reserve_out: Felt,
+    ) -> Result<Felt, SwapSimulationError> {
+        let amount_out = BigUint::from_bytes_be(&amount_out.to_bytes_be());
+        let reserve_in = BigUint::from_bytes_be(&reserve_in.to_bytes_be());
+        let reserve_out = BigUint::from_bytes_be(&reserve_out.to_bytes_be());
+
+        if amount_out == BigUint::from(0u32) {
+            return Ok(Felt::ZERO);
+        }
+
+        if reserve_in == BigUint::from(0u32) || amount_out >= reserve_out {
+            return Err(SwapSimulationError::LiquidityUnderflow);
+        }
+
+        let fee = fee_factor(self.fee);
+        if fee == BigUint::ZERO {
+            return Err(SwapSimulationError::InvalidFee(self.fee));
+        }
+
+        let numerator = &reserve_in * &amount_out * BigUint::from(1000u32);
+        let denominator = (&reserve_out - &amount_out) * &fee;
+
+        let result = &numerator / &denominator + BigUint::from(1u32);
+
+        Ok(Felt::from_bytes_be_slice(&result.to_bytes_be()))
+    }
+
+    async fn get_reserves<P>(
+        &mut self,
+        block_id: BlockId,
+        provider: Arc<P>,
+    ) -> Result<Reserves, AMMError>
+    where
+        P: Provider + Sync + Send,
+    {
+        let result =
+            call_contract(provider, self.pool_address, "getReserves", vec![], block_id).await?;
+        let [reserve_a, reserve_b] =
+            read_result(&result, self.pool_address, "getReserves", block_id)?;
+
+        self.reserve_a = reserve_a;
+        self.reserve_b = reserve_b;
+        Ok(Reserves {
+            reserve_a,
+            reserve_b,
+        })
+    }
+
+    pub async fn new_from_address<P>(
+        pool_address: Felt,
+        fee: u32,
+        provider: Arc<P>,
+    ) -> Result<Self, AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        // Read every field at the same block.
+        let block_number = provider.block_number().await?;
+        let mut pool = get_pool_info(
+            pool_address,
+            BlockId::Number(block_number),
+            &TokenRegistry::default(),
+            provider,
+        )
+        .await?;
+        pool.fee = fee;
+
+        Ok(pool)
+    }
+}
+
+/// Reads `N` consecutive felts from `data`, starting at `offset`.
+fn read_felts<const N: usize>(data: &[Felt], offset: usize) -> Result<[Felt; N], EventLogError> {
+    data.get(offset..offset + N)
+        .and_then(|words| words.try_into().ok())
+        .ok_or(EventLogError::InvalidEventData)
+}
@@ -1,8 +1,78 @@
+use std::cmp::Ordering;
+
+use num_bigint::BigUint;
+use num_traits::{ToPrimitive, Zero};
 use starknet::core::types::Felt;
 
+use crate::errors::ArithmeticError;
+
 #[derive(Debug)]
 pub struct Reserves {
     pub reserve_a: Felt,
     pub reserve_b: Felt,
     // pub block_timestamp_last: BigUint,
 }
+
+/// Exact spot price of a base token denominated in a quote token, kept as an
+/// unreduced fraction so that prices can be compared without float rounding.
+#[derive(Debug, Clone)]
+pub struct Price {
+    pub numerator: BigUint,
+    pub denominator: BigUint,
+}
+
+impl Price {
+    /// Spot price of the base token in the quote token for a constant product pool,
+    /// normalized by the decimals of both tokens.
+    pub fn from_reserves(
+        reserve_base: Felt,
+        base_decimals: u8,
+        reserve_quote: Felt,
+        quote_decimals: u8,
+    ) -> Result<Price, ArithmeticError> {
+        let reserve_base = BigUint::from_bytes_be(&reserve_base.to_bytes_be());
+        let reserve_quote = BigUint::from_bytes_be(&reserve_quote.to_bytes_be());
+
+        if reserve_base.is_zero() {
+            return Err(ArithmeticError::YIsZero);
+        }
+
+        Ok(Price {
+            numerator: reserve_quote * BigUint::from(10u32).pow(base_decimals.into()),
+            denominator: reserve_base * BigUint::from(10u32).pow(quote_decimals.into()),
+        })
+    }
+
+    /// Lossy f64 representation of the price.
+    pub fn to_f64(&self) -> f64 {
+        let numerator = self.numerator.to_f64().unwrap_or(f64::INFINITY);
+        let denominator = self.denominator.to_f64().unwrap_or(f64::INFINITY);
+        numerator / denominator
+    }
+}
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.numerator * &other.denominator).cmp(&(&other.numerator * &self.denominator))
+    }
+}
+
+/// Thousandths of the input of a constant product swap left after the pool `fee`, 997 for the
+/// 0.3% fee of 300 taken by Jediswap and 10KSwap pairs.
+pub fn fee_factor(fee: u32) -> BigUint {
+    BigUint::from(10000u32.saturating_sub(fee / 10) / 10)
+}
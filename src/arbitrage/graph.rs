@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use starknet::core::types::Felt;
+
+use crate::amm::pool::{AutomatedMarketMaker, AMM};
+
+/// A swap of `token_in` for `token_out` through the pool at `pool`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Hop {
+    pub pool: Felt,
+    pub token_in: Felt,
+    pub token_out: Felt,
+}
+
+/// A sequence of hops that starts and ends with the same token, each through a different pool.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Cycle {
+    pub hops: Vec<Hop>,
+}
+
+impl Cycle {
+    /// Returns the token the cycle is entered and exited with.
+    pub fn start_token(&self) -> Felt {
+        self.hops[0].token_in
+    }
+
+    /// Returns the addresses of the pools of the cycle, in trading order.
+    pub fn pools(&self) -> impl Iterator<Item = Felt> + '_ {
+        self.hops.iter().map(|hop| hop.pool)
+    }
+
+    /// Returns the same loop entered and exited with `token`, or `None` when it does not
+    /// trade `token`.
+    pub fn rotated_to(&self, token: Felt) -> Option<Cycle> {
+        let start = self.hops.iter().position(|hop| hop.token_in == token)?;
+        let mut hops = self.hops.clone();
+        hops.rotate_left(start);
+        Some(Cycle { hops })
+    }
+}
+
+/// Directed graph of tokens, with one edge per pool and trading direction.
+#[derive(Debug, Clone, Default)]
+pub struct TokenGraph {
+    edges: HashMap<Felt, Vec<Hop>>,
+}
+
+impl TokenGraph {
+    pub fn new<'a>(amms: impl IntoIterator<Item = &'a AMM>) -> TokenGraph {
+        let mut edges: HashMap<Felt, Vec<Hop>> = HashMap::new();
+        for amm in amms {
+            let tokens = amm.tokens();
+            for &token_in in &tokens {
+                for &token_out in &tokens {
+                    if token_in != token_out {
+                        edges.entry(token_in).or_default().push(Hop {
+                            pool: amm.address(),
+                            token_in,
+                            token_out,
+                        });
+                    }
+                }
+            }
+        }
+
+        TokenGraph { edges }
+    }
+
+    /// Returns every hop that sells `token`.
+    pub fn hops_from(&self, token: Felt) -> &[Hop] {
+        self.edges
+            .get(&token)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns every hop of the graph.
+    pub fn hops(&self) -> impl Iterator<Item = &Hop> {
+        self.edges.values().flatten()
+    }
+
+    /// Returns every cycle of two to `max_hops` hops through `start_token`.
+    ///
+    /// Cycles never go through the same pool or intermediate token twice. Both trading
+    /// directions of a loop are returned, as they are distinct opportunities.
+    pub fn cycles(&self, start_token: Felt, max_hops: usize) -> Vec<Cycle> {
+        let mut cycles = vec![];
+        let mut path = vec![];
+        self.walk(start_token, start_token, max_hops, &mut path, &mut cycles);
+        cycles
+    }
+
+    fn walk(
+        &self,
+        start_token: Felt,
+        token: Felt,
+        max_hops: usize,
+        path: &mut Vec<Hop>,
+        cycles: &mut Vec<Cycle>,
+    ) {
+        for hop in self.hops_from(token) {
+            if path.iter().any(|visited| visited.pool == hop.pool) {
+                continue;
+            }
+
+            if hop.token_out == start_token {
+                if !path.is_empty() {
+                    let mut hops = path.clone();
+                    hops.push(hop.clone());
+                    cycles.push(Cycle { hops });
+                }
+                continue;
+            }
+
+            let revisits_token = path.iter().any(|visited| visited.token_in == hop.token_out);
+            if revisits_token || path.len() + 2 > max_hops {
+                continue;
+            }
+
+            path.push(hop.clone());
+            self.walk(start_token, hop.token_out, max_hops, path, cycles);
+            path.pop();
+        }
+    }
+}
@@ -0,0 +1,212 @@
+pub mod graph;
+pub mod negative_cycles;
+pub mod profit;
+pub mod sizing;
+
+use std::{collections::HashMap, sync::Arc};
+
+use num_bigint::BigUint;
+use num_traits::CheckedSub;
+use starknet::{core::types::Felt, providers::Provider};
+
+use crate::{
+    amm::pool::{AutomatedMarketMaker, AMM},
+    errors::{AMMError, ArbitrageError},
+};
+
+use self::{
+    graph::{Cycle, TokenGraph},
+    negative_cycles::find_negative_cycles,
+};
+
+/// Cycles of up to three hops, which covers triangular arbitrage.
+pub const DEFAULT_MAX_HOPS: usize = 3;
+
+/// A token arbitrage cycles start from, and the amount of it each cycle is simulated with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartToken {
+    pub token: Felt,
+    pub amount_in: Felt,
+}
+
+/// A cycle that returns more of its start token than it takes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opportunity {
+    pub cycle: Cycle,
+    pub amount_in: Felt,
+    pub amount_out: Felt,
+}
+
+impl Opportunity {
+    /// Returns the amount of the start token gained by trading the cycle, or `None` when it
+    /// returns less than it takes.
+    pub fn profit(&self) -> Option<BigUint> {
+        self.amount_out
+            .to_biguint()
+            .checked_sub(&self.amount_in.to_biguint())
+    }
+}
+
+/// Finds the profitable cycles through a set of start tokens.
+#[derive(Debug, Clone)]
+pub struct ArbitrageScanner {
+    pub start_tokens: Vec<StartToken>,
+    pub max_hops: usize,
+}
+
+impl ArbitrageScanner {
+    pub fn new(start_tokens: Vec<StartToken>) -> ArbitrageScanner {
+        ArbitrageScanner {
+            start_tokens,
+            max_hops: DEFAULT_MAX_HOPS,
+        }
+    }
+
+    pub fn with_max_hops(mut self, max_hops: usize) -> ArbitrageScanner {
+        self.max_hops = max_hops;
+        self
+    }
+
+    /// Simulates every cycle through the start tokens and returns the profitable ones, most
+    /// profitable first.
+    pub async fn scan<P>(
+        &self,
+        amms: &HashMap<Felt, AMM>,
+        provider: Arc<P>,
+    ) -> Result<Vec<Opportunity>, ArbitrageError>
+    where
+        P: Provider + Send + Sync,
+    {
+        self.scan_touching(amms, None, provider).await
+    }
+
+    /// Same as `scan`, but only simulates the cycles that go through one of `changed_pools`,
+    /// such as the pools updated by the latest block.
+    pub async fn scan_changed<P>(
+        &self,
+        amms: &HashMap<Felt, AMM>,
+        changed_pools: &[Felt],
+        provider: Arc<P>,
+    ) -> Result<Vec<Opportunity>, ArbitrageError>
+    where
+        P: Provider + Send + Sync,
+    {
+        self.scan_touching(amms, Some(changed_pools), provider)
+            .await
+    }
+
+    /// Same as `scan`, but only simulates the negative cycles found by
+    /// `negative_cycles::find_negative_cycles` through the start tokens. This scales to large
+    /// pool sets, and is not limited to `max_hops`.
+    pub async fn scan_negative_cycles<P>(
+        &self,
+        amms: &HashMap<Felt, AMM>,
+        provider: Arc<P>,
+    ) -> Result<Vec<Opportunity>, ArbitrageError>
+    where
+        P: Provider + Send + Sync,
+    {
+        let mut opportunities = vec![];
+        for cycle in find_negative_cycles(amms) {
+            for start in &self.start_tokens {
+                let Some(cycle) = cycle.rotated_to(start.token) else {
+                    continue;
+                };
+                if let Some(opportunity) = confirm(cycle, start, amms, provider.clone()).await? {
+                    opportunities.push(opportunity);
+                }
+            }
+        }
+
+        opportunities.sort_by_key(|opportunity| std::cmp::Reverse(opportunity.profit()));
+
+        Ok(opportunities)
+    }
+
+    async fn scan_touching<P>(
+        &self,
+        amms: &HashMap<Felt, AMM>,
+        changed_pools: Option<&[Felt]>,
+        provider: Arc<P>,
+    ) -> Result<Vec<Opportunity>, ArbitrageError>
+    where
+        P: Provider + Send + Sync,
+    {
+        let graph = TokenGraph::new(amms.values());
+
+        let mut opportunities = vec![];
+        for start in &self.start_tokens {
+            for cycle in graph.cycles(start.token, self.max_hops) {
+                if let Some(changed_pools) = changed_pools {
+                    if !cycle.pools().any(|pool| changed_pools.contains(&pool)) {
+                        continue;
+                    }
+                }
+
+                if let Some(opportunity) = confirm(cycle, start, amms, provider.clone()).await? {
+                    opportunities.push(opportunity);
+                }
+            }
+        }
+
+        opportunities.sort_by_key(|opportunity| std::cmp::Reverse(opportunity.profit()));
+
+        Ok(opportunities)
+    }
+}
+
+/// Simulates `cycle` with the amount of `start`, and returns it as an opportunity when it is
+/// profitable.
+async fn confirm<P>(
+    cycle: Cycle,
+    start: &StartToken,
+    amms: &HashMap<Felt, AMM>,
+    provider: Arc<P>,
+) -> Result<Option<Opportunity>, ArbitrageError>
+where
+    P: Provider + Send + Sync,
+{
+    let amount_out = match simulate_cycle(&cycle, amms, start.amount_in, provider).await {
+        Ok(amount_out) => amount_out,
+        Err(ArbitrageError::AMMError(
+            err @ (AMMError::SwapSimulationError(_) | AMMError::ArithmeticError(_)),
+        )) => {
+            tracing::debug!(?cycle, ?err, "Skipping cycle that cannot be simulated");
+            return Ok(None);
+        }
+        Err(err) => return Err(err),
+    };
+
+    if amount_out.to_biguint() <= start.amount_in.to_biguint() {
+        return Ok(None);
+    }
+
+    Ok(Some(Opportunity {
+        cycle,
+        amount_in: start.amount_in,
+        amount_out,
+    }))
+}
+
+/// Returns the amount of the start token received for trading `amount_in` through `cycle`.
+pub async fn simulate_cycle<P>(
+    cycle: &Cycle,
+    amms: &HashMap<Felt, AMM>,
+    amount_in: Felt,
+    provider: Arc<P>,
+) -> Result<Felt, ArbitrageError>
+where
+    P: Provider + Send + Sync,
+{
+    let mut amount = amount_in;
+    for hop in &cycle.hops {
+        let amm = amms
+            .get(&hop.pool)
+            .ok_or(ArbitrageError::PoolNotFound(hop.pool))?;
+        amount = amm
+            .simulate_swap(hop.token_in, amount, provider.clone())
+            .await?;
+    }
+
+    Ok(amount)
+}
@@ -0,0 +1,146 @@
+use std::collections::{HashMap, HashSet};
+
+use num_traits::ToPrimitive;
+use starknet::core::types::Felt;
+
+use crate::amm::{
+    jediswap_v2::math::FEE_DENOMINATOR as JEDISWAP_V2_FEE_DENOMINATOR,
+    myswap::pool::FEE_DENOMINATOR as MYSWAP_FEE_DENOMINATOR,
+    pool::{AutomatedMarketMaker, AMM},
+    sithswap::math::FEE_DENOMINATOR as SITHSWAP_FEE_DENOMINATOR,
+    types::fee_factor,
+};
+
+use super::graph::{Cycle, Hop, TokenGraph};
+
+/// Improvement below which a relaxation is put down to float rounding rather than arbitrage.
+pub const RELAXATION_TOLERANCE: f64 = 1e-12;
+
+/// A hop of the graph with the token indices it connects.
+struct Edge {
+    hop: Hop,
+    from: usize,
+    to: usize,
+    weight: f64,
+}
+
+/// Returns the fraction of the input that is left once `amm` has taken its fee.
+pub fn edge_fee_multiplier(amm: &AMM) -> f64 {
+    let v2_fee_factor = |fee: u32| fee_factor(fee).to_f64().unwrap_or_default() / 1000.0;
+
+    match amm {
+        AMM::JediswapPool(pool) => v2_fee_factor(pool.fee),
+        AMM::TenkSwapPool(pool) => v2_fee_factor(pool.fee),
+        AMM::JediswapV2Pool(pool) => {
+            1.0 - f64::from(pool.fee) / f64::from(JEDISWAP_V2_FEE_DENOMINATOR)
+        }
+        AMM::EkuboPool(pool) => 1.0 - pool.key.fee as f64 / 2f64.powi(128),
+        AMM::MySwapPool(pool) => 1.0 - f64::from(pool.fee) / f64::from(MYSWAP_FEE_DENOMINATOR),
+        AMM::SithSwapPool(pool) => 1.0 - f64::from(pool.fee) / f64::from(SITHSWAP_FEE_DENOMINATOR),
+    }
+}
+
+/// Returns `-ln(marginal price * fee factor)` of trading through `hop`, or `None` when `amm`
+/// cannot price it.
+///
+/// A cycle whose weights add up to less than zero returns more than it takes at the margin.
+pub fn hop_weight(amm: &AMM, hop: &Hop) -> Option<f64> {
+    let price = amm.calculate_price(hop.token_in, hop.token_out).ok()?;
+    let rate = price * edge_fee_multiplier(amm);
+    (rate.is_finite() && rate > 0.0).then(|| -rate.ln())
+}
+
+/// Runs Bellman-Ford over every pool of `amms` and returns the negative cycles it detects.
+///
+/// The weights are marginal, so this is a pre-filter: a cycle found here is profitable for an
+/// infinitesimal trade only, and has to be confirmed with the pools' swap math, such as with
+/// `simulate_cycle` or `sizing::optimal_trade_size`. Not every negative cycle of the graph is
+/// returned, but there is at least one whenever the graph has any.
+pub fn find_negative_cycles(amms: &HashMap<Felt, AMM>) -> Vec<Cycle> {
+    let graph = TokenGraph::new(amms.values());
+
+    let mut tokens = HashMap::new();
+    let mut edges = vec![];
+    for hop in graph.hops() {
+        let Some(weight) = amms.get(&hop.pool).and_then(|amm| hop_weight(amm, hop)) else {
+            continue;
+        };
+        let next_index = tokens.len();
+        let from = *tokens.entry(hop.token_in).or_insert(next_index);
+        let next_index = tokens.len();
+        let to = *tokens.entry(hop.token_out).or_insert(next_index);
+        edges.push(Edge {
+            hop: hop.clone(),
+            from,
+            to,
+            weight,
+        });
+    }
+
+    // Every token starts at distance zero, as if reached from a virtual source, so that
+    // cycles are found wherever they are in the graph.
+    let token_count = tokens.len();
+    let mut distances = vec![0.0; token_count];
+    let mut predecessors = vec![None; token_count];
+    let mut relaxed = vec![];
+    for _ in 0..=token_count {
+        relaxed.clear();
+        for (index, edge) in edges.iter().enumerate() {
+            let distance = distances[edge.from] + edge.weight;
+            if distance < distances[edge.to] - RELAXATION_TOLERANCE {
+                distances[edge.to] = distance;
+                predecessors[edge.to] = Some(index);
+                relaxed.push(edge.to);
+            }
+        }
+
+        if relaxed.is_empty() {
+            return vec![];
+        }
+    }
+
+    // Distances still shrink after as many rounds as there are tokens, so the predecessors of
+    // the tokens relaxed last lead into negative cycles.
+    let mut seen = HashSet::new();
+    let mut cycles = vec![];
+    for token in relaxed {
+        let Some(cycle) = trace_cycle(token, &predecessors, &edges) else {
+            continue;
+        };
+
+        let mut pools = cycle.pools().collect::<Vec<_>>();
+        pools.sort();
+        if seen.insert(pools) {
+            cycles.push(cycle);
+        }
+    }
+
+    cycles
+}
+
+/// Follows the predecessors of `token` back into the cycle they lead to.
+fn trace_cycle(token: usize, predecessors: &[Option<usize>], edges: &[Edge]) -> Option<Cycle> {
+    // Walking back once per token is guaranteed to end up on the cycle.
+    let mut start = token;
+    for _ in 0..predecessors.len() {
+        start = edges[predecessors[start]?].from;
+    }
+
+    let mut hops = vec![];
+    let mut current = start;
+    loop {
+        let edge = &edges[predecessors[current]?];
+        hops.push(edge.hop.clone());
+        current = edge.from;
+        if current == start {
+            break;
+        }
+        if hops.len() > predecessors.len() {
+            return None;
+        }
+    }
+    hops.reverse();
+
+    let pools = hops.iter().map(|hop| hop.pool).collect::<HashSet<_>>();
+    (pools.len() == hops.len()).then_some(Cycle { hops })
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use num_bigint::BigUint;
+use num_traits::{CheckedSub, One, Zero};
+use starknet::{
+    core::types::{BlockId, BroadcastedInvokeTransaction, BroadcastedTransaction, Felt},
+    providers::Provider,
+};
+
+use crate::{
+    amm::pool::{AutomatedMarketMaker, AMM},
+    errors::ArbitrageError,
+};
+
+use super::{
+    graph::{Hop, TokenGraph},
+    Opportunity,
+};
+
+/// Fee of executing a cycle, in the fee token, as a fixed cost plus a cost per hop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HopFeeModel {
+    pub base_fee: BigUint,
+    pub fee_per_hop: BigUint,
+}
+
+impl HopFeeModel {
+    pub fn new(base_fee: Felt, fee_per_hop: Felt) -> HopFeeModel {
+        HopFeeModel {
+            base_fee: base_fee.to_biguint(),
+            fee_per_hop: fee_per_hop.to_biguint(),
+        }
+    }
+
+    /// Returns the predicted fee of a cycle of `hops` hops.
+    pub fn fee(&self, hops: usize) -> BigUint {
+        &self.base_fee + &self.fee_per_hop * hops
+    }
+
+    /// Fits the cost per hop so that the model predicts `fee` for a cycle of `hops` hops, such
+    /// as the fee of an executed or estimated invoke.
+    pub fn calibrate(&mut self, hops: usize, fee: Felt) {
+        let fee = fee.to_biguint();
+        if hops == 0 || fee <= self.base_fee {
+            return;
+        }
+        // Rounded up, so that the model never underestimates the fee it was fitted to.
+        self.fee_per_hop = (fee - &self.base_fee + hops - 1u32) / hops;
+    }
+}
+
+/// Returns the overall fee of `invoke` at `block_id`, in the fee token of its version: ETH for
+/// V1 transactions and STRK for V3 ones.
+pub async fn estimate_invoke_fee<P>(
+    invoke: BroadcastedInvokeTransaction,
+    block_id: BlockId,
+    provider: Arc<P>,
+) -> Result<Felt, ArbitrageError>
+where
+    P: Provider + Send + Sync,
+{
+    let estimate = provider
+        .estimate_fee_single(BroadcastedTransaction::Invoke(invoke), [], block_id)
+        .await?;
+
+    Ok(estimate.overall_fee)
+}
+
+/// An opportunity valued in the numeraire, net of the fee of executing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvaluatedOpportunity {
+    pub opportunity: Opportunity,
+    /// Profit of the cycle, in the numeraire.
+    pub gross_profit: BigUint,
+    /// Fee of executing the cycle, in the numeraire.
+    pub fee: BigUint,
+}
+
+impl EvaluatedOpportunity {
+    /// Returns the gross profit less the fee, or `None` when the fee exceeds it.
+    pub fn net_profit(&self) -> Option<BigUint> {
+        self.gross_profit.checked_sub(&self.fee)
+    }
+}
+
+/// Values opportunities and their execution fee in a common numeraire, and only keeps the
+/// ones whose profit net of the fee clears `min_net_profit`.
+///
+/// Amounts are converted at the marginal prices of the pools along the shortest path from
+/// their token to the numeraire, typically the fee token itself.
+#[derive(Debug, Clone)]
+pub struct ProfitEvaluator {
+    pub numeraire: Felt,
+    pub fee_token: Felt,
+    pub fee_model: HopFeeModel,
+    /// Smallest net profit reported, in the numeraire.
+    pub min_net_profit: BigUint,
+}
+
+impl ProfitEvaluator {
+    pub fn new(numeraire: Felt, fee_token: Felt, fee_model: HopFeeModel) -> ProfitEvaluator {
+        ProfitEvaluator {
+            numeraire,
+            fee_token,
+            fee_model,
+            min_net_profit: BigUint::one(),
+        }
+    }
+
+    pub fn with_min_net_profit(mut self, min_net_profit: Felt) -> ProfitEvaluator {
+        self.min_net_profit = min_net_profit.to_biguint();
+        self
+    }
+
+    /// Evaluates `opportunity` with the fee predicted by the fee model.
+    pub fn evaluate(
+        &self,
+        opportunity: Opportunity,
+        amms: &HashMap<Felt, AMM>,
+    ) -> Result<Option<EvaluatedOpportunity>, ArbitrageError> {
+        let fee = self.fee_model.fee(opportunity.cycle.hops.len());
+        self.evaluate_with_fee(opportunity, Felt::from(fee), amms)
+    }
+
+    /// Evaluates `opportunity` with a known `fee` in the fee token, such as the result of
+    /// `estimate_invoke_fee`. Returns `None` when it does not clear `min_net_profit`.
+    pub fn evaluate_with_fee(
+        &self,
+        opportunity: Opportunity,
+        fee: Felt,
+        amms: &HashMap<Felt, AMM>,
+    ) -> Result<Option<EvaluatedOpportunity>, ArbitrageError> {
+        let Some(profit) = opportunity.profit() else {
+            return Ok(None);
+        };
+        let gross_profit = self.to_numeraire(opportunity.cycle.start_token(), &profit, amms)?;
+        let fee = self.to_numeraire(self.fee_token, &fee.to_biguint(), amms)?;
+
+        if gross_profit < &fee + &self.min_net_profit {
+            return Ok(None);
+        }
+
+        Ok(Some(EvaluatedOpportunity {
+            opportunity,
+            gross_profit,
+            fee,
+        }))
+    }
+
+    /// Evaluates every opportunity with the fee model, and returns the ones that clear
+    /// `min_net_profit`, most profitable first.
+    pub fn filter(
+        &self,
+        opportunities: impl IntoIterator<Item = Opportunity>,
+        amms: &HashMap<Felt, AMM>,
+    ) -> Result<Vec<EvaluatedOpportunity>, ArbitrageError> {
+        let mut evaluated = vec![];
+        for opportunity in opportunities {
+            if let Some(opportunity) = self.evaluate(opportunity, amms)? {
+                evaluated.push(opportunity);
+            }
+        }
+
+        evaluated.sort_by_key(|opportunity| std::cmp::Reverse(opportunity.net_profit()));
+
+        Ok(evaluated)
+    }
+
+    /// Converts `amount` of `token` into the numeraire.
+    pub fn to_numeraire(
+        &self,
+        token: Felt,
+        amount: &BigUint,
+        amms: &HashMap<Felt, AMM>,
+    ) -> Result<BigUint, ArbitrageError> {
+        if token == self.numeraire {
+            return Ok(amount.clone());
+        }
+
+        let path =
+            price_path(token, self.numeraire, amms).ok_or(ArbitrageError::NoPricePath(token))?;
+
+        let mut numerator = amount.clone();
+        let mut denominator = BigUint::one();
+        for hop in path {
+            let amm = amms
+                .get(&hop.pool)
+                .ok_or(ArbitrageError::PoolNotFound(hop.pool))?;
+            let price = amm.calculate_price_exact(hop.token_in, hop.token_out)?;
+            let (decimals_in, decimals_out) =
+                token_decimals(amm, &hop).ok_or(ArbitrageError::NoPricePath(token))?;
+
+            // Prices are normalized by decimals, and amounts are not.
+            numerator *= price.numerator * BigUint::from(10u32).pow(decimals_out.into());
+            denominator *= price.denominator * BigUint::from(10u32).pow(decimals_in.into());
+        }
+
+        Ok(numerator / denominator)
+    }
+}
+
+/// Returns the hops of a shortest path from `token` to `numeraire`.
+///
+/// Each token of the path is reached through the pool with the most of it, so that amounts are
+/// not converted at the price of a dust pool. Ties go to the lowest pool address.
+fn price_path(token: Felt, numeraire: Felt, amms: &HashMap<Felt, AMM>) -> Option<Vec<Hop>> {
+    let graph = TokenGraph::new(amms.values());
+
+    let mut reached_by: HashMap<Felt, &Hop> = HashMap::new();
+    let mut visited = HashSet::from([token]);
+    let mut frontier = vec![token];
+    while !frontier.is_empty() {
+        if visited.contains(&numeraire) {
+            let mut path = vec![];
+            let mut current = numeraire;
+            while let Some(&hop) = reached_by.get(&current) {
+                path.push(hop.clone());
+                current = hop.token_in;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let mut next: HashMap<Felt, (&Hop, BigUint)> = HashMap::new();
+        for current in frontier {
+            for hop in graph.hops_from(current) {
+                if visited.contains(&hop.token_out) {
+                    continue;
+                }
+
+                let depth = amms
+                    .get(&hop.pool)
+                    .map(|amm| pool_depth(amm, hop.token_out))
+                    .unwrap_or_default();
+                let deeper = match next.get(&hop.token_out) {
+                    Some((best, best_depth)) => {
+                        (&depth, std::cmp::Reverse((hop.pool, hop.token_in)))
+                            > (best_depth, std::cmp::Reverse((best.pool, best.token_in)))
+                    }
+                    None => true,
+                };
+                if deeper {
+                    next.insert(hop.token_out, (hop, depth));
+                }
+            }
+        }
+
+        frontier = next.keys().copied().collect();
+        visited.extend(next.keys());
+        reached_by.extend(next.into_iter().map(|(token, (hop, _))| (token, hop)));
+    }
+
+    None
+}
+
+/// Returns the amount of `token` held by `amm`, or its virtual reserve for concentrated
+/// liquidity pools.
+fn pool_depth(amm: &AMM, token: Felt) -> BigUint {
+    let (token_a, reserve_a, reserve_b) = match amm {
+        AMM::JediswapPool(pool) => (pool.token_a, pool.reserve_a, pool.reserve_b),
+        AMM::TenkSwapPool(pool) => (pool.token_a, pool.reserve_a, pool.reserve_b),
+        AMM::MySwapPool(pool) => (pool.token_a, pool.reserve_a, pool.reserve_b),
+        AMM::SithSwapPool(pool) => (pool.token_a, pool.reserve_a, pool.reserve_b),
+        AMM::JediswapV2Pool(pool) => {
+            return virtual_reserve(pool.liquidity, &pool.sqrt_price, 96, token == pool.token_a)
+        }
+        AMM::EkuboPool(pool) => {
+            return virtual_reserve(
+                pool.liquidity,
+                &pool.sqrt_ratio,
+                128,
+                token == pool.key.token0,
+            )
+        }
+    };
+
+    if token == token_a {
+        reserve_a.to_biguint()
+    } else {
+        reserve_b.to_biguint()
+    }
+}
+
+/// Returns `liquidity / sqrt_price` of token 0, or `liquidity * sqrt_price` of token 1, with
+/// `sqrt_price` a fixed point number of `fraction_bits` bits.
+fn virtual_reserve(
+    liquidity: u128,
+    sqrt_price: &BigUint,
+    fraction_bits: u32,
+    token0: bool,
+) -> BigUint {
+    let liquidity = BigUint::from(liquidity);
+    if token0 {
+        if sqrt_price.is_zero() {
+            return BigUint::zero();
+        }
+        (liquidity << fraction_bits) / sqrt_price
+    } else {
+        (liquidity * sqrt_price) >> fraction_bits
+    }
+}
+
+/// Returns the decimals of the input and output tokens of `hop`.
+fn token_decimals(amm: &AMM, hop: &Hop) -> Option<(u8, u8)> {
+    let (token_a, token_b, decimals_a, decimals_b) = match amm {
+        AMM::JediswapPool(pool) => (
+            pool.token_a,
+            pool.token_b,
+            pool.token_a_decimals,
+            pool.token_b_decimals,
+        ),
+        AMM::TenkSwapPool(pool) => (
+            pool.token_a,
+            pool.token_b,
+            pool.token_a_decimals,
+            pool.token_b_decimals,
+        ),
+        AMM::JediswapV2Pool(pool) => (
+            pool.token_a,
+            pool.token_b,
+            pool.token_a_decimals,
+            pool.token_b_decimals,
+        ),
+        AMM::EkuboPool(pool) => (
+            pool.key.token0,
+            pool.key.token1,
+            pool.token0_decimals,
+            pool.token1_decimals,
+        ),
+        AMM::MySwapPool(pool) => (
+            pool.token_a,
+            pool.token_b,
+            pool.token_a_decimals,
+            pool.token_b_decimals,
+        ),
+        AMM::SithSwapPool(pool) => (
+            pool.token_a,
+            pool.token_b,
+            pool.token_a_decimals,
+            pool.token_b_decimals,
+        ),
+    };
+
+    if hop.token_in == token_a && hop.token_out == token_b {
+        Some((decimals_a, decimals_b))
+    } else if hop.token_in == token_b && hop.token_out == token_a {
+        Some((decimals_b, decimals_a))
+    } else {
+        None
+    }
+}
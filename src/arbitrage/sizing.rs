@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use num_bigint::{BigInt, BigUint};
+use num_traits::{One, Zero};
+use starknet::core::types::Felt;
+
+use crate::{
+    amm::{
+        myswap::pool::FEE_DENOMINATOR as MYSWAP_FEE_DENOMINATOR,
+        pool::{AutomatedMarketMaker, AMM},
+        sithswap::math::FEE_DENOMINATOR as SITHSWAP_FEE_DENOMINATOR,
+        types::fee_factor,
+    },
+    errors::ArbitrageError,
+};
+
+use super::{graph::Cycle, Opportunity};
+
+/// A constant-product leg of a cycle, pricing `amount_in` as
+/// `fee_numerator * amount_in * reserve_out / (fee_denominator * reserve_in + fee_numerator * amount_in)`.
+struct ConstantProductLeg {
+    fee_numerator: BigUint,
+    fee_denominator: BigUint,
+    reserve_in: BigUint,
+    reserve_out: BigUint,
+}
+
+/// Returns the input of at most `max_amount_in` that maximizes the profit of trading `cycle`,
+/// or `None` when no input is profitable.
+///
+/// When every leg is a constant-product pool the cycle behaves like a single virtual pool, and
+/// the optimum is solved in closed form. Other curves are searched by ternary search over
+/// `[1, max_amount_in]`, which assumes the profit is unimodal in the input. The returned amounts
+/// are always the exact output of the pools' own swap math.
+pub fn optimal_trade_size(
+    cycle: &Cycle,
+    amms: &HashMap<Felt, AMM>,
+    max_amount_in: Felt,
+) -> Result<Option<Opportunity>, ArbitrageError> {
+    let pools = cycle
+        .hops
+        .iter()
+        .map(|hop| {
+            amms.get(&hop.pool)
+                .ok_or(ArbitrageError::PoolNotFound(hop.pool))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let max_amount_in = max_amount_in.to_biguint();
+    if max_amount_in.is_zero() {
+        return Ok(None);
+    }
+
+    let legs = cycle
+        .hops
+        .iter()
+        .zip(&pools)
+        .map(|(hop, amm)| constant_product_leg(amm, hop.token_in))
+        .collect::<Option<Vec<_>>>();
+
+    let amount_in = match legs {
+        Some(legs) => match closed_form_amount_in(&legs) {
+            Some(amount_in) => amount_in.min(max_amount_in),
+            None => return Ok(None),
+        },
+        None => ternary_search(cycle, &pools, max_amount_in),
+    };
+
+    let Some(amount_out) = simulate(cycle, &pools, &amount_in) else {
+        return Ok(None);
+    };
+    if amount_out <= amount_in {
+        return Ok(None);
+    }
+
+    Ok(Some(Opportunity {
+        cycle: cycle.clone(),
+        amount_in: Felt::from(amount_in),
+        amount_out: Felt::from(amount_out),
+    }))
+}
+
+/// Composes the legs into one virtual pool `a * x / (b + c * x)` and returns the input that
+/// maximizes `a * x / (b + c * x) - x`, which is `(sqrt(a * b) - b) / c`.
+fn closed_form_amount_in(legs: &[ConstantProductLeg]) -> Option<BigUint> {
+    let (mut a, mut b, mut c) = (BigUint::one(), BigUint::one(), BigUint::zero());
+    for leg in legs {
+        let leg_a = &leg.fee_numerator * &leg.reserve_out;
+        let leg_b = &leg.fee_denominator * &leg.reserve_in;
+        c = &leg_b * c + &leg.fee_numerator * &a;
+        a *= leg_a;
+        b *= leg_b;
+    }
+
+    // The marginal price of the cycle is a / b, so it only pays when it is above one.
+    if a <= b || c.is_zero() {
+        return None;
+    }
+
+    let amount_in = ((a * &b).sqrt() - b) / c;
+    (!amount_in.is_zero()).then_some(amount_in)
+}
+
+/// Narrows `[1, max_amount_in]` down to the most profitable input, treating inputs that
+/// cannot be simulated as the least profitable.
+fn ternary_search(cycle: &Cycle, pools: &[&AMM], max_amount_in: BigUint) -> BigUint {
+    let profit = |amount_in: &BigUint| {
+        simulate(cycle, pools, amount_in)
+            .map(|amount_out| BigInt::from(amount_out) - BigInt::from(amount_in.clone()))
+    };
+
+    let (mut low, mut high) = (BigUint::one(), max_amount_in);
+    while &high - &low > BigUint::from(2u32) {
+        let third = (&high - &low) / 3u32;
+        let left = &low + &third;
+        let right = &high - &third;
+        if profit(&left) < profit(&right) {
+            low = left + 1u32;
+        } else {
+            high = right;
+        }
+    }
+
+    let mut best = low.clone();
+    let mut best_profit = profit(&low);
+    while low < high {
+        low += 1u32;
+        let low_profit = profit(&low);
+        if low_profit > best_profit {
+            best = low.clone();
+            best_profit = low_profit;
+        }
+    }
+
+    best
+}
+
+/// Trades `amount_in` through copies of the cycle's pools, returning `None` when a leg cannot
+/// be simulated.
+fn simulate(cycle: &Cycle, pools: &[&AMM], amount_in: &BigUint) -> Option<BigUint> {
+    let mut amount = Felt::from(amount_in.clone());
+    for (hop, amm) in cycle.hops.iter().zip(pools) {
+        amount = (*amm)
+            .clone()
+            .simulate_swap_mut(hop.token_in, hop.token_out, amount)
+            .ok()?;
+    }
+
+    Some(amount.to_biguint())
+}
+
+/// Returns the constant-product model of `amm` when sold `token_in`, or `None` for other curves.
+fn constant_product_leg(amm: &AMM, token_in: Felt) -> Option<ConstantProductLeg> {
+    let v2_fee = |fee: u32| (fee_factor(fee), BigUint::from(1000u32));
+
+    let (token_a, token_b, reserve_a, reserve_b, (fee_numerator, fee_denominator)) = match amm {
+        AMM::JediswapPool(pool) => (
+            pool.token_a,
+            pool.token_b,
+            pool.reserve_a,
+            pool.reserve_b,
+            v2_fee(pool.fee),
+        ),
+        AMM::TenkSwapPool(pool) => (
+            pool.token_a,
+            pool.token_b,
+            pool.reserve_a,
+            pool.reserve_b,
+            v2_fee(pool.fee),
+        ),
+        AMM::MySwapPool(pool) => (
+            pool.token_a,
+            pool.token_b,
+            pool.reserve_a,
+            pool.reserve_b,
+            (
+                BigUint::from(MYSWAP_FEE_DENOMINATOR.saturating_sub(pool.fee)),
+                BigUint::from(MYSWAP_FEE_DENOMINATOR),
+            ),
+        ),
+        AMM::SithSwapPool(pool) if !pool.stable => (
+            pool.token_a,
+            pool.token_b,
+            pool.reserve_a,
+            pool.reserve_b,
+            (
+                BigUint::from(SITHSWAP_FEE_DENOMINATOR.saturating_sub(pool.fee)),
+                BigUint::from(SITHSWAP_FEE_DENOMINATOR),
+            ),
+        ),
+        _ => return None,
+    };
+
+    let (reserve_in, reserve_out) = if token_in == token_a {
+        (reserve_a, reserve_b)
+    } else if token_in == token_b {
+        (reserve_b, reserve_a)
+    } else {
+        return None;
+    };
+
+    Some(ConstantProductLeg {
+        fee_numerator,
+        fee_denominator,
+        reserve_in: reserve_in.to_biguint(),
+        reserve_out: reserve_out.to_biguint(),
+    })
+}
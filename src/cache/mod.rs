@@ -10,18 +10,23 @@ use std::{
 use serde::{Deserialize, Serialize};
 
 use starknet::{
-    core::types::{Felt, StarknetError},
+    core::types::{BlockId, Felt},
     providers::Provider,
 };
 use tokio::task::JoinHandle;
 
 use crate::{
     amm::{
+        ekubo::factory::EkuboFactory,
         factory::{AutomatedMarketMakerFactory, Factory},
-        jediswap::{self, factory::JediswapFactory},
-        tenkswap::{self, factory::TenKFactory},
+        jediswap::factory::JediswapFactory,
+        jediswap_v2::factory::JediswapV2Factory,
+        myswap::factory::MySwapFactory,
+        sithswap::factory::SithSwapFactory,
+        tenkswap::factory::TenKFactory,
     },
     errors::{AMMError, CheckpointError},
+    token_registry::TokenRegistry,
 };
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -30,6 +35,9 @@ pub struct Checkpoint {
     pub block_number: u64,
     pub factories: Vec<Factory>,
     pub amms: Vec<AMM>,
+    /// Metadata of the tokens of `amms`, which only refer to them by address.
+    #[serde(default)]
+    pub tokens: TokenRegistry,
 }
 
 impl Checkpoint {
@@ -38,12 +46,14 @@ impl Checkpoint {
         block_number: u64,
         factories: Vec<Factory>,
         amms: Vec<AMM>,
+        tokens: TokenRegistry,
     ) -> Checkpoint {
         Checkpoint {
             timestamp,
             block_number,
             factories,
             amms,
+            tokens,
         }
     }
 }
@@ -58,37 +68,42 @@ where
     P: Provider + Send + Sync + 'static,
     A: AsRef<Path>,
 {
-    let current_block = provider.block_number().await.unwrap();
+    let current_block = provider.block_number().await?;
 
-    let checkpoint: Checkpoint =
+    let mut checkpoint: Checkpoint =
         serde_json::from_str(read_to_string(&path_to_checkpoint)?.as_str())?;
-
-    // Sort all of the pools from the checkpoint into uniswap_v2_pools and uniswap_v3_pools pools so we can sync them concurrently
-    let (jediswap_pools, tenkswap_pools) = sort_amms(checkpoint.amms);
+    // Pools that are only synced, rather than populated again, keep the decimals restored here.
+    checkpoint.tokens.populate_decimals(&mut checkpoint.amms);
+    let tokens = checkpoint.tokens;
+    let factories = checkpoint
+        .factories
+        .into_iter()
+        .map(|factory| factory.with_token_registry(tokens.clone()))
+        .collect::<Vec<_>>();
+
+    // Group the pools from the checkpoint by AMM variant so each group can be synced concurrently
+    let amms_by_variant = sort_amms(checkpoint.amms);
 
     let mut aggregated_amms = vec![];
     let mut handles = vec![];
 
-    // Sync all uniswap v2 pools from checkpoint
-    if !jediswap_pools.is_empty() {
-        handles.push(
-            batch_sync_amms_from_checkpoint(jediswap_pools, Some(current_block), provider.clone())
-                .await,
-        );
-    }
-
-    // Sync all uniswap v2 pools from checkpoint
-    if !tenkswap_pools.is_empty() {
+    for amms in amms_by_variant {
         handles.push(
-            batch_sync_amms_from_checkpoint(tenkswap_pools, Some(current_block), provider.clone())
-                .await,
+            batch_sync_amms_from_checkpoint(
+                amms,
+                checkpoint.block_number + 1,
+                BlockId::Number(current_block),
+                tokens.clone(),
+                provider.clone(),
+            )
+            .await,
         );
     }
 
     // Sync all pools from the since synced block
     handles.extend(
         get_new_amms_from_range(
-            checkpoint.factories.clone(),
+            factories.clone(),
             checkpoint.block_number,
             current_block,
             step,
@@ -100,27 +115,24 @@ where
     for handle in handles {
         match handle.await {
             Ok(sync_result) => aggregated_amms.extend(sync_result?),
-            Err(err) => {
-                {
-                    if err.is_panic() {
-                        // Resume the panic on the main task
-                        resume_unwind(err.into_panic());
-                    }
-                }
+            Err(err) if err.is_panic() => {
+                // Resume the panic on the main task
+                resume_unwind(err.into_panic());
             }
+            Err(err) => return Err(err.into()),
         }
     }
 
     //update the sync checkpoint
     save_checkpoint(
-        checkpoint.factories.clone(),
+        factories.clone(),
         &aggregated_amms,
+        &tokens,
         current_block,
         path_to_checkpoint,
-    )
-    .unwrap();
+    )?;
 
-    Ok((checkpoint.factories, aggregated_amms))
+    Ok((factories, aggregated_amms))
 }
 
 pub async fn get_new_amms_from_range<P>(
@@ -147,7 +159,7 @@ where
                 .await?;
 
             factory
-                .populate_amm_data(&mut amms, Some(to_block), provider.clone())
+                .populate_amm_data(&mut amms, BlockId::Number(to_block), provider.clone())
                 .await?;
 
             // TODO :  Clean empty pools
@@ -159,27 +171,54 @@ where
     handles
 }
 
+/// Syncs `amms`, restored from a checkpoint taken at `from_block - 1`, up to `block_id`.
 pub async fn batch_sync_amms_from_checkpoint<P>(
     mut amms: Vec<AMM>,
-    block_number: Option<u64>,
+    from_block: u64,
+    block_id: BlockId,
+    tokens: TokenRegistry,
     provider: Arc<P>,
 ) -> JoinHandle<Result<Vec<AMM>, AMMError>>
 where
     P: Provider + Send + Sync + 'static,
 {
-    let factory = match amms[0] {
-        AMM::JediswapPool(_) => Some(Factory::JediswapFactory(JediswapFactory::new(Felt::ZERO))),
-        AMM::TenkSwapPool(_) => Some(Factory::TenKFactory(TenKFactory::new(Felt::ZERO))),
-    };
+    let factory = amms
+        .first()
+        .map(|amm| match amm {
+            AMM::JediswapPool(_) => Factory::JediswapFactory(JediswapFactory::new(Felt::ZERO)),
+            AMM::TenkSwapPool(_) => Factory::TenKFactory(TenKFactory::new(Felt::ZERO)),
+            AMM::JediswapV2Pool(_) => {
+                Factory::JediswapV2Factory(JediswapV2Factory::new(Felt::ZERO, 0))
+            }
+            AMM::EkuboPool(_) => Factory::EkuboFactory(EkuboFactory::new(Felt::ZERO, 0)),
+            AMM::MySwapPool(_) => Factory::MySwapFactory(MySwapFactory::new(Felt::ZERO)),
+            AMM::SithSwapPool(_) => Factory::SithSwapFactory(SithSwapFactory::new(Felt::ZERO)),
+        })
+        .map(|factory| factory.with_token_registry(tokens));
 
     // Spawn a new thread to get all pools and sync data for each dex
     tokio::spawn(async move {
         if let Some(factory) = factory {
             if amms_are_congruent(&amms) {
-                // Get all pool data via batched calls
-                factory
-                    .populate_amm_data(&mut amms, block_number, provider)
-                    .await?;
+                match factory {
+                    // Concentrated liquidity pools keep their tick data in the checkpoint
+                    Factory::JediswapV2Factory(factory) => {
+                        factory
+                            .sync_amm_data(&mut amms, from_block, block_id, provider)
+                            .await?
+                    }
+                    Factory::EkuboFactory(factory) => {
+                        factory
+                            .sync_amm_data(&mut amms, from_block, block_id, provider)
+                            .await?
+                    }
+                    // Get all pool data via batched calls
+                    factory => {
+                        factory
+                            .populate_amm_data(&mut amms, block_id, provider)
+                            .await?
+                    }
+                }
 
                 // TODO : Clean empty pools
 
@@ -193,21 +232,26 @@ where
     })
 }
 
-pub fn sort_amms(amms: Vec<AMM>) -> (Vec<AMM>, Vec<AMM>) {
-    let mut jediswap_pools = vec![];
-    let mut tenkswap_pools = vec![];
+/// Groups `amms` by variant, preserving their order within each group.
+pub fn sort_amms(amms: Vec<AMM>) -> Vec<Vec<AMM>> {
+    let mut groups: Vec<Vec<AMM>> = vec![];
     for amm in amms {
-        match amm {
-            AMM::JediswapPool(_) => jediswap_pools.push(amm),
-            AMM::TenkSwapPool(_) => tenkswap_pools.push(amm),
+        match groups
+            .iter_mut()
+            .find(|group| std::mem::discriminant(&group[0]) == std::mem::discriminant(&amm))
+        {
+            Some(group) => group.push(amm),
+            None => groups.push(vec![amm]),
         }
     }
 
-    (jediswap_pools, tenkswap_pools)
+    groups
 }
 
 pub fn amms_are_congruent(amms: &[AMM]) -> bool {
-    let expected_amm = &amms[0];
+    let Some(expected_amm) = amms.first() else {
+        return true;
+    };
 
     for amm in amms {
         if std::mem::discriminant(expected_amm) != std::mem::discriminant(amm) {
@@ -217,45 +261,10 @@ pub fn amms_are_congruent(amms: &[AMM]) -> bool {
     true
 }
 
-pub async fn get_new_pools_from_range<P>(
-    factories: Vec<Factory>,
-    from_block: u64,
-    to_block: u64,
-    step: u64,
-    provider: Arc<P>,
-) -> Vec<JoinHandle<Result<Vec<AMM>, AMMError>>>
-where
-    P: Provider + Send + Sync + 'static,
-{
-    // Create the filter with all the pair created events
-    // Aggregate the populated pools from each thread
-    let mut handles = vec![];
-
-    for factory in factories {
-        let provider = provider.clone();
-
-        // Spawn a new thread to get all pools and sync data for each dex
-        handles.push(tokio::spawn(async move {
-            let mut pools = factory
-                .get_all_pools_from_logs(from_block, to_block, step, provider.clone())
-                .await?;
-
-            factory
-                .populate_amm_data(&mut pools, Some(to_block), provider.clone())
-                .await?;
-
-            // TODO :Clean empty pools
-
-            Ok::<_, AMMError>(pools)
-        }));
-    }
-
-    handles
-}
-
 pub fn save_checkpoint<P>(
     factories: Vec<Factory>,
     amms: &[AMM],
+    tokens: &TokenRegistry,
     latest_block: u64,
     checkpoint_path: P,
 ) -> Result<(), CheckpointError>
@@ -267,19 +276,21 @@ where
         latest_block,
         factories,
         amms.to_vec(),
+        tokens.clone(),
     );
 
-    std::fs::write(checkpoint_path, serde_json::to_string_pretty(&checkpoint)?).unwrap();
+    std::fs::write(checkpoint_path, serde_json::to_string_pretty(&checkpoint)?)?;
 
     Ok(())
 }
 
 // Deconstructs the checkpoint into a Vec<AMM>
-pub fn read_checkpoint<P>(checkpoint_path: P) -> Result<(Vec<AMM>, u64), StarknetError>
+pub fn read_checkpoint<P>(checkpoint_path: P) -> Result<(Vec<AMM>, u64), CheckpointError>
 where
     P: AsRef<Path>,
 {
-    let checkpoint: Checkpoint =
-        serde_json::from_str(read_to_string(checkpoint_path).unwrap().as_str()).unwrap();
+    let mut checkpoint: Checkpoint =
+        serde_json::from_str(read_to_string(checkpoint_path)?.as_str())?;
+    checkpoint.tokens.populate_decimals(&mut checkpoint.amms);
     Ok((checkpoint.amms, checkpoint.block_number))
 }
@@ -1,8 +1,10 @@
 use starknet::{
-    core::types::{Felt, U256},
+    accounts::{single_owner::SignError, AccountError},
+    core::types::{BlockId, Felt, U256},
     providers::ProviderError,
+    signers::local_wallet::SignError as LocalWalletSignError,
 };
-use std::time::SystemTimeError;
+use std::time::{Duration, SystemTimeError};
 use thiserror::Error;
 use tokio::task::JoinError;
 
@@ -44,6 +46,22 @@ pub enum AMMError {
     CheckpointError(#[from] CheckpointError),
     #[error(transparent)]
     ProviderError(#[from] ProviderError),
+    #[error("Call to {method} on {address:#x} at {block_id:?} failed: {source}")]
+    CallError {
+        address: Felt,
+        method: String,
+        block_id: BlockId,
+        #[source]
+        source: Box<ProviderError>,
+    },
+    #[error("Unexpected result of {method} on {address:#x} at {block_id:?}")]
+    UnexpectedCallResult {
+        address: Felt,
+        method: String,
+        block_id: BlockId,
+    },
+    #[error("Invalid contract method name: {0}")]
+    InvalidMethodName(String),
 }
 
 #[derive(Error, Debug)]
@@ -70,6 +88,10 @@ pub enum EventLogError {
     InvalidEventSignature,
     #[error("Log Block number not found")]
     LogBlockNumberNotFound,
+    #[error("Invalid event data")]
+    InvalidEventData,
+    #[error("Event was not emitted for this AMM")]
+    UnrelatedEvent,
 }
 
 #[derive(Error, Debug)]
@@ -78,6 +100,8 @@ pub enum SwapSimulationError {
     InvalidTick,
     #[error("Liquidity underflow")]
     LiquidityUnderflow,
+    #[error("Pool fee {0} leaves no input to swap")]
+    InvalidFee(u32),
     #[error(transparent)]
     ArithmeticError(#[from] ArithmeticError),
 }
@@ -87,5 +111,79 @@ pub enum CheckpointError {
     #[error(transparent)]
     SystemTimeError(#[from] SystemTimeError),
     #[error(transparent)]
-    SerdeJsonError(#[from] serde_json::error::Error)
+    SerdeJsonError(#[from] serde_json::error::Error),
+    #[error(transparent)]
+    IOError(#[from] std::io::Error),
+}
+
+#[derive(Error, Debug)]
+pub enum StateSpaceError {
+    #[error(transparent)]
+    AMMError(#[from] AMMError),
+    #[error(transparent)]
+    ProviderError(#[from] ProviderError),
+    #[error("Block {0} not found")]
+    BlockNotFound(u64),
+    #[error("Block {0} is not after the latest synced block")]
+    BlockAlreadySynced(u64),
+    #[error("Reorg goes deeper than the state change cache")]
+    ReorgBeyondCache,
+}
+
+#[derive(Error, Debug)]
+pub enum ArbitrageError {
+    #[error("Pool of the cycle not found in the state space")]
+    PoolNotFound(Felt),
+    #[error("No pool path prices the token in the numeraire")]
+    NoPricePath(Felt),
+    #[error(transparent)]
+    AMMError(#[from] AMMError),
+    #[error(transparent)]
+    ProviderError(#[from] ProviderError),
+}
+
+#[derive(Error, Debug)]
+pub enum ExecutorError {
+    #[error("Pool of the opportunity not found in the state space")]
+    PoolNotFound(Felt),
+    #[error("No router configured for the pool")]
+    NoRouter(Felt),
+    #[error("Unexpected result when calling the contract")]
+    UnexpectedCallResult(Felt),
+    #[error("Transaction not accepted before the confirmation timeout")]
+    ConfirmationTimeout(Felt),
+    #[error("Estimated fee does not fit the resource bounds of an invoke")]
+    FeeOutOfRange,
+    #[error("Simulated transaction reverted: {0}")]
+    PreflightReverted(String),
+    #[error("Simulated output diverges from the predicted one")]
+    PreflightMismatch(Felt),
+    #[error("Simulation returned the trace of another transaction type")]
+    UnexpectedTrace,
+    #[error("No flash executor contract configured")]
+    NoFlashExecutor,
+    #[error("Pool does not support flash swaps through the executor contract")]
+    UnsupportedFlashPool(Felt),
+    #[error("Flash swaps need a cycle of at least two hops")]
+    CycleTooShort,
+    #[error(transparent)]
+    AMMError(#[from] AMMError),
+    #[error(transparent)]
+    ProviderError(#[from] ProviderError),
+    #[error(transparent)]
+    AccountError(#[from] AccountError<SignError<LocalWalletSignError>>),
+    #[error(transparent)]
+    SystemTimeError(#[from] SystemTimeError),
+}
+
+/// Errors raised by the provider wrappers of `crate::provider`, returned as the transport error
+/// of a `JsonRpcClientError` boxed in `ProviderError::Other`.
+#[derive(Error, Debug)]
+pub enum ProviderWrapperError {
+    #[error("Request timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("No RPC endpoint configured")]
+    NoEndpoints,
+    #[error("{agreeing} of the {quorum} endpoints needed agreed on the result")]
+    QuorumNotReached { quorum: usize, agreeing: usize },
 }
@@ -0,0 +1,69 @@
+use num_bigint::BigUint;
+use starknet::{
+    core::types::{Call, Felt},
+    macros::selector,
+};
+
+use crate::utils::encode_u256;
+
+/// A Uniswap V2 style router, and the entry point of its exact input swap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Router {
+    pub address: Felt,
+    pub swap_selector: Felt,
+}
+
+impl Router {
+    /// Jediswap's router, whose entry points are snake case.
+    pub fn jediswap(address: Felt) -> Router {
+        Router {
+            address,
+            swap_selector: selector!("swap_exact_tokens_for_tokens"),
+        }
+    }
+
+    /// 10kSwap's router, whose entry points are camel case.
+    pub fn tenkswap(address: Felt) -> Router {
+        Router {
+            address,
+            swap_selector: selector!("swapExactTokensForTokens"),
+        }
+    }
+}
+
+/// Builds the ERC20 `approve` of `amount` of `token` to `spender`.
+pub fn approve_call(token: Felt, spender: Felt, amount: &BigUint) -> Call {
+    let mut calldata = vec![spender];
+    calldata.extend(encode_u256(amount));
+
+    Call {
+        to: token,
+        selector: selector!("approve"),
+        calldata,
+    }
+}
+
+/// Builds the router call that swaps exactly `amount_in` along `path`, reverting when less
+/// than `amount_out_min` would be received by `to` or once `deadline` has passed.
+pub fn swap_exact_tokens_for_tokens_call(
+    router: &Router,
+    amount_in: &BigUint,
+    amount_out_min: &BigUint,
+    path: &[Felt],
+    to: Felt,
+    deadline: u64,
+) -> Call {
+    let mut calldata = vec![];
+    calldata.extend(encode_u256(amount_in));
+    calldata.extend(encode_u256(amount_out_min));
+    calldata.push(Felt::from(path.len()));
+    calldata.extend_from_slice(path);
+    calldata.push(to);
+    calldata.push(Felt::from(deadline));
+
+    Call {
+        to: router.address,
+        selector: router.swap_selector,
+        calldata,
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+use starknet::{
+    core::types::{Call, Felt},
+    macros::selector,
+};
+
+use crate::{
+    amm::pool::{AutomatedMarketMaker, AMM},
+    arbitrage::{graph::Cycle, Opportunity},
+    errors::ExecutorError,
+    utils::encode_u256,
+};
+
+/// A hop the executor contract trades the borrowed tokens through, directly against a pair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashHop {
+    pub pool: Felt,
+    pub token_in: Felt,
+    pub token_out: Felt,
+    /// Output predicted by the pair's swap math.
+    pub amount_out: BigUint,
+    /// Least output the hop accepts, below which the whole transaction reverts.
+    pub min_amount_out: BigUint,
+}
+
+/// An arbitrage cycle executed without holding any of its tokens.
+///
+/// The output of the first hop is flash-borrowed from its pair and traded through the other
+/// hops, and the pair is repaid in the start token from the proceeds, in the same transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashSwapOpportunity {
+    pub cycle: Cycle,
+    /// Pair of the first hop, which the borrowed tokens come from.
+    pub pair: Felt,
+    /// Amounts of the pair's token0 and token1 borrowed. One of them is zero.
+    pub amount0_out: BigUint,
+    pub amount1_out: BigUint,
+    /// Amount of the start token the pair is owed for the borrowed tokens.
+    pub repay_amount: BigUint,
+    pub hops: Vec<FlashHop>,
+}
+
+impl FlashSwapOpportunity {
+    /// Turns `opportunity` into a flash swap borrowing the output of its first hop, with a
+    /// minimum output `slippage_bps` below the simulated one on every other hop.
+    ///
+    /// Every pool of the cycle must be a Jediswap or 10kSwap pair, the pools the executor
+    /// contract can flash-borrow from and swap against.
+    pub fn new(
+        opportunity: &Opportunity,
+        amms: &HashMap<Felt, AMM>,
+        slippage_bps: u32,
+    ) -> Result<FlashSwapOpportunity, ExecutorError> {
+        if opportunity.cycle.hops.len() < 2 {
+            return Err(ExecutorError::CycleTooShort);
+        }
+
+        let mut pools = vec![];
+        for hop in &opportunity.cycle.hops {
+            let amm = amms
+                .get(&hop.pool)
+                .ok_or(ExecutorError::PoolNotFound(hop.pool))?;
+            if !matches!(amm, AMM::JediswapPool(_) | AMM::TenkSwapPool(_)) {
+                return Err(ExecutorError::UnsupportedFlashPool(hop.pool));
+            }
+            pools.push(amm);
+        }
+
+        let (first, rest) = (&opportunity.cycle.hops[0], &opportunity.cycle.hops[1..]);
+        let pair = pools[0];
+
+        let borrowed = pair.clone().simulate_swap_mut(
+            first.token_in,
+            first.token_out,
+            opportunity.amount_in,
+        )?;
+        // The pair only asks for what its invariant needs, which can be below `amount_in`.
+        let repay_amount = pair
+            .simulate_swap_exact_out(first.token_in, first.token_out, borrowed)?
+            .to_biguint();
+
+        let (amount0_out, amount1_out) = if pair.tokens()[0] == first.token_out {
+            (borrowed.to_biguint(), BigUint::default())
+        } else {
+            (BigUint::default(), borrowed.to_biguint())
+        };
+
+        let mut hops = vec![];
+        let mut amount_in = borrowed;
+        for (hop, &amm) in rest.iter().zip(&pools[1..]) {
+            let amount_out =
+                amm.clone()
+                    .simulate_swap_mut(hop.token_in, hop.token_out, amount_in)?;
+            let min_amount_out =
+                amount_out.to_biguint() * (10_000 - slippage_bps.min(10_000)) / 10_000u32;
+
+            hops.push(FlashHop {
+                pool: hop.pool,
+                token_in: hop.token_in,
+                token_out: hop.token_out,
+                amount_out: amount_out.to_biguint(),
+                min_amount_out,
+            });
+            amount_in = amount_out;
+        }
+
+        Ok(FlashSwapOpportunity {
+            cycle: opportunity.cycle.clone(),
+            pair: first.pool,
+            amount0_out,
+            amount1_out,
+            repay_amount,
+            hops,
+        })
+    }
+
+    /// Returns the amount of the start token the last hop is predicted to return.
+    pub fn amount_out(&self) -> BigUint {
+        self.hops
+            .last()
+            .map(|hop| hop.amount_out.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns the amount of the start token left once the pair is repaid.
+    pub fn profit(&self) -> BigUint {
+        let amount_out = self.amount_out();
+        if amount_out > self.repay_amount {
+            amount_out - &self.repay_amount
+        } else {
+            BigUint::default()
+        }
+    }
+}
+
+/// Address of a flash arbitrage executor contract, and the encoding of calls to it.
+///
+/// The contract is not part of this crate and has to be deployed separately. The calldata is
+/// encoded by hand for the following interface, which the contract must implement:
+///
+/// ```cairo
+/// #[derive(Drop, Serde)]
+/// struct Hop {
+///     pool: ContractAddress,
+///     token_in: ContractAddress,
+///     token_out: ContractAddress,
+///     amount_out_min: u256,
+/// }
+///
+/// #[starknet::interface]
+/// trait IFlashExecutor<TContractState> {
+///     fn flash_arbitrage(
+///         ref self: TContractState,
+///         pair: ContractAddress,
+///         amount0_out: u256,
+///         amount1_out: u256,
+///         repay_token: ContractAddress,
+///         repay_amount: u256,
+///         hops: Array<Hop>,
+///         min_profit: u256,
+///     );
+/// }
+/// ```
+///
+/// `flash_arbitrage` is expected to call the pair's `swap` with the contract as recipient and
+/// the rest of its arguments as callback data. In the pair's callback, `jediswap_call` or
+/// `swapCall`, the contract swaps the borrowed tokens through `hops`, repays the pair, and sends
+/// the profit to the caller, reverting when it is below `min_profit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlashExecutor {
+    pub address: Felt,
+}
+
+impl FlashExecutor {
+    pub fn new(address: Felt) -> FlashExecutor {
+        FlashExecutor { address }
+    }
+
+    /// Builds the call executing `opportunity`, which reverts unless at least `min_profit` of
+    /// the start token is made.
+    pub fn flash_arbitrage_call(
+        &self,
+        opportunity: &FlashSwapOpportunity,
+        min_profit: &BigUint,
+    ) -> Call {
+        let mut calldata = vec![opportunity.pair];
+        calldata.extend(encode_u256(&opportunity.amount0_out));
+        calldata.extend(encode_u256(&opportunity.amount1_out));
+        calldata.push(opportunity.cycle.start_token());
+        calldata.extend(encode_u256(&opportunity.repay_amount));
+        calldata.push(Felt::from(opportunity.hops.len()));
+        for hop in &opportunity.hops {
+            calldata.extend([hop.pool, hop.token_in, hop.token_out]);
+            calldata.extend(encode_u256(&hop.min_amount_out));
+        }
+        calldata.extend(encode_u256(min_profit));
+
+        Call {
+            to: self.address,
+            selector: selector!("flash_arbitrage"),
+            calldata,
+        }
+    }
+}
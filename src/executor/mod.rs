@@ -0,0 +1,598 @@
+pub mod calls;
+pub mod flash;
+pub mod nonce;
+pub mod preflight;
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use num_bigint::{BigInt, BigUint};
+use num_traits::{One, ToPrimitive};
+use starknet::{
+    accounts::{Account, ConnectedAccount},
+    core::types::{
+        BlockId, BlockTag, Call, ExecuteInvocation, ExecutionResult, Felt, FunctionCall,
+        StarknetError, TransactionExecutionStatus, TransactionStatus, TransactionTrace,
+    },
+    macros::selector,
+    providers::{Provider, ProviderError},
+};
+
+use crate::{
+    amm::pool::{AutomatedMarketMaker, AMM},
+    arbitrage::Opportunity,
+    errors::ExecutorError,
+    utils::{parse_u256, LocalWalletSignerMiddleware},
+};
+
+use self::{
+    calls::{approve_call, swap_exact_tokens_for_tokens_call, Router},
+    flash::{FlashExecutor, FlashSwapOpportunity},
+    nonce::{is_nonce_error, NonceManager},
+    preflight::{
+        check_received, predicted_amounts, received_amounts, PreflightReport, DEFAULT_TOLERANCE_BPS,
+    },
+};
+
+/// Slippage tolerated on the output of every hop, in basis points.
+pub const DEFAULT_SLIPPAGE_BPS: u32 = 50;
+/// Time after which the swaps of a submitted transaction revert.
+pub const DEFAULT_DEADLINE: Duration = Duration::from_secs(120);
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Time a submitted transaction is tracked before giving up on it.
+pub const DEFAULT_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(180);
+/// Margin over the estimated gas and gas price given to the resource bounds of an invoke, as
+/// a numerator and a denominator.
+pub const RESOURCE_BOUNDS_MULTIPLIER: (u32, u32) = (3, 2);
+/// Increase of the gas price bound of a transaction replacing a stuck one, in percent.
+pub const DEFAULT_FEE_BUMP_PERCENT: u32 = 20;
+/// Times a stuck transaction is replaced before giving up on it.
+pub const DEFAULT_MAX_REPLACEMENTS: usize = 2;
+
+/// A hop of an opportunity, ready to be traded through its router.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapLeg {
+    pub router: Router,
+    pub token_in: Felt,
+    pub token_out: Felt,
+    pub amount_in: BigUint,
+    /// Output predicted by the pool's swap math.
+    pub amount_out: BigUint,
+    /// Least output the swap accepts, below which the whole transaction reverts.
+    pub min_amount_out: BigUint,
+}
+
+/// An invoke whose nonce and resource bounds are resolved, so that the transaction simulated
+/// before submission is the one submitted.
+#[derive(Debug, Clone)]
+pub struct PreparedInvoke {
+    pub calls: Vec<Call>,
+    pub nonce: Felt,
+    pub gas: u64,
+    pub gas_price: u128,
+}
+
+impl PreparedInvoke {
+    /// Returns the same invoke with a gas price bound `percent` higher, to replace it while it
+    /// is stuck in the mempool.
+    pub fn with_bumped_fee(&self, percent: u32) -> PreparedInvoke {
+        let bump = (self.gas_price.saturating_mul(u128::from(percent)) / 100).max(1);
+        PreparedInvoke {
+            gas_price: self.gas_price.saturating_add(bump),
+            ..self.clone()
+        }
+    }
+}
+
+/// How a tracked transaction ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionStatus {
+    Succeeded,
+    Reverted(String),
+    /// The sequencer refused the transaction, which was not included in a block.
+    Rejected,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionOutcome {
+    pub transaction_hash: Felt,
+    pub status: ExecutionStatus,
+}
+
+/// Executes opportunities as a single multicall of router swaps, signed with a local wallet.
+///
+/// Every hop is swapped through the router of its pool, with a minimum output of the
+/// simulated one less the slippage tolerance. Each hop trades the minimum output of the
+/// previous one, so the multicall can not run short of tokens midway.
+pub struct Executor {
+    account: LocalWalletSignerMiddleware,
+    jediswap_router: Option<Router>,
+    tenkswap_router: Option<Router>,
+    flash_executor: Option<FlashExecutor>,
+    slippage_bps: u32,
+    deadline: Duration,
+    poll_interval: Duration,
+    confirmation_timeout: Duration,
+    preflight_tolerance_bps: u32,
+    fee_bump_percent: u32,
+    max_replacements: usize,
+    nonces: NonceManager,
+}
+
+impl Executor {
+    pub fn new(account: LocalWalletSignerMiddleware) -> Executor {
+        Executor {
+            nonces: NonceManager::new(account.clone()),
+            account,
+            jediswap_router: None,
+            tenkswap_router: None,
+            flash_executor: None,
+            slippage_bps: DEFAULT_SLIPPAGE_BPS,
+            deadline: DEFAULT_DEADLINE,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            confirmation_timeout: DEFAULT_CONFIRMATION_TIMEOUT,
+            preflight_tolerance_bps: DEFAULT_TOLERANCE_BPS,
+            fee_bump_percent: DEFAULT_FEE_BUMP_PERCENT,
+            max_replacements: DEFAULT_MAX_REPLACEMENTS,
+        }
+    }
+
+    /// Sets the router Jediswap pools are traded through.
+    pub fn with_jediswap_router(mut self, address: Felt) -> Executor {
+        self.jediswap_router = Some(Router::jediswap(address));
+        self
+    }
+
+    /// Sets the router 10kSwap pools are traded through.
+    pub fn with_tenkswap_router(mut self, address: Felt) -> Executor {
+        self.tenkswap_router = Some(Router::tenkswap(address));
+        self
+    }
+
+    /// Sets the flash arbitrage executor contract flash swaps are executed through. The contract
+    /// is deployed separately, see `FlashExecutor` for the interface it must implement.
+    pub fn with_flash_executor(mut self, address: Felt) -> Executor {
+        self.flash_executor = Some(FlashExecutor::new(address));
+        self
+    }
+
+    pub fn with_slippage_bps(mut self, slippage_bps: u32) -> Executor {
+        self.slippage_bps = slippage_bps.min(10_000);
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Duration) -> Executor {
+        self.deadline = deadline;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Executor {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn with_confirmation_timeout(mut self, confirmation_timeout: Duration) -> Executor {
+        self.confirmation_timeout = confirmation_timeout;
+        self
+    }
+
+    /// Sets how far, in basis points, the simulated net amounts may deviate from the predicted
+    /// ones before a transaction is aborted.
+    pub fn with_preflight_tolerance_bps(mut self, tolerance_bps: u32) -> Executor {
+        self.preflight_tolerance_bps = tolerance_bps;
+        self
+    }
+
+    /// Sets by how much, in percent, the gas price bound of a stuck transaction is raised to
+    /// replace it, and how many times it is replaced before giving up.
+    pub fn with_replacement(mut self, fee_bump_percent: u32, max_replacements: usize) -> Executor {
+        self.fee_bump_percent = fee_bump_percent;
+        self.max_replacements = max_replacements;
+        self
+    }
+
+    pub fn account(&self) -> &LocalWalletSignerMiddleware {
+        &self.account
+    }
+
+    /// Returns the nonce manager shared by the submissions of the executor.
+    pub fn nonces(&self) -> &NonceManager {
+        &self.nonces
+    }
+
+    /// Builds the transaction executing `opportunity`, simulates it, and submits and tracks it
+    /// when the simulation matches the prediction.
+    ///
+    /// Opportunities can be executed concurrently: each transaction gets the next nonce of the
+    /// account.
+    pub async fn execute(
+        &self,
+        opportunity: &Opportunity,
+        amms: &HashMap<Felt, AMM>,
+    ) -> Result<ExecutionOutcome, ExecutorError> {
+        let legs = self.plan(opportunity, amms)?;
+        let calls = self.build_calls(&legs).await?;
+        let invoke = self.prepare(calls).await?;
+        if let Err(err) = self.preflight(&invoke, &predicted_amounts(&legs)).await {
+            self.nonces.release(invoke.nonce).await;
+            return Err(err);
+        }
+
+        self.submit_and_track(invoke).await
+    }
+
+    /// Turns `opportunity` into a flash swap, with the slippage tolerance of the executor.
+    pub fn plan_flash(
+        &self,
+        opportunity: &Opportunity,
+        amms: &HashMap<Felt, AMM>,
+    ) -> Result<FlashSwapOpportunity, ExecutorError> {
+        FlashSwapOpportunity::new(opportunity, amms, self.slippage_bps)
+    }
+
+    /// Executes `opportunity` through the flash executor contract, so that none of its tokens
+    /// has to be held. The transaction reverts unless `min_profit` of the start token is made.
+    pub async fn execute_flash(
+        &self,
+        opportunity: &FlashSwapOpportunity,
+        min_profit: Felt,
+    ) -> Result<ExecutionOutcome, ExecutorError> {
+        let flash_executor = self.flash_executor.ok_or(ExecutorError::NoFlashExecutor)?;
+        let call = flash_executor.flash_arbitrage_call(opportunity, &min_profit.to_biguint());
+
+        let invoke = self.prepare(vec![call]).await?;
+        // The only transfer to the account is the profit.
+        let predicted = HashMap::from([(
+            opportunity.cycle.start_token(),
+            BigInt::from(opportunity.profit()),
+        )]);
+        if let Err(err) = self.preflight(&invoke, &predicted).await {
+            self.nonces.release(invoke.nonce).await;
+            return Err(err);
+        }
+
+        self.submit_and_track(invoke).await
+    }
+
+    /// Submits `invoke` and tracks it until it settles, replacing it with a higher fee bound
+    /// whenever it is not accepted within the confirmation timeout.
+    pub async fn submit_and_track(
+        &self,
+        invoke: PreparedInvoke,
+    ) -> Result<ExecutionOutcome, ExecutorError> {
+        let transaction_hash = match self.submit(&invoke).await {
+            Ok(transaction_hash) => transaction_hash,
+            Err(err) => {
+                if is_nonce_error(&err) {
+                    self.nonces.resync().await;
+                } else {
+                    self.nonces.release(invoke.nonce).await;
+                }
+                return Err(err);
+            }
+        };
+        tracing::info!(?transaction_hash, nonce = ?invoke.nonce, "Submitted transaction");
+
+        // Any of the submitted versions may be the one included, so all of them are tracked.
+        let mut invoke = invoke;
+        let mut transaction_hashes = vec![transaction_hash];
+        loop {
+            let replaced = transaction_hashes.len() - 1;
+            match self.wait_for_any(&transaction_hashes).await {
+                Err(ExecutorError::ConfirmationTimeout(stuck))
+                    if replaced < self.max_replacements =>
+                {
+                    invoke = invoke.with_bumped_fee(self.fee_bump_percent);
+                    tracing::warn!(?stuck, nonce = ?invoke.nonce, gas_price = invoke.gas_price, "Replacing stuck transaction");
+
+                    match self.submit(&invoke).await {
+                        Ok(transaction_hash) => transaction_hashes.push(transaction_hash),
+                        // The stuck transaction was included meanwhile.
+                        Err(err) if is_nonce_error(&err) => {}
+                        Err(err) => return Err(err),
+                    }
+                }
+                Ok(outcome) => {
+                    if outcome.status == ExecutionStatus::Rejected {
+                        self.nonces.resync().await;
+                    }
+                    return Ok(outcome);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Simulates the hops of `opportunity` and returns the swap of each, with its minimum
+    /// output.
+    ///
+    /// Every hop trades the simulated output of the previous one, which it requires in full as
+    /// the next hop spends all of it. The slippage tolerance is only applied once, to the
+    /// output of the last hop against the amount the opportunity returns.
+    pub fn plan(
+        &self,
+        opportunity: &Opportunity,
+        amms: &HashMap<Felt, AMM>,
+    ) -> Result<Vec<SwapLeg>, ExecutorError> {
+        // Pools traded twice by the cycle are simulated from the state left by the first hop.
+        let mut simulated: HashMap<Felt, AMM> = HashMap::new();
+        let mut legs: Vec<SwapLeg> = vec![];
+        let mut amount_in = opportunity.amount_in;
+        for hop in &opportunity.cycle.hops {
+            let amm = amms
+                .get(&hop.pool)
+                .ok_or(ExecutorError::PoolNotFound(hop.pool))?;
+            let router = self.router_for(amm)?;
+
+            let amount_out = simulated
+                .entry(hop.pool)
+                .or_insert_with(|| amm.clone())
+                .simulate_swap_mut(hop.token_in, hop.token_out, amount_in)?;
+
+            legs.push(SwapLeg {
+                router,
+                token_in: hop.token_in,
+                token_out: hop.token_out,
+                amount_in: amount_in.to_biguint(),
+                amount_out: amount_out.to_biguint(),
+                min_amount_out: amount_out.to_biguint(),
+            });
+            amount_in = amount_out;
+        }
+
+        if let Some(last) = legs.last_mut() {
+            last.min_amount_out =
+                opportunity.amount_out.to_biguint() * (10_000 - self.slippage_bps) / 10_000u32;
+        }
+
+        Ok(legs)
+    }
+
+    /// Returns the calls of the multicall executing `legs`: the approvals the account is
+    /// missing, followed by the swaps.
+    pub async fn build_calls(&self, legs: &[SwapLeg]) -> Result<Vec<Call>, ExecutorError> {
+        // Hops selling the same token through the same router share one approval.
+        let mut required: Vec<(Felt, Felt, BigUint)> = vec![];
+        for leg in legs {
+            match required.iter_mut().find(|(token, spender, _)| {
+                *token == leg.token_in && *spender == leg.router.address
+            }) {
+                Some((_, _, amount)) => *amount += &leg.amount_in,
+                None => required.push((leg.token_in, leg.router.address, leg.amount_in.clone())),
+            }
+        }
+
+        let mut calls = vec![];
+        for (token, spender, amount) in required {
+            if self.allowance(token, spender).await? < amount {
+                calls.push(approve_call(token, spender, &amount));
+            }
+        }
+
+        let deadline = SystemTime::now().duration_since(UNIX_EPOCH)? + self.deadline;
+        calls.extend(self.swap_calls(legs, deadline.as_secs()));
+
+        Ok(calls)
+    }
+
+    /// Returns the router swaps of `legs`, paying every output to the account.
+    pub fn swap_calls(&self, legs: &[SwapLeg], deadline: u64) -> Vec<Call> {
+        legs.iter()
+            .map(|leg| {
+                swap_exact_tokens_for_tokens_call(
+                    &leg.router,
+                    &leg.amount_in,
+                    &leg.min_amount_out,
+                    &[leg.token_in, leg.token_out],
+                    self.account.address(),
+                    deadline,
+                )
+            })
+            .collect()
+    }
+
+    /// Reserves the next nonce of the account and resolves the resource bounds of an invoke of
+    /// `calls`. The nonce is given back when the estimation fails.
+    pub async fn prepare(&self, calls: Vec<Call>) -> Result<PreparedInvoke, ExecutorError> {
+        let nonce = self.nonces.next().await?;
+        let estimate = match self
+            .account
+            .execute_v3(calls.clone())
+            .nonce(nonce)
+            .estimate_fee()
+            .await
+        {
+            Ok(estimate) => estimate,
+            Err(err) => {
+                let err = ExecutorError::from(err);
+                if is_nonce_error(&err) {
+                    self.nonces.resync().await;
+                } else {
+                    self.nonces.release(nonce).await;
+                }
+                return Err(err);
+            }
+        };
+
+        let overall_fee = estimate.overall_fee.to_biguint();
+        let gas_price = estimate.gas_price.to_biguint().max(BigUint::one());
+        let gas = (overall_fee + &gas_price - 1u32) / &gas_price;
+        let (Some(gas), Some(gas_price)) = (gas.to_u64(), gas_price.to_u128()) else {
+            self.nonces.release(nonce).await;
+            return Err(ExecutorError::FeeOutOfRange);
+        };
+
+        let (numerator, denominator) = RESOURCE_BOUNDS_MULTIPLIER;
+        Ok(PreparedInvoke {
+            calls,
+            nonce,
+            gas: gas.saturating_mul(numerator.into()) / u64::from(denominator),
+            gas_price: gas_price.saturating_mul(numerator.into()) / u128::from(denominator),
+        })
+    }
+
+    /// Simulates `invoke`, signed and charged as it would be submitted, and checks that the
+    /// account receives the `predicted` net amount of every token, such as the
+    /// `predicted_amounts` of the legs of an opportunity.
+    ///
+    /// Fails when the simulation reverts, or when an output deviates from the prediction by
+    /// more than the preflight tolerance, as happens when the local pool states are stale.
+    pub async fn preflight(
+        &self,
+        invoke: &PreparedInvoke,
+        predicted: &HashMap<Felt, BigInt>,
+    ) -> Result<PreflightReport, ExecutorError> {
+        let simulated = self
+            .account
+            .execute_v3(invoke.calls.clone())
+            .nonce(invoke.nonce)
+            .gas(invoke.gas)
+            .gas_price(invoke.gas_price)
+            .simulate(false, false)
+            .await?;
+
+        let invocation = match simulated.transaction_trace {
+            TransactionTrace::Invoke(trace) => match trace.execute_invocation {
+                ExecuteInvocation::Success(invocation) => invocation,
+                ExecuteInvocation::Reverted(reverted) => {
+                    return Err(ExecutorError::PreflightReverted(reverted.revert_reason))
+                }
+            },
+            _ => return Err(ExecutorError::UnexpectedTrace),
+        };
+
+        let received = received_amounts(&invocation, self.account.address());
+        check_received(predicted, &received, self.preflight_tolerance_bps)?;
+
+        Ok(PreflightReport {
+            fee_estimate: simulated.fee_estimation,
+            received,
+        })
+    }
+
+    /// Signs `invoke`, submits it and returns its hash.
+    pub async fn submit(&self, invoke: &PreparedInvoke) -> Result<Felt, ExecutorError> {
+        let result = self
+            .account
+            .execute_v3(invoke.calls.clone())
+            .nonce(invoke.nonce)
+            .gas(invoke.gas)
+            .gas_price(invoke.gas_price)
+            .send()
+            .await?;
+
+        Ok(result.transaction_hash)
+    }
+
+    /// Polls the status of `transaction_hash` until it is accepted or rejected.
+    pub async fn wait_for_transaction(
+        &self,
+        transaction_hash: Felt,
+    ) -> Result<ExecutionOutcome, ExecutorError> {
+        self.wait_for_any(&[transaction_hash]).await
+    }
+
+    /// Polls the statuses of `transaction_hashes`, versions of a transaction with the same
+    /// nonce, until one of them is accepted or all of them are rejected.
+    pub async fn wait_for_any(
+        &self,
+        transaction_hashes: &[Felt],
+    ) -> Result<ExecutionOutcome, ExecutorError> {
+        let started = Instant::now();
+        loop {
+            let mut rejected = 0;
+            for &transaction_hash in transaction_hashes {
+                let status = self.transaction_status(transaction_hash).await?;
+                match status {
+                    Some(ExecutionStatus::Rejected) => rejected += 1,
+                    Some(status) => {
+                        tracing::info!(?transaction_hash, ?status, "Transaction settled");
+                        return Ok(ExecutionOutcome {
+                            transaction_hash,
+                            status,
+                        });
+                    }
+                    None => {}
+                }
+            }
+
+            let last = transaction_hashes.last().copied().unwrap_or_default();
+            if rejected == transaction_hashes.len() {
+                tracing::warn!(transaction_hash = ?last, "Transaction rejected");
+                return Ok(ExecutionOutcome {
+                    transaction_hash: last,
+                    status: ExecutionStatus::Rejected,
+                });
+            }
+
+            if started.elapsed() >= self.confirmation_timeout {
+                return Err(ExecutorError::ConfirmationTimeout(last));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Returns how `transaction_hash` ended, or `None` while it is pending.
+    async fn transaction_status(
+        &self,
+        transaction_hash: Felt,
+    ) -> Result<Option<ExecutionStatus>, ExecutorError> {
+        let provider = self.account.provider();
+        let status = match provider.get_transaction_status(transaction_hash).await {
+            Ok(TransactionStatus::Received) => None,
+            Ok(TransactionStatus::Rejected) => Some(ExecutionStatus::Rejected),
+            Ok(
+                TransactionStatus::AcceptedOnL2(execution)
+                | TransactionStatus::AcceptedOnL1(execution),
+            ) => match execution {
+                TransactionExecutionStatus::Succeeded => Some(ExecutionStatus::Succeeded),
+                TransactionExecutionStatus::Reverted => {
+                    let receipt = provider.get_transaction_receipt(transaction_hash).await?;
+                    let reason = match receipt.receipt.execution_result() {
+                        ExecutionResult::Reverted { reason } => reason.clone(),
+                        ExecutionResult::Succeeded => String::new(),
+                    };
+                    Some(ExecutionStatus::Reverted(reason))
+                }
+            },
+            // The node may not know about the transaction right after it was submitted.
+            Err(ProviderError::StarknetError(StarknetError::TransactionHashNotFound)) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(status)
+    }
+
+    /// Returns how much of `token` `spender` may currently spend on behalf of the account.
+    async fn allowance(&self, token: Felt, spender: Felt) -> Result<BigUint, ExecutorError> {
+        let result = self
+            .account
+            .provider()
+            .call(
+                FunctionCall {
+                    contract_address: token,
+                    entry_point_selector: selector!("allowance"),
+                    calldata: vec![self.account.address(), spender],
+                },
+                BlockId::Tag(BlockTag::Pending),
+            )
+            .await?;
+
+        match result.as_slice() {
+            [low, high] => Ok(parse_u256(*low, *high)),
+            _ => Err(ExecutorError::UnexpectedCallResult(token)),
+        }
+    }
+
+    fn router_for(&self, amm: &AMM) -> Result<Router, ExecutorError> {
+        let router = match amm {
+            AMM::JediswapPool(_) => self.jediswap_router,
+            AMM::TenkSwapPool(_) => self.tenkswap_router,
+            _ => None,
+        };
+
+        router.ok_or(ExecutorError::NoRouter(amm.address()))
+    }
+}
@@ -0,0 +1,88 @@
+use starknet::{
+    accounts::{Account, AccountError, ConnectedAccount},
+    core::types::{BlockId, BlockTag, Felt, StarknetError},
+    providers::{Provider, ProviderError},
+};
+use tokio::sync::Mutex;
+
+use crate::{errors::ExecutorError, utils::LocalWalletSignerMiddleware};
+
+/// Hands out the nonces of an account to concurrent submissions.
+///
+/// The nonce is read from the pending block once, and then incremented locally for every
+/// submission. After a rejection, or when the node reports an invalid nonce, call `resync` so
+/// that the next nonce is read from the chain again.
+pub struct NonceManager {
+    account: LocalWalletSignerMiddleware,
+    next_nonce: Mutex<Option<Felt>>,
+}
+
+impl NonceManager {
+    pub fn new(account: LocalWalletSignerMiddleware) -> NonceManager {
+        NonceManager {
+            account,
+            next_nonce: Mutex::new(None),
+        }
+    }
+
+    /// Returns the nonce of the next submission, and reserves it.
+    pub async fn next(&self) -> Result<Felt, ExecutorError> {
+        // The lock is held while fetching, so concurrent callers wait for the same read.
+        let mut next_nonce = self.next_nonce.lock().await;
+        let nonce = match *next_nonce {
+            Some(nonce) => nonce,
+            None => self.fetch().await?,
+        };
+        *next_nonce = Some(nonce + Felt::ONE);
+
+        Ok(nonce)
+    }
+
+    /// Sets the nonce handed out next.
+    pub async fn set(&self, nonce: Felt) {
+        *self.next_nonce.lock().await = Some(nonce);
+    }
+
+    /// Gives back a reserved nonce that was never submitted.
+    ///
+    /// When later nonces were handed out meanwhile, the cache is dropped instead, as they can
+    /// only be included once `nonce` is used.
+    pub async fn release(&self, nonce: Felt) {
+        let mut next_nonce = self.next_nonce.lock().await;
+        *next_nonce = (*next_nonce == Some(nonce + Felt::ONE)).then_some(nonce);
+    }
+
+    /// Drops the cached nonce, so that the next one is read from the chain.
+    pub async fn resync(&self) {
+        *self.next_nonce.lock().await = None;
+    }
+
+    async fn fetch(&self) -> Result<Felt, ExecutorError> {
+        let nonce = self
+            .account
+            .provider()
+            .get_nonce(BlockId::Tag(BlockTag::Pending), self.account.address())
+            .await?;
+        tracing::debug!(?nonce, "Fetched account nonce");
+
+        Ok(nonce)
+    }
+}
+
+/// Returns whether `err` means the nonce of a transaction was already used or skipped ahead.
+pub fn is_nonce_error(err: &ExecutorError) -> bool {
+    let starknet_error = match err {
+        ExecutorError::ProviderError(ProviderError::StarknetError(err)) => err,
+        ExecutorError::AccountError(AccountError::Provider(ProviderError::StarknetError(err))) => {
+            err
+        }
+        _ => return false,
+    };
+
+    match starknet_error {
+        StarknetError::InvalidTransactionNonce => true,
+        // Some nodes report the nonce check as a failed validation.
+        StarknetError::ValidationFailure(reason) => reason.to_lowercase().contains("nonce"),
+        _ => false,
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::{HashMap, HashSet};
+
+use num_bigint::{BigInt, BigUint};
+use starknet::{
+    core::types::{FeeEstimate, Felt, FunctionInvocation, OrderedEvent},
+    macros::selector,
+};
+
+use crate::{errors::ExecutorError, utils::parse_u256};
+
+use super::SwapLeg;
+
+/// Basis points the simulated net amounts may deviate from the predicted ones by.
+pub const DEFAULT_TOLERANCE_BPS: u32 = 10;
+
+/// Outcome of simulating an invoke before submitting it.
+#[derive(Debug, Clone)]
+pub struct PreflightReport {
+    pub fee_estimate: FeeEstimate,
+    /// Net amount of every token transferred to the account by the simulated swaps.
+    pub received: HashMap<Felt, BigInt>,
+}
+
+/// Returns the net amount of every token `legs` are predicted to pay out to the account, that
+/// is the outputs of the legs less their inputs.
+pub fn predicted_amounts(legs: &[SwapLeg]) -> HashMap<Felt, BigInt> {
+    let mut predicted: HashMap<Felt, BigInt> = HashMap::new();
+    for leg in legs {
+        *predicted.entry(leg.token_out).or_default() += BigInt::from(leg.amount_out.clone());
+        *predicted.entry(leg.token_in).or_default() -= BigInt::from(leg.amount_in.clone());
+    }
+
+    predicted
+}
+
+/// Returns the net amount of every token transferred to `account` by `invocation` and the
+/// calls it made, read from their ERC20 `Transfer` events: what it received less what it sent.
+pub fn received_amounts(invocation: &FunctionInvocation, account: Felt) -> HashMap<Felt, BigInt> {
+    let mut received: HashMap<Felt, BigInt> = HashMap::new();
+    let mut pending = vec![invocation];
+    while let Some(invocation) = pending.pop() {
+        for event in &invocation.events {
+            if let Some((from, to, amount)) = parse_transfer(event) {
+                let net = received.entry(invocation.contract_address).or_default();
+                if to == account {
+                    *net += BigInt::from(amount.clone());
+                }
+                if from == account {
+                    *net -= BigInt::from(amount);
+                }
+            }
+        }
+        pending.extend(&invocation.calls);
+    }
+
+    received
+}
+
+/// Checks that the net amount of every token received matches the prediction, give or take
+/// `tolerance_bps` of it. Tokens missing from either side count as zero, so an unpredicted
+/// outflow fails the check.
+pub fn check_received(
+    predicted: &HashMap<Felt, BigInt>,
+    received: &HashMap<Felt, BigInt>,
+    tolerance_bps: u32,
+) -> Result<(), ExecutorError> {
+    let tokens: HashSet<&Felt> = predicted.keys().chain(received.keys()).collect();
+    for token in tokens {
+        let predicted = predicted.get(token).cloned().unwrap_or_default();
+        let simulated = received.get(token).cloned().unwrap_or_default();
+        let deviation = (&simulated - &predicted).magnitude().clone();
+
+        if deviation * 10_000u32 > predicted.magnitude() * tolerance_bps {
+            tracing::warn!(?token, %predicted, %simulated, "Simulated output diverges from prediction");
+            return Err(ExecutorError::PreflightMismatch(*token));
+        }
+    }
+
+    Ok(())
+}
+
+/// Decodes the sender, recipient and amount of a `Transfer` event, whether the addresses are
+/// keys, as in Cairo 1 tokens, or data, as in Cairo 0 ones.
+fn parse_transfer(event: &OrderedEvent) -> Option<(Felt, Felt, BigUint)> {
+    if event.keys.first() != Some(&selector!("Transfer")) {
+        return None;
+    }
+
+    match (event.keys.as_slice(), event.data.as_slice()) {
+        ([_, from, to], [low, high]) | ([_], [from, to, low, high]) => {
+            Some((*from, *to, parse_u256(*low, *high)))
+        }
+        _ => None,
+    }
+}
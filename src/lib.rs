@@ -1,4 +1,9 @@
 pub mod amm;
+pub mod arbitrage;
 pub mod cache;
 pub mod errors;
+pub mod executor;
+pub mod provider;
+pub mod state_space;
+pub mod token_registry;
 pub mod utils;
@@ -0,0 +1,740 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::{future::join_all, stream::FuturesUnordered, StreamExt};
+use starknet::{
+    core::types::{
+        BlockHashAndNumber, BlockId, BroadcastedDeclareTransaction,
+        BroadcastedDeployAccountTransaction, BroadcastedInvokeTransaction, BroadcastedTransaction,
+        ContractClass, DeclareTransactionResult, DeployAccountTransactionResult, EventFilter,
+        EventsPage, FeeEstimate, Felt, FunctionCall, InvokeTransactionResult,
+        MaybePendingBlockWithReceipts, MaybePendingBlockWithTxHashes, MaybePendingBlockWithTxs,
+        MaybePendingStateUpdate, MsgFromL1, SimulatedTransaction, SimulationFlag,
+        SimulationFlagForEstimateFee, StarknetError, SyncStatusType, Transaction,
+        TransactionReceiptWithBlockInfo, TransactionStatus, TransactionTrace,
+        TransactionTraceWithHash,
+    },
+    providers::{
+        jsonrpc::JsonRpcTransport, JsonRpcClient, Provider, ProviderError, ProviderRequestData,
+        ProviderResponseData,
+    },
+};
+use tokio::time::Instant;
+
+use crate::errors::ProviderWrapperError;
+
+use super::{is_transient, wrapper_error};
+
+/// Blocks an endpoint may lag behind the highest block seen on any endpoint before it is only
+/// used once every other endpoint failed.
+pub const DEFAULT_MAX_LAG: u64 = 2;
+/// Weight of the latest request in the moving average of the latency of an endpoint.
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// A `Provider` spreading requests over several JSON-RPC endpoints.
+///
+/// Reads go to the healthiest endpoint: endpoints lagging more than `max_lag` blocks behind
+/// come last, then the ones that failed their last requests, then the slowest ones. When an
+/// endpoint fails with a transient error, or does not know the requested block yet, the read
+/// fails over to the next one. Transactions are only sent to the healthiest endpoint.
+///
+/// Block heights are learned from the `block_number` responses, so `refresh` should be called
+/// regularly, e.g. on every new block, to keep the ranking up to date.
+///
+/// Clones share the health of the endpoints, so that `with_quorum` can make a quorum view of
+/// the provider for the reads the state depends on.
+#[derive(Debug)]
+pub struct FallbackProvider<T> {
+    endpoints: Arc<Vec<Endpoint<T>>>,
+    max_lag: u64,
+    quorum: Option<usize>,
+}
+
+/// Health of an endpoint, as measured by the requests sent to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EndpointHealth {
+    /// Moving average of the latency of the answered requests.
+    pub latency: Option<Duration>,
+    /// Highest block number returned by the endpoint.
+    pub block_number: Option<u64>,
+    /// Requests failed since the last answered one.
+    pub consecutive_failures: u32,
+}
+
+#[derive(Debug)]
+struct Endpoint<T> {
+    client: JsonRpcClient<T>,
+    health: Mutex<EndpointHealth>,
+}
+
+impl<T> Clone for FallbackProvider<T> {
+    fn clone(&self) -> FallbackProvider<T> {
+        FallbackProvider {
+            endpoints: self.endpoints.clone(),
+            max_lag: self.max_lag,
+            quorum: self.quorum,
+        }
+    }
+}
+
+impl<T> FallbackProvider<T> {
+    pub fn new(clients: Vec<JsonRpcClient<T>>) -> FallbackProvider<T> {
+        let endpoints = clients
+            .into_iter()
+            .map(|client| Endpoint {
+                client,
+                health: Mutex::new(EndpointHealth::default()),
+            })
+            .collect();
+
+        FallbackProvider {
+            endpoints: Arc::new(endpoints),
+            max_lag: DEFAULT_MAX_LAG,
+            quorum: None,
+        }
+    }
+
+    pub fn with_max_lag(mut self, max_lag: u64) -> FallbackProvider<T> {
+        self.max_lag = max_lag;
+        self
+    }
+
+    /// Sends `block_number`, `call`, `get_storage_at` and `get_nonce` to every endpoint, and only
+    /// returns a result once `quorum` endpoints agree on it.
+    ///
+    /// `block_number` returns the highest block reached by `quorum` endpoints. The other reads
+    /// are compared as is, so they should target a block number rather than a block tag, which
+    /// endpoints at different heights would answer differently.
+    pub fn with_quorum(mut self, quorum: usize) -> FallbackProvider<T> {
+        self.quorum = Some(quorum.max(1));
+        self
+    }
+
+    /// Returns the health of every endpoint, in the order they were given.
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| *endpoint.health())
+            .collect()
+    }
+
+    /// Returns the indices of the endpoints, healthiest first.
+    fn ranked(&self) -> Vec<usize> {
+        let health = self.health();
+        let highest_block = health.iter().filter_map(|health| health.block_number).max();
+
+        let mut ranked = (0..health.len()).collect::<Vec<_>>();
+        ranked.sort_by_key(|idx| {
+            let health = health[*idx];
+            let lagging = match (highest_block, health.block_number) {
+                (Some(highest_block), Some(block_number)) => {
+                    highest_block - block_number > self.max_lag
+                }
+                _ => false,
+            };
+            // Endpoints never measured come first, so that they get measured.
+            (
+                lagging,
+                health.consecutive_failures,
+                health.latency.unwrap_or_default(),
+            )
+        });
+        ranked
+    }
+
+    /// Sends `request` to the endpoint at `idx`, recording how it went.
+    async fn request<'a, R, F, Fut>(&'a self, idx: usize, request: &F) -> Result<R, ProviderError>
+    where
+        F: Fn(&'a Endpoint<T>) -> Fut,
+        Fut: Future<Output = Result<R, ProviderError>>,
+    {
+        let endpoint = &self.endpoints[idx];
+        let start = Instant::now();
+        let result = request(endpoint).await;
+
+        let mut health = endpoint.health();
+        match &result {
+            Err(err) if fails_over(err) => health.consecutive_failures += 1,
+            _ => {
+                let latency = start.elapsed();
+                health.latency = Some(match health.latency {
+                    Some(average) => {
+                        average.mul_f64(1.0 - LATENCY_SMOOTHING)
+                            + latency.mul_f64(LATENCY_SMOOTHING)
+                    }
+                    None => latency,
+                });
+                health.consecutive_failures = 0;
+            }
+        }
+
+        result
+    }
+
+    /// Sends a read to the healthiest endpoint, failing over to the next ones.
+    async fn read<'a, R, F, Fut>(&'a self, method: &str, request: F) -> Result<R, ProviderError>
+    where
+        F: Fn(&'a Endpoint<T>) -> Fut,
+        Fut: Future<Output = Result<R, ProviderError>>,
+    {
+        let mut last_err = None;
+        for idx in self.ranked() {
+            match self.request(idx, &request).await {
+                Err(err) if fails_over(&err) => {
+                    tracing::debug!(method, endpoint = idx, %err, "Failing over to the next endpoint");
+                    last_err = Some(err);
+                }
+                result => return result,
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| wrapper_error(ProviderWrapperError::NoEndpoints)))
+    }
+
+    /// Sends a transaction to the healthiest endpoint.
+    async fn send<'a, R, F, Fut>(&'a self, request: F) -> Result<R, ProviderError>
+    where
+        F: Fn(&'a Endpoint<T>) -> Fut,
+        Fut: Future<Output = Result<R, ProviderError>>,
+    {
+        match self.ranked().first() {
+            Some(idx) => self.request(*idx, &request).await,
+            None => Err(wrapper_error(ProviderWrapperError::NoEndpoints)),
+        }
+    }
+
+    /// Sends a read to every endpoint when running a quorum, returning the first result
+    /// `quorum` endpoints agree on. Falls back to `read` otherwise.
+    async fn critical_read<'a, R, F, Fut>(
+        &'a self,
+        method: &str,
+        request: F,
+    ) -> Result<R, ProviderError>
+    where
+        F: Fn(&'a Endpoint<T>) -> Fut,
+        Fut: Future<Output = Result<R, ProviderError>>,
+        R: PartialEq,
+    {
+        let Some(quorum) = self.quorum else {
+            return self.read(method, request).await;
+        };
+
+        let mut responses = (0..self.endpoints.len())
+            .map(|idx| self.request(idx, &request))
+            .collect::<FuturesUnordered<_>>();
+        let mut votes: Vec<(R, usize)> = vec![];
+        let mut last_err = None;
+
+        while let Some(response) = responses.next().await {
+            let value = match response {
+                Ok(value) => value,
+                Err(err) => {
+                    last_err = Some(err);
+                    continue;
+                }
+            };
+
+            let idx = match votes.iter().position(|(voted, _)| *voted == value) {
+                Some(idx) => idx,
+                None => {
+                    votes.push((value, 0));
+                    votes.len() - 1
+                }
+            };
+            votes[idx].1 += 1;
+            if votes[idx].1 >= quorum {
+                return Ok(votes.swap_remove(idx).0);
+            }
+        }
+
+        let agreeing = votes.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        tracing::warn!(method, quorum, agreeing, "Quorum not reached");
+        match last_err {
+            // Every endpoint failed, most likely for the same reason.
+            Some(err) if votes.is_empty() => Err(err),
+            _ => Err(wrapper_error(ProviderWrapperError::QuorumNotReached {
+                quorum,
+                agreeing,
+            })),
+        }
+    }
+}
+
+impl<T> FallbackProvider<T>
+where
+    T: 'static + JsonRpcTransport + Send + Sync,
+{
+    /// Measures the latency and block height of every endpoint.
+    pub async fn refresh(&self) {
+        join_all((0..self.endpoints.len()).map(|idx| self.request(idx, &Endpoint::block_number)))
+            .await;
+    }
+}
+
+impl<T> Endpoint<T> {
+    fn health(&self) -> std::sync::MutexGuard<'_, EndpointHealth> {
+        // Health is only a hint for the ranking, so it stays usable after a panic.
+        self.health.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn record_block_number(&self, block_number: u64) {
+        let mut health = self.health();
+        health.block_number = health.block_number.max(Some(block_number));
+    }
+}
+
+impl<T> Endpoint<T>
+where
+    T: 'static + JsonRpcTransport + Send + Sync,
+{
+    async fn block_number(&self) -> Result<u64, ProviderError> {
+        let block_number = self.client.block_number().await?;
+        self.record_block_number(block_number);
+        Ok(block_number)
+    }
+}
+
+/// Returns whether a read failing with `err` should be sent to another endpoint: on transient
+/// errors, and when the endpoint does not know the requested block yet.
+fn fails_over(err: &ProviderError) -> bool {
+    is_transient(err)
+        || matches!(
+            err,
+            ProviderError::StarknetError(StarknetError::BlockNotFound)
+        )
+}
+
+#[async_trait]
+impl<T> Provider for FallbackProvider<T>
+where
+    T: 'static + JsonRpcTransport + Send + Sync,
+{
+    async fn spec_version(&self) -> Result<String, ProviderError> {
+        self.read("spec_version", |endpoint| endpoint.client.spec_version())
+            .await
+    }
+
+    async fn get_block_with_tx_hashes<B>(
+        &self,
+        block_id: B,
+    ) -> Result<MaybePendingBlockWithTxHashes, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.read("get_block_with_tx_hashes", |endpoint| {
+            endpoint.client.get_block_with_tx_hashes(block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn get_block_with_txs<B>(
+        &self,
+        block_id: B,
+    ) -> Result<MaybePendingBlockWithTxs, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.read("get_block_with_txs", |endpoint| {
+            endpoint.client.get_block_with_txs(block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn get_block_with_receipts<B>(
+        &self,
+        block_id: B,
+    ) -> Result<MaybePendingBlockWithReceipts, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.read("get_block_with_receipts", |endpoint| {
+            endpoint.client.get_block_with_receipts(block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn get_state_update<B>(
+        &self,
+        block_id: B,
+    ) -> Result<MaybePendingStateUpdate, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.read("get_state_update", |endpoint| {
+            endpoint.client.get_state_update(block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn get_storage_at<A, K, B>(
+        &self,
+        contract_address: A,
+        key: K,
+        block_id: B,
+    ) -> Result<Felt, ProviderError>
+    where
+        A: AsRef<Felt> + Send + Sync,
+        K: AsRef<Felt> + Send + Sync,
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.critical_read("get_storage_at", |endpoint| {
+            endpoint.client.get_storage_at(
+                contract_address.as_ref(),
+                key.as_ref(),
+                block_id.as_ref(),
+            )
+        })
+        .await
+    }
+
+    async fn get_transaction_status<H>(
+        &self,
+        transaction_hash: H,
+    ) -> Result<TransactionStatus, ProviderError>
+    where
+        H: AsRef<Felt> + Send + Sync,
+    {
+        self.read("get_transaction_status", |endpoint| {
+            endpoint
+                .client
+                .get_transaction_status(transaction_hash.as_ref())
+        })
+        .await
+    }
+
+    async fn get_transaction_by_hash<H>(
+        &self,
+        transaction_hash: H,
+    ) -> Result<Transaction, ProviderError>
+    where
+        H: AsRef<Felt> + Send + Sync,
+    {
+        self.read("get_transaction_by_hash", |endpoint| {
+            endpoint
+                .client
+                .get_transaction_by_hash(transaction_hash.as_ref())
+        })
+        .await
+    }
+
+    async fn get_transaction_by_block_id_and_index<B>(
+        &self,
+        block_id: B,
+        index: u64,
+    ) -> Result<Transaction, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.read("get_transaction_by_block_id_and_index", |endpoint| {
+            endpoint
+                .client
+                .get_transaction_by_block_id_and_index(block_id.as_ref(), index)
+        })
+        .await
+    }
+
+    async fn get_transaction_receipt<H>(
+        &self,
+        transaction_hash: H,
+    ) -> Result<TransactionReceiptWithBlockInfo, ProviderError>
+    where
+        H: AsRef<Felt> + Send + Sync,
+    {
+        self.read("get_transaction_receipt", |endpoint| {
+            endpoint
+                .client
+                .get_transaction_receipt(transaction_hash.as_ref())
+        })
+        .await
+    }
+
+    async fn get_class<B, H>(
+        &self,
+        block_id: B,
+        class_hash: H,
+    ) -> Result<ContractClass, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+        H: AsRef<Felt> + Send + Sync,
+    {
+        self.read("get_class", |endpoint| {
+            endpoint
+                .client
+                .get_class(block_id.as_ref(), class_hash.as_ref())
+        })
+        .await
+    }
+
+    async fn get_class_hash_at<B, A>(
+        &self,
+        block_id: B,
+        contract_address: A,
+    ) -> Result<Felt, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+        A: AsRef<Felt> + Send + Sync,
+    {
+        self.read("get_class_hash_at", |endpoint| {
+            endpoint
+                .client
+                .get_class_hash_at(block_id.as_ref(), contract_address.as_ref())
+        })
+        .await
+    }
+
+    async fn get_class_at<B, A>(
+        &self,
+        block_id: B,
+        contract_address: A,
+    ) -> Result<ContractClass, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+        A: AsRef<Felt> + Send + Sync,
+    {
+        self.read("get_class_at", |endpoint| {
+            endpoint
+                .client
+                .get_class_at(block_id.as_ref(), contract_address.as_ref())
+        })
+        .await
+    }
+
+    async fn get_block_transaction_count<B>(&self, block_id: B) -> Result<u64, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.read("get_block_transaction_count", |endpoint| {
+            endpoint
+                .client
+                .get_block_transaction_count(block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn call<R, B>(&self, request: R, block_id: B) -> Result<Vec<Felt>, ProviderError>
+    where
+        R: AsRef<FunctionCall> + Send + Sync,
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.critical_read("call", |endpoint| {
+            endpoint.client.call(request.as_ref(), block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn estimate_fee<R, S, B>(
+        &self,
+        request: R,
+        simulation_flags: S,
+        block_id: B,
+    ) -> Result<Vec<FeeEstimate>, ProviderError>
+    where
+        R: AsRef<[BroadcastedTransaction]> + Send + Sync,
+        S: AsRef<[SimulationFlagForEstimateFee]> + Send + Sync,
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.read("estimate_fee", |endpoint| {
+            endpoint.client.estimate_fee(
+                request.as_ref(),
+                simulation_flags.as_ref(),
+                block_id.as_ref(),
+            )
+        })
+        .await
+    }
+
+    async fn estimate_message_fee<M, B>(
+        &self,
+        message: M,
+        block_id: B,
+    ) -> Result<FeeEstimate, ProviderError>
+    where
+        M: AsRef<MsgFromL1> + Send + Sync,
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.read("estimate_message_fee", |endpoint| {
+            endpoint
+                .client
+                .estimate_message_fee(message.as_ref(), block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn block_number(&self) -> Result<u64, ProviderError> {
+        let Some(quorum) = self.quorum else {
+            return self.read("block_number", Endpoint::block_number).await;
+        };
+
+        let responses = join_all(
+            (0..self.endpoints.len()).map(|idx| self.request(idx, &Endpoint::block_number)),
+        )
+        .await;
+        let mut block_numbers = responses
+            .into_iter()
+            .filter_map(Result::ok)
+            .collect::<Vec<_>>();
+        block_numbers.sort_unstable_by(|a, b| b.cmp(a));
+
+        block_numbers.get(quorum - 1).copied().ok_or_else(|| {
+            wrapper_error(ProviderWrapperError::QuorumNotReached {
+                quorum,
+                agreeing: block_numbers.len(),
+            })
+        })
+    }
+
+    async fn block_hash_and_number(&self) -> Result<BlockHashAndNumber, ProviderError> {
+        self.read("block_hash_and_number", |endpoint| async move {
+            let block = endpoint.client.block_hash_and_number().await?;
+            endpoint.record_block_number(block.block_number);
+            Ok(block)
+        })
+        .await
+    }
+
+    async fn chain_id(&self) -> Result<Felt, ProviderError> {
+        self.read("chain_id", |endpoint| endpoint.client.chain_id())
+            .await
+    }
+
+    async fn syncing(&self) -> Result<SyncStatusType, ProviderError> {
+        self.read("syncing", |endpoint| endpoint.client.syncing())
+            .await
+    }
+
+    async fn get_events(
+        &self,
+        filter: EventFilter,
+        continuation_token: Option<String>,
+        chunk_size: u64,
+    ) -> Result<EventsPage, ProviderError> {
+        self.read("get_events", |endpoint| {
+            endpoint
+                .client
+                .get_events(filter.clone(), continuation_token.clone(), chunk_size)
+        })
+        .await
+    }
+
+    async fn get_nonce<B, A>(&self, block_id: B, contract_address: A) -> Result<Felt, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+        A: AsRef<Felt> + Send + Sync,
+    {
+        self.critical_read("get_nonce", |endpoint| {
+            endpoint
+                .client
+                .get_nonce(block_id.as_ref(), contract_address.as_ref())
+        })
+        .await
+    }
+
+    async fn add_invoke_transaction<I>(
+        &self,
+        invoke_transaction: I,
+    ) -> Result<InvokeTransactionResult, ProviderError>
+    where
+        I: AsRef<BroadcastedInvokeTransaction> + Send + Sync,
+    {
+        self.send(|endpoint| {
+            endpoint
+                .client
+                .add_invoke_transaction(invoke_transaction.as_ref())
+        })
+        .await
+    }
+
+    async fn add_declare_transaction<D>(
+        &self,
+        declare_transaction: D,
+    ) -> Result<DeclareTransactionResult, ProviderError>
+    where
+        D: AsRef<BroadcastedDeclareTransaction> + Send + Sync,
+    {
+        self.send(|endpoint| {
+            endpoint
+                .client
+                .add_declare_transaction(declare_transaction.as_ref())
+        })
+        .await
+    }
+
+    async fn add_deploy_account_transaction<D>(
+        &self,
+        deploy_account_transaction: D,
+    ) -> Result<DeployAccountTransactionResult, ProviderError>
+    where
+        D: AsRef<BroadcastedDeployAccountTransaction> + Send + Sync,
+    {
+        self.send(|endpoint| {
+            endpoint
+                .client
+                .add_deploy_account_transaction(deploy_account_transaction.as_ref())
+        })
+        .await
+    }
+
+    async fn trace_transaction<H>(
+        &self,
+        transaction_hash: H,
+    ) -> Result<TransactionTrace, ProviderError>
+    where
+        H: AsRef<Felt> + Send + Sync,
+    {
+        self.read("trace_transaction", |endpoint| {
+            endpoint.client.trace_transaction(transaction_hash.as_ref())
+        })
+        .await
+    }
+
+    async fn simulate_transactions<B, TX, S>(
+        &self,
+        block_id: B,
+        transactions: TX,
+        simulation_flags: S,
+    ) -> Result<Vec<SimulatedTransaction>, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+        TX: AsRef<[BroadcastedTransaction]> + Send + Sync,
+        S: AsRef<[SimulationFlag]> + Send + Sync,
+    {
+        self.read("simulate_transactions", |endpoint| {
+            endpoint.client.simulate_transactions(
+                block_id.as_ref(),
+                transactions.as_ref(),
+                simulation_flags.as_ref(),
+            )
+        })
+        .await
+    }
+
+    async fn trace_block_transactions<B>(
+        &self,
+        block_id: B,
+    ) -> Result<Vec<TransactionTraceWithHash>, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.read("trace_block_transactions", |endpoint| {
+            endpoint.client.trace_block_transactions(block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn batch_requests<R>(
+        &self,
+        requests: R,
+    ) -> Result<Vec<ProviderResponseData>, ProviderError>
+    where
+        R: AsRef<[ProviderRequestData]> + Send + Sync,
+    {
+        self.read("batch_requests", |endpoint| {
+            endpoint.client.batch_requests(requests.as_ref())
+        })
+        .await
+    }
+}
@@ -0,0 +1,53 @@
+pub mod fallback;
+pub mod retry;
+
+use starknet::providers::{
+    jsonrpc::{HttpTransportError, JsonRpcClientError, JsonRpcError},
+    ProviderError,
+};
+
+use crate::errors::ProviderWrapperError;
+
+/// Returns whether `err` may not happen again if the request is retried: rate limiting,
+/// timeouts and transport errors. Starknet errors, JSON-RPC errors and malformed responses are
+/// final.
+///
+/// Errors of the JSON-RPC client over HTTP and of the wrappers of this module are told apart by
+/// downcasting them. The errors of other provider implementations are assumed to come from
+/// their transport.
+pub(crate) fn is_transient(err: &ProviderError) -> bool {
+    let err = match err {
+        ProviderError::RateLimited => return true,
+        ProviderError::StarknetError(_) | ProviderError::ArrayLengthMismatch => return false,
+        ProviderError::Other(err) => err.as_any(),
+    };
+
+    if let Some(err) = err.downcast_ref::<JsonRpcClientError<HttpTransportError>>() {
+        match err {
+            JsonRpcClientError::TransportError(HttpTransportError::Reqwest(_)) => true,
+            JsonRpcClientError::JsonRpcError(err) => is_rate_limited(err),
+            _ => false,
+        }
+    } else if let Some(err) = err.downcast_ref::<JsonRpcClientError<ProviderWrapperError>>() {
+        // A quorum is missed when endpoints fail or lag behind, which can both be transient.
+        matches!(
+            err,
+            JsonRpcClientError::TransportError(
+                ProviderWrapperError::Timeout(_) | ProviderWrapperError::QuorumNotReached { .. }
+            )
+        )
+    } else {
+        true
+    }
+}
+
+/// Returns whether a JSON-RPC error reports rate limiting, as node providers do with either
+/// the HTTP status code or the "limit exceeded" code.
+fn is_rate_limited(err: &JsonRpcError) -> bool {
+    matches!(err.code, 429 | -32005)
+}
+
+/// Boxes `err` in a `ProviderError`, as the transport error of a JSON-RPC client.
+pub(crate) fn wrapper_error(err: ProviderWrapperError) -> ProviderError {
+    JsonRpcClientError::TransportError(err).into()
+}
@@ -0,0 +1,638 @@
+use std::{
+    collections::hash_map::RandomState,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use starknet::{
+    core::types::{
+        BlockHashAndNumber, BlockId, BroadcastedDeclareTransaction,
+        BroadcastedDeployAccountTransaction, BroadcastedInvokeTransaction, BroadcastedTransaction,
+        ContractClass, DeclareTransactionResult, DeployAccountTransactionResult, EventFilter,
+        EventsPage, FeeEstimate, Felt, FunctionCall, InvokeTransactionResult,
+        MaybePendingBlockWithReceipts, MaybePendingBlockWithTxHashes, MaybePendingBlockWithTxs,
+        MaybePendingStateUpdate, MsgFromL1, SimulatedTransaction, SimulationFlag,
+        SimulationFlagForEstimateFee, SyncStatusType, Transaction, TransactionReceiptWithBlockInfo,
+        TransactionStatus, TransactionTrace, TransactionTraceWithHash,
+    },
+    providers::{Provider, ProviderError, ProviderRequestData, ProviderResponseData},
+};
+use tokio::time::Instant;
+
+use crate::errors::ProviderWrapperError;
+
+use super::{is_transient, wrapper_error};
+
+pub const DEFAULT_MAX_RETRIES: u32 = 5;
+/// Delay before the first retry, doubled on every following one.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+/// Time after which a single attempt of a request is abandoned.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A `Provider` wrapping another one to make it resilient to flaky or rate limited nodes.
+///
+/// Every request waits for a token of an optional token bucket, so that bursts are spread out
+/// before they reach the node, and every attempt is abandoned after a timeout. Failed reads are
+/// retried with an exponential backoff and jitter, unless the node returned a Starknet error,
+/// which would only be returned again. Transactions are never retried, as a submission that
+/// timed out may still have been received.
+#[derive(Debug)]
+pub struct RetryProvider<P> {
+    inner: P,
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    timeout: Duration,
+    rate_limiter: Option<RateLimiter>,
+    counters: Counters,
+}
+
+/// Counts of the requests handled by a `RetryProvider` since it was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProviderStats {
+    /// Attempts sent to the inner provider, retries included.
+    pub requests: u64,
+    pub retries: u64,
+    /// Requests that waited for the rate limiter.
+    pub throttled: u64,
+    pub timeouts: u64,
+    /// Requests that returned an error, after every retry.
+    pub failures: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    requests: AtomicU64,
+    retries: AtomicU64,
+    throttled: AtomicU64,
+    timeouts: AtomicU64,
+    failures: AtomicU64,
+}
+
+#[derive(Debug)]
+struct RateLimiter {
+    requests_per_second: f64,
+    burst: f64,
+    /// Tokens available, and when they were last refilled.
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl<P> RetryProvider<P> {
+    pub fn new(inner: P) -> RetryProvider<P> {
+        RetryProvider {
+            inner,
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            rate_limiter: None,
+            counters: Counters::default(),
+        }
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> RetryProvider<P> {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> RetryProvider<P> {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> RetryProvider<P> {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Limits the requests sent to the inner provider to `requests_per_second`, allowing bursts
+    /// of up to `burst` requests.
+    pub fn with_rate_limit(mut self, requests_per_second: f64, burst: u32) -> RetryProvider<P> {
+        let burst = f64::from(burst.max(1));
+        self.rate_limiter = Some(RateLimiter {
+            requests_per_second,
+            burst,
+            bucket: Mutex::new((burst, Instant::now())),
+        });
+        self
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn stats(&self) -> ProviderStats {
+        ProviderStats {
+            requests: self.counters.requests.load(Ordering::Relaxed),
+            retries: self.counters.retries.load(Ordering::Relaxed),
+            throttled: self.counters.throttled.load(Ordering::Relaxed),
+            timeouts: self.counters.timeouts.load(Ordering::Relaxed),
+            failures: self.counters.failures.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Sends a read, retrying it while it fails with a transient error.
+    async fn retry<T, F, Fut>(&self, method: &str, request: F) -> Result<T, ProviderError>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<T, ProviderError>> + Send,
+        T: Send,
+    {
+        let mut retries = 0;
+        loop {
+            match self.attempt(&request).await {
+                Err(err) if retries < self.max_retries && is_transient(&err) => {
+                    let backoff = self.backoff(retries);
+                    tracing::debug!(method, retries, ?backoff, %err, "Retrying request");
+                    retries += 1;
+                    self.counters.retries.fetch_add(1, Ordering::Relaxed);
+                    tokio::time::sleep(backoff).await;
+                }
+                result => return self.record(method, result),
+            }
+        }
+    }
+
+    /// Sends a transaction, without retrying it.
+    async fn send<T, F, Fut>(&self, method: &str, request: F) -> Result<T, ProviderError>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<T, ProviderError>> + Send,
+        T: Send,
+    {
+        let result = self.attempt(&request).await;
+        self.record(method, result)
+    }
+
+    async fn attempt<T, F, Fut>(&self, request: &F) -> Result<T, ProviderError>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<T, ProviderError>> + Send,
+    {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            if rate_limiter.acquire().await {
+                self.counters.throttled.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        self.counters.requests.fetch_add(1, Ordering::Relaxed);
+        match tokio::time::timeout(self.timeout, request()).await {
+            Ok(result) => result,
+            Err(_) => {
+                self.counters.timeouts.fetch_add(1, Ordering::Relaxed);
+                Err(wrapper_error(ProviderWrapperError::Timeout(self.timeout)))
+            }
+        }
+    }
+
+    fn record<T>(
+        &self,
+        method: &str,
+        result: Result<T, ProviderError>,
+    ) -> Result<T, ProviderError> {
+        if let Err(err) = &result {
+            tracing::warn!(method, %err, "Request failed");
+            self.counters.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Returns the delay before retry number `retries`, with "equal jitter": half of the
+    /// exponential backoff, plus a random part of the other half.
+    fn backoff(&self, retries: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retries))
+            .min(self.max_backoff);
+        backoff / 2 + (backoff / 2).mul_f64(random_unit())
+    }
+}
+
+impl RateLimiter {
+    /// Waits until a token is available and takes it, returning whether it had to wait.
+    async fn acquire(&self) -> bool {
+        let mut waited = false;
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap_or_else(PoisonError::into_inner);
+                let (tokens, refilled_at) = &mut *bucket;
+                let now = Instant::now();
+                *tokens = (*tokens
+                    + now.duration_since(*refilled_at).as_secs_f64() * self.requests_per_second)
+                    .min(self.burst);
+                *refilled_at = now;
+
+                if *tokens >= 1.0 {
+                    *tokens -= 1.0;
+                    return waited;
+                }
+                Duration::from_secs_f64((1.0 - *tokens) / self.requests_per_second)
+            };
+
+            waited = true;
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Returns a random number in `[0, 1]`, seeded by the random keys of the std hasher.
+fn random_unit() -> f64 {
+    RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64
+}
+
+#[async_trait]
+impl<P> Provider for RetryProvider<P>
+where
+    P: Provider + Send + Sync,
+{
+    async fn spec_version(&self) -> Result<String, ProviderError> {
+        self.retry("spec_version", || self.inner.spec_version())
+            .await
+    }
+
+    async fn get_block_with_tx_hashes<B>(
+        &self,
+        block_id: B,
+    ) -> Result<MaybePendingBlockWithTxHashes, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.retry("get_block_with_tx_hashes", || {
+            self.inner.get_block_with_tx_hashes(block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn get_block_with_txs<B>(
+        &self,
+        block_id: B,
+    ) -> Result<MaybePendingBlockWithTxs, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.retry("get_block_with_txs", || {
+            self.inner.get_block_with_txs(block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn get_block_with_receipts<B>(
+        &self,
+        block_id: B,
+    ) -> Result<MaybePendingBlockWithReceipts, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.retry("get_block_with_receipts", || {
+            self.inner.get_block_with_receipts(block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn get_state_update<B>(
+        &self,
+        block_id: B,
+    ) -> Result<MaybePendingStateUpdate, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.retry("get_state_update", || {
+            self.inner.get_state_update(block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn get_storage_at<A, K, B>(
+        &self,
+        contract_address: A,
+        key: K,
+        block_id: B,
+    ) -> Result<Felt, ProviderError>
+    where
+        A: AsRef<Felt> + Send + Sync,
+        K: AsRef<Felt> + Send + Sync,
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.retry("get_storage_at", || {
+            self.inner
+                .get_storage_at(contract_address.as_ref(), key.as_ref(), block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn get_transaction_status<H>(
+        &self,
+        transaction_hash: H,
+    ) -> Result<TransactionStatus, ProviderError>
+    where
+        H: AsRef<Felt> + Send + Sync,
+    {
+        self.retry("get_transaction_status", || {
+            self.inner.get_transaction_status(transaction_hash.as_ref())
+        })
+        .await
+    }
+
+    async fn get_transaction_by_hash<H>(
+        &self,
+        transaction_hash: H,
+    ) -> Result<Transaction, ProviderError>
+    where
+        H: AsRef<Felt> + Send + Sync,
+    {
+        self.retry("get_transaction_by_hash", || {
+            self.inner
+                .get_transaction_by_hash(transaction_hash.as_ref())
+        })
+        .await
+    }
+
+    async fn get_transaction_by_block_id_and_index<B>(
+        &self,
+        block_id: B,
+        index: u64,
+    ) -> Result<Transaction, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.retry("get_transaction_by_block_id_and_index", || {
+            self.inner
+                .get_transaction_by_block_id_and_index(block_id.as_ref(), index)
+        })
+        .await
+    }
+
+    async fn get_transaction_receipt<H>(
+        &self,
+        transaction_hash: H,
+    ) -> Result<TransactionReceiptWithBlockInfo, ProviderError>
+    where
+        H: AsRef<Felt> + Send + Sync,
+    {
+        self.retry("get_transaction_receipt", || {
+            self.inner
+                .get_transaction_receipt(transaction_hash.as_ref())
+        })
+        .await
+    }
+
+    async fn get_class<B, H>(
+        &self,
+        block_id: B,
+        class_hash: H,
+    ) -> Result<ContractClass, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+        H: AsRef<Felt> + Send + Sync,
+    {
+        self.retry("get_class", || {
+            self.inner.get_class(block_id.as_ref(), class_hash.as_ref())
+        })
+        .await
+    }
+
+    async fn get_class_hash_at<B, A>(
+        &self,
+        block_id: B,
+        contract_address: A,
+    ) -> Result<Felt, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+        A: AsRef<Felt> + Send + Sync,
+    {
+        self.retry("get_class_hash_at", || {
+            self.inner
+                .get_class_hash_at(block_id.as_ref(), contract_address.as_ref())
+        })
+        .await
+    }
+
+    async fn get_class_at<B, A>(
+        &self,
+        block_id: B,
+        contract_address: A,
+    ) -> Result<ContractClass, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+        A: AsRef<Felt> + Send + Sync,
+    {
+        self.retry("get_class_at", || {
+            self.inner
+                .get_class_at(block_id.as_ref(), contract_address.as_ref())
+        })
+        .await
+    }
+
+    async fn get_block_transaction_count<B>(&self, block_id: B) -> Result<u64, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.retry("get_block_transaction_count", || {
+            self.inner.get_block_transaction_count(block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn call<R, B>(&self, request: R, block_id: B) -> Result<Vec<Felt>, ProviderError>
+    where
+        R: AsRef<FunctionCall> + Send + Sync,
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.retry("call", || {
+            self.inner.call(request.as_ref(), block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn estimate_fee<R, S, B>(
+        &self,
+        request: R,
+        simulation_flags: S,
+        block_id: B,
+    ) -> Result<Vec<FeeEstimate>, ProviderError>
+    where
+        R: AsRef<[BroadcastedTransaction]> + Send + Sync,
+        S: AsRef<[SimulationFlagForEstimateFee]> + Send + Sync,
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.retry("estimate_fee", || {
+            self.inner.estimate_fee(
+                request.as_ref(),
+                simulation_flags.as_ref(),
+                block_id.as_ref(),
+            )
+        })
+        .await
+    }
+
+    async fn estimate_message_fee<M, B>(
+        &self,
+        message: M,
+        block_id: B,
+    ) -> Result<FeeEstimate, ProviderError>
+    where
+        M: AsRef<MsgFromL1> + Send + Sync,
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.retry("estimate_message_fee", || {
+            self.inner
+                .estimate_message_fee(message.as_ref(), block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn block_number(&self) -> Result<u64, ProviderError> {
+        self.retry("block_number", || self.inner.block_number())
+            .await
+    }
+
+    async fn block_hash_and_number(&self) -> Result<BlockHashAndNumber, ProviderError> {
+        self.retry("block_hash_and_number", || {
+            self.inner.block_hash_and_number()
+        })
+        .await
+    }
+
+    async fn chain_id(&self) -> Result<Felt, ProviderError> {
+        self.retry("chain_id", || self.inner.chain_id()).await
+    }
+
+    async fn syncing(&self) -> Result<SyncStatusType, ProviderError> {
+        self.retry("syncing", || self.inner.syncing()).await
+    }
+
+    async fn get_events(
+        &self,
+        filter: EventFilter,
+        continuation_token: Option<String>,
+        chunk_size: u64,
+    ) -> Result<EventsPage, ProviderError> {
+        self.retry("get_events", || {
+            self.inner
+                .get_events(filter.clone(), continuation_token.clone(), chunk_size)
+        })
+        .await
+    }
+
+    async fn get_nonce<B, A>(&self, block_id: B, contract_address: A) -> Result<Felt, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+        A: AsRef<Felt> + Send + Sync,
+    {
+        self.retry("get_nonce", || {
+            self.inner
+                .get_nonce(block_id.as_ref(), contract_address.as_ref())
+        })
+        .await
+    }
+
+    async fn add_invoke_transaction<I>(
+        &self,
+        invoke_transaction: I,
+    ) -> Result<InvokeTransactionResult, ProviderError>
+    where
+        I: AsRef<BroadcastedInvokeTransaction> + Send + Sync,
+    {
+        self.send("add_invoke_transaction", || {
+            self.inner
+                .add_invoke_transaction(invoke_transaction.as_ref())
+        })
+        .await
+    }
+
+    async fn add_declare_transaction<D>(
+        &self,
+        declare_transaction: D,
+    ) -> Result<DeclareTransactionResult, ProviderError>
+    where
+        D: AsRef<BroadcastedDeclareTransaction> + Send + Sync,
+    {
+        self.send("add_declare_transaction", || {
+            self.inner
+                .add_declare_transaction(declare_transaction.as_ref())
+        })
+        .await
+    }
+
+    async fn add_deploy_account_transaction<D>(
+        &self,
+        deploy_account_transaction: D,
+    ) -> Result<DeployAccountTransactionResult, ProviderError>
+    where
+        D: AsRef<BroadcastedDeployAccountTransaction> + Send + Sync,
+    {
+        self.send("add_deploy_account_transaction", || {
+            self.inner
+                .add_deploy_account_transaction(deploy_account_transaction.as_ref())
+        })
+        .await
+    }
+
+    async fn trace_transaction<H>(
+        &self,
+        transaction_hash: H,
+    ) -> Result<TransactionTrace, ProviderError>
+    where
+        H: AsRef<Felt> + Send + Sync,
+    {
+        self.retry("trace_transaction", || {
+            self.inner.trace_transaction(transaction_hash.as_ref())
+        })
+        .await
+    }
+
+    async fn simulate_transactions<B, T, S>(
+        &self,
+        block_id: B,
+        transactions: T,
+        simulation_flags: S,
+    ) -> Result<Vec<SimulatedTransaction>, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+        T: AsRef<[BroadcastedTransaction]> + Send + Sync,
+        S: AsRef<[SimulationFlag]> + Send + Sync,
+    {
+        self.retry("simulate_transactions", || {
+            self.inner.simulate_transactions(
+                block_id.as_ref(),
+                transactions.as_ref(),
+                simulation_flags.as_ref(),
+            )
+        })
+        .await
+    }
+
+    async fn trace_block_transactions<B>(
+        &self,
+        block_id: B,
+    ) -> Result<Vec<TransactionTraceWithHash>, ProviderError>
+    where
+        B: AsRef<BlockId> + Send + Sync,
+    {
+        self.retry("trace_block_transactions", || {
+            self.inner.trace_block_transactions(block_id.as_ref())
+        })
+        .await
+    }
+
+    async fn batch_requests<R>(
+        &self,
+        requests: R,
+    ) -> Result<Vec<ProviderResponseData>, ProviderError>
+    where
+        R: AsRef<[ProviderRequestData]> + Send + Sync,
+    {
+        self.retry("batch_requests", || {
+            self.inner.batch_requests(requests.as_ref())
+        })
+        .await
+    }
+}
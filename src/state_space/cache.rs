@@ -0,0 +1,70 @@
+use std::collections::VecDeque;
+
+use starknet::core::types::Felt;
+
+use crate::amm::pool::AMM;
+
+/// Number of synced blocks kept to roll the state space back on a reorg.
+pub const STATE_CHANGE_CACHE_SIZE: usize = 150;
+
+/// What a synced block changed in the state space.
+#[derive(Debug, Clone)]
+pub struct StateChange {
+    pub block_number: u64,
+    pub block_hash: Felt,
+    /// States of the updated pools before the block was applied.
+    pub previous_amms: Vec<AMM>,
+    /// Addresses of the pools the block added.
+    pub added_amms: Vec<Felt>,
+}
+
+/// Ring buffer of the state changes of the most recent blocks, oldest first.
+#[derive(Debug, Clone)]
+pub struct StateChangeCache {
+    changes: VecDeque<StateChange>,
+    capacity: usize,
+}
+
+impl StateChangeCache {
+    pub fn new(capacity: usize) -> StateChangeCache {
+        StateChangeCache {
+            changes: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Records the change of the latest block, evicting the oldest one when full.
+    pub fn push(&mut self, change: StateChange) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.changes.len() == self.capacity {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(change);
+    }
+
+    /// Returns the change of the latest cached block.
+    pub fn latest(&self) -> Option<&StateChange> {
+        self.changes.back()
+    }
+
+    /// Removes and returns the change of the latest cached block.
+    pub fn pop_latest(&mut self) -> Option<StateChange> {
+        self.changes.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl Default for StateChangeCache {
+    fn default() -> StateChangeCache {
+        StateChangeCache::new(STATE_CHANGE_CACHE_SIZE)
+    }
+}
@@ -0,0 +1,382 @@
+pub mod cache;
+
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use starknet::{
+    core::types::{BlockId, EmittedEvent, EventFilter, Felt, MaybePendingBlockWithTxHashes},
+    providers::{Provider, ProviderError},
+};
+use tokio::{
+    sync::{broadcast, RwLock},
+    task::JoinHandle,
+};
+
+use crate::{
+    amm::{
+        factory::{AutomatedMarketMakerFactory, Factory, EVENTS_CHUNK_SIZE},
+        pool::{
+            apply_events_skipping_failures, sync_on_event_signatures, AutomatedMarketMaker, AMM,
+        },
+    },
+    errors::{AMMError, StateSpaceError},
+    provider::is_transient,
+    utils::get_events,
+};
+
+use self::cache::{StateChange, StateChangeCache};
+
+/// Pools tracked by a `StateSpaceManager`, keyed by their `address()`.
+pub type StateSpace = HashMap<Felt, AMM>;
+
+/// Number of state changes a subscriber can lag behind before it misses the oldest ones.
+pub const STATE_CHANGE_CHANNEL_CAPACITY: usize = 100;
+/// Delay before the spawned sync loop retries after a failed sync, doubled on every following
+/// failure up to `MAX_SYNC_BACKOFF`.
+pub const INITIAL_SYNC_BACKOFF: Duration = Duration::from_millis(500);
+pub const MAX_SYNC_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Keeps a set of pools in sync with the chain block by block.
+///
+/// Every synced block applies the events of the tracked pools, adds the pools created by
+/// `factories`, and broadcasts the addresses of the pools that changed. What each block
+/// changed is kept in a `StateChangeCache`, so blocks that get reorganized away can be
+/// rolled back.
+pub struct StateSpaceManager<P> {
+    state: Arc<RwLock<StateSpace>>,
+    factories: Vec<Factory>,
+    latest_synced_block: u64,
+    state_change_cache: StateChangeCache,
+    provider: Arc<P>,
+    state_changes: broadcast::Sender<Vec<Felt>>,
+}
+
+/// Hashes identifying a block and its position in the chain.
+struct BlockHeader {
+    number: u64,
+    hash: Felt,
+    parent_hash: Felt,
+}
+
+impl<P> StateSpaceManager<P>
+where
+    P: Provider + Send + Sync + 'static,
+{
+    /// Creates a manager for `amms`, whose state must be the one at `latest_synced_block`.
+    pub fn new(
+        amms: Vec<AMM>,
+        factories: Vec<Factory>,
+        latest_synced_block: u64,
+        provider: Arc<P>,
+    ) -> StateSpaceManager<P> {
+        let state = amms.into_iter().map(|amm| (amm.address(), amm)).collect();
+        let (state_changes, _) = broadcast::channel(STATE_CHANGE_CHANNEL_CAPACITY);
+
+        StateSpaceManager {
+            state: Arc::new(RwLock::new(state)),
+            factories,
+            latest_synced_block,
+            state_change_cache: StateChangeCache::default(),
+            provider,
+            state_changes,
+        }
+    }
+
+    /// Sets the number of blocks that can be rolled back on a reorg.
+    pub fn with_state_change_cache_size(mut self, size: usize) -> StateSpaceManager<P> {
+        self.state_change_cache = StateChangeCache::new(size);
+        self
+    }
+
+    /// Returns a handle to the pools, shared with the manager.
+    pub fn state(&self) -> Arc<RwLock<StateSpace>> {
+        self.state.clone()
+    }
+
+    pub fn latest_synced_block(&self) -> u64 {
+        self.latest_synced_block
+    }
+
+    /// Returns a receiver of the addresses of the pools changed by each synced block.
+    pub fn subscribe_state_changes(&self) -> broadcast::Receiver<Vec<Felt>> {
+        self.state_changes.subscribe()
+    }
+
+    /// Syncs every block after `latest_synced_block` up to the current chain head.
+    pub async fn sync_to_latest_block(&mut self) -> Result<(), StateSpaceError> {
+        let chain_head = self.provider.block_number().await?;
+        while self.latest_synced_block < chain_head {
+            self.sync_block(self.latest_synced_block + 1).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Brings the state to the end of `block_number` and returns the addresses of the pools
+    /// that changed, which are also sent to the subscribers.
+    ///
+    /// Every block after the latest synced one is synced in turn, so that no event is missed.
+    /// When the parent of a block is not the latest synced block, the blocks that were
+    /// reorganized away are rolled back to the common ancestor and the canonical blocks are
+    /// applied again. Pools created in a block are read at the end of it, so their events
+    /// from the same block are not applied on top.
+    pub async fn sync_block(&mut self, block_number: u64) -> Result<Vec<Felt>, StateSpaceError> {
+        if block_number <= self.latest_synced_block {
+            return Err(StateSpaceError::BlockAlreadySynced(block_number));
+        }
+
+        let mut changed = vec![];
+        while self.latest_synced_block < block_number {
+            changed.extend(self.sync_next_block().await?);
+        }
+
+        let mut seen = HashSet::new();
+        changed.retain(|address| seen.insert(*address));
+        tracing::debug!(block_number, changed = changed.len(), "Synced state space");
+
+        if !changed.is_empty() {
+            // Sending only fails when nobody is subscribed.
+            let _ = self.state_changes.send(changed.clone());
+        }
+
+        Ok(changed)
+    }
+
+    /// Moves the manager to a task that syncs every new block, polling the chain head every
+    /// `poll_interval`.
+    ///
+    /// A failed sync is retried with an exponential backoff, from the block it stopped at. The
+    /// task only ends on the errors a retry cannot fix, see `is_fatal`.
+    pub fn spawn(mut self, poll_interval: Duration) -> JoinHandle<Result<(), StateSpaceError>> {
+        tokio::spawn(async move {
+            let mut backoff = INITIAL_SYNC_BACKOFF;
+            loop {
+                match self.sync_to_latest_block().await {
+                    Ok(()) => {
+                        backoff = INITIAL_SYNC_BACKOFF;
+                        tokio::time::sleep(poll_interval).await;
+                    }
+                    Err(err) if is_fatal(&err) => return Err(err),
+                    Err(err) => {
+                        tracing::warn!(%err, block_number = self.latest_synced_block + 1, ?backoff, "Failed to sync block, retrying");
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(MAX_SYNC_BACKOFF);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Syncs the block after the latest synced one, rolling back the blocks reorganized away
+    /// first.
+    async fn sync_next_block(&mut self) -> Result<Vec<Felt>, StateSpaceError> {
+        let header = self.get_block_header(self.latest_synced_block + 1).await?;
+
+        let mut changed = vec![];
+        let reorged = self.state_change_cache.latest().is_some_and(|latest| {
+            latest.block_number + 1 == header.number && latest.block_hash != header.parent_hash
+        });
+        if reorged {
+            changed.extend(self.unwind_to_common_ancestor().await?);
+            while self.latest_synced_block + 1 < header.number {
+                let header = self.get_block_header(self.latest_synced_block + 1).await?;
+                changed.extend(self.apply_block(&header).await?);
+            }
+        }
+        changed.extend(self.apply_block(&header).await?);
+
+        Ok(changed)
+    }
+
+    /// Applies a block on top of the state and records what it changed.
+    async fn apply_block(&mut self, header: &BlockHeader) -> Result<Vec<Felt>, StateSpaceError> {
+        // Pin everything to the hash so a block replaced meanwhile is not mixed in.
+        let block_id = BlockId::Hash(header.hash);
+        let events = self.get_block_events(block_id).await?;
+        let new_amms = self.discover_pools(&events, block_id).await?;
+
+        let mut state = self.state.write().await;
+        let (previous_amms, errors) = apply_events_skipping_failures(state.values_mut(), &events);
+        for err in errors {
+            tracing::warn!(%err, block_number = header.number, "Skipped event that failed to apply");
+        }
+        let mut added_amms = vec![];
+        for amm in new_amms {
+            let address = amm.address();
+            if let Entry::Vacant(entry) = state.entry(address) {
+                entry.insert(amm);
+                added_amms.push(address);
+            }
+        }
+        drop(state);
+
+        let changed = previous_amms
+            .iter()
+            .map(|amm| amm.address())
+            .chain(added_amms.iter().copied())
+            .collect();
+
+        self.state_change_cache.push(StateChange {
+            block_number: header.number,
+            block_hash: header.hash,
+            previous_amms,
+            added_amms,
+        });
+        self.latest_synced_block = header.number;
+
+        Ok(changed)
+    }
+
+    /// Rolls back the cached blocks that are no longer canonical, and returns the addresses of
+    /// the pools that were reverted.
+    async fn unwind_to_common_ancestor(&mut self) -> Result<Vec<Felt>, StateSpaceError> {
+        let mut reverted = vec![];
+        loop {
+            let Some(latest) = self.state_change_cache.latest() else {
+                return Err(StateSpaceError::ReorgBeyondCache);
+            };
+
+            let canonical = self.get_block_header(latest.block_number).await?;
+            if canonical.hash == latest.block_hash {
+                self.latest_synced_block = latest.block_number;
+                break;
+            }
+
+            let Some(change) = self.state_change_cache.pop_latest() else {
+                return Err(StateSpaceError::ReorgBeyondCache);
+            };
+            tracing::warn!(block_number = change.block_number, block_hash = ?change.block_hash, "Rolling back reorged block");
+
+            self.latest_synced_block = change.block_number - 1;
+
+            let mut state = self.state.write().await;
+            for address in change.added_amms {
+                state.remove(&address);
+                reverted.push(address);
+            }
+            for amm in change.previous_amms {
+                let address = amm.address();
+                state.insert(address, amm);
+                reverted.push(address);
+            }
+        }
+
+        Ok(reverted)
+    }
+
+    async fn get_block_header(&self, block_number: u64) -> Result<BlockHeader, StateSpaceError> {
+        match self
+            .provider
+            .get_block_with_tx_hashes(BlockId::Number(block_number))
+            .await?
+        {
+            MaybePendingBlockWithTxHashes::Block(block) => Ok(BlockHeader {
+                number: block.block_number,
+                hash: block.block_hash,
+                parent_hash: block.parent_hash,
+            }),
+            MaybePendingBlockWithTxHashes::PendingBlock(_) => {
+                Err(StateSpaceError::BlockNotFound(block_number))
+            }
+        }
+    }
+
+    /// Fetches the events of `block_id` that update a tracked pool or create a new one.
+    async fn get_block_events(
+        &self,
+        block_id: BlockId,
+    ) -> Result<Vec<EmittedEvent>, StateSpaceError> {
+        let mut signatures = sync_on_event_signatures(self.state.read().await.values());
+        for factory in &self.factories {
+            for signature in factory.amm_created_event_signature().into_iter().flatten() {
+                if !signatures.contains(&signature) {
+                    signatures.push(signature);
+                }
+            }
+        }
+
+        if signatures.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let filter = EventFilter {
+            from_block: Some(block_id),
+            to_block: Some(block_id),
+            address: None,
+            keys: Some(vec![signatures]),
+        };
+
+        Ok(get_events(self.provider.clone(), filter, EVENTS_CHUNK_SIZE).await?)
+    }
+
+    /// Decodes the pool creation events of the factories and populates the new pools at
+    /// `block_id`.
+    async fn discover_pools(
+        &self,
+        events: &[EmittedEvent],
+        block_id: BlockId,
+    ) -> Result<Vec<AMM>, StateSpaceError> {
+        let known = self
+            .state
+            .read()
+            .await
+            .keys()
+            .copied()
+            .collect::<HashSet<_>>();
+
+        let mut new_amms = vec![];
+        for factory in &self.factories {
+            let signatures = factory
+                .amm_created_event_signature()
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+            let mut amms = vec![];
+            for event in events {
+                let created = event.from_address == factory.address()
+                    && event
+                        .keys
+                        .first()
+                        .is_some_and(|selector| signatures.contains(selector));
+                if !created {
+                    continue;
+                }
+
+                match factory.new_empty_amm_from_log(event.clone()) {
+                    Ok(amm) if !known.contains(&amm.address()) => amms.push(amm),
+                    Ok(_) => {}
+                    Err(err) => {
+                        tracing::warn!(%err, transaction_hash = ?event.transaction_hash, "Skipped pool creation event that failed to decode");
+                    }
+                }
+            }
+
+            if !amms.is_empty() {
+                factory
+                    .populate_amm_data(&mut amms, block_id, self.provider.clone())
+                    .await?;
+                new_amms.extend(amms);
+            }
+        }
+
+        Ok(new_amms)
+    }
+}
+
+/// Returns whether the sync loop cannot recover from `err` by retrying: the reorg is deeper than
+/// the state change cache, or the transport failed in a way that is not transient, such as a
+/// missing endpoint or responses it cannot decode.
+pub fn is_fatal(err: &StateSpaceError) -> bool {
+    let provider_error = match err {
+        StateSpaceError::ReorgBeyondCache => return true,
+        StateSpaceError::ProviderError(err)
+        | StateSpaceError::AMMError(AMMError::ProviderError(err)) => err,
+        StateSpaceError::AMMError(AMMError::CallError { source, .. }) => source.as_ref(),
+        _ => return false,
+    };
+
+    matches!(provider_error, ProviderError::Other(_)) && !is_transient(provider_error)
+}
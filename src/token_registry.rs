@@ -0,0 +1,322 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use starknet::{
+    core::types::{BlockId, Felt},
+    providers::{Provider, ProviderError},
+};
+
+use crate::{
+    amm::pool::AMM,
+    errors::AMMError,
+    utils::{batch_call, function_call, parse_u128, read_integer},
+};
+
+/// Metadata of an ERC20 token.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    pub decimals: u8,
+    /// Empty when the token does not implement `symbol`.
+    pub symbol: String,
+    /// Empty when the token does not implement `name`.
+    pub name: String,
+}
+
+/// Cache of the metadata of every token traded by the pools, so that a token shared by many
+/// pools is only fetched once.
+///
+/// Clones share the same cache, so one registry can be handed to every factory. Pools only
+/// keep a copy of their tokens' decimals for pricing, which is left out when they are
+/// serialized and restored from the registry with `populate_decimals`.
+#[derive(Debug, Clone, Default)]
+pub struct TokenRegistry {
+    tokens: Arc<RwLock<HashMap<Felt, TokenMetadata>>>,
+}
+
+impl TokenRegistry {
+    pub fn new() -> TokenRegistry {
+        TokenRegistry::default()
+    }
+
+    /// Returns the cached metadata of `token`.
+    pub fn get(&self, token: Felt) -> Option<TokenMetadata> {
+        self.read().get(&token).cloned()
+    }
+
+    /// Returns the cached decimals of `token`.
+    pub fn decimals(&self, token: Felt) -> Option<u8> {
+        self.read().get(&token).map(|metadata| metadata.decimals)
+    }
+
+    pub fn insert(&self, token: Felt, metadata: TokenMetadata) {
+        self.write().insert(token, metadata);
+    }
+
+    pub fn contains(&self, token: Felt) -> bool {
+        self.read().contains_key(&token)
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Returns a copy of every cached token.
+    pub fn tokens(&self) -> HashMap<Felt, TokenMetadata> {
+        self.read().clone()
+    }
+
+    /// Fetches the metadata of every token in `tokens` that is not cached yet, through batched
+    /// calls of at most `batch_size` requests.
+    ///
+    /// `decimals` is required. `symbol` and `name` are optional in the ERC20 standard and left
+    /// empty when a token does not implement them.
+    pub async fn fetch<P>(
+        &self,
+        tokens: impl IntoIterator<Item = Felt>,
+        block_id: BlockId,
+        batch_size: usize,
+        provider: Arc<P>,
+    ) -> Result<(), AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        let mut missing = tokens
+            .into_iter()
+            .filter(|token| !self.contains(*token))
+            .collect::<Vec<_>>();
+        missing.sort();
+        missing.dedup();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let calls = missing
+            .iter()
+            .map(|token| function_call(*token, "decimals", vec![]))
+            .collect::<Result<Vec<_>, _>>()?;
+        let decimals = batch_call(provider.clone(), calls, block_id, batch_size).await?;
+
+        let strings =
+            fetch_strings(&missing, ["symbol", "name"], block_id, batch_size, provider).await?;
+
+        // Every token is decoded before any is cached, so a failure leaves the cache untouched.
+        let mut metadata = Vec::with_capacity(missing.len());
+        for ((token, decimals), [symbol, name]) in missing.into_iter().zip(decimals).zip(strings) {
+            let decimals = read_integer(&decimals, token, "decimals", block_id)?;
+            metadata.push((
+                token,
+                TokenMetadata {
+                    decimals,
+                    symbol,
+                    name,
+                },
+            ));
+        }
+
+        let mut cache = self.write();
+        cache.extend(metadata);
+        tracing::debug!(tokens = cache.len(), "Updated token registry");
+
+        Ok(())
+    }
+
+    /// Returns the decimals of every token in `tokens`, fetching the ones not cached yet.
+    pub async fn get_decimals<P>(
+        &self,
+        tokens: impl IntoIterator<Item = Felt>,
+        block_id: BlockId,
+        batch_size: usize,
+        provider: Arc<P>,
+    ) -> Result<HashMap<Felt, u8>, AMMError>
+    where
+        P: Provider + Send + Sync,
+    {
+        let tokens = tokens.into_iter().collect::<Vec<_>>();
+        self.fetch(tokens.iter().copied(), block_id, batch_size, provider)
+            .await?;
+
+        let cache = self.read();
+        tokens
+            .into_iter()
+            .map(|token| {
+                cache
+                    .get(&token)
+                    .map(|metadata| (token, metadata.decimals))
+                    .ok_or(AMMError::PoolDataError)
+            })
+            .collect()
+    }
+
+    /// Sets the decimals of the tokens of every pool in `amms` from the registry, leaving
+    /// tokens it does not know untouched.
+    pub fn populate_decimals(&self, amms: &mut [AMM]) {
+        let cache = self.read();
+        let set = |token: Felt, decimals: &mut u8| {
+            if let Some(metadata) = cache.get(&token) {
+                *decimals = metadata.decimals;
+            }
+        };
+
+        for amm in amms {
+            match amm {
+                AMM::JediswapPool(pool) => {
+                    set(pool.token_a, &mut pool.token_a_decimals);
+                    set(pool.token_b, &mut pool.token_b_decimals);
+                }
+                AMM::TenkSwapPool(pool) => {
+                    set(pool.token_a, &mut pool.token_a_decimals);
+                    set(pool.token_b, &mut pool.token_b_decimals);
+                }
+                AMM::JediswapV2Pool(pool) => {
+                    set(pool.token_a, &mut pool.token_a_decimals);
+                    set(pool.token_b, &mut pool.token_b_decimals);
+                }
+                AMM::EkuboPool(pool) => {
+                    set(pool.key.token0, &mut pool.token0_decimals);
+                    set(pool.key.token1, &mut pool.token1_decimals);
+                }
+                AMM::MySwapPool(pool) => {
+                    set(pool.token_a, &mut pool.token_a_decimals);
+                    set(pool.token_b, &mut pool.token_b_decimals);
+                }
+                AMM::SithSwapPool(pool) => {
+                    set(pool.token_a, &mut pool.token_a_decimals);
+                    set(pool.token_b, &mut pool.token_b_decimals);
+                }
+            }
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<Felt, TokenMetadata>> {
+        // The cache is only written whole entries at a time, so it stays usable after a panic.
+        self.tokens.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<Felt, TokenMetadata>> {
+        self.tokens.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Serialize for TokenRegistry {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.read().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TokenRegistry {
+    fn deserialize<D>(deserializer: D) -> Result<TokenRegistry, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(TokenRegistry {
+            tokens: Arc::new(RwLock::new(HashMap::deserialize(deserializer)?)),
+        })
+    }
+}
+
+/// Decodes a string returned by a token, either a short string packed in a single felt, as
+/// returned by Cairo 0 tokens, or a serialized `ByteArray`, as returned by recent Cairo 1 ones.
+pub fn parse_token_string(felts: &[Felt]) -> Result<String, AMMError> {
+    let bytes = match felts {
+        [short_string] => short_string_bytes(*short_string),
+        // ByteArray { data: Array<bytes31>, pending_word: felt252, pending_word_len: usize }
+        [data_len, rest @ ..] => {
+            let data_len =
+                usize::try_from(parse_u128(*data_len)?).map_err(|_| AMMError::PoolDataError)?;
+            let [data @ .., pending_word, pending_word_len] = rest else {
+                return Err(AMMError::PoolDataError);
+            };
+            let pending_word_len = parse_u128(*pending_word_len)?;
+            if data.len() != data_len || pending_word_len > 30 {
+                return Err(AMMError::PoolDataError);
+            }
+
+            let mut bytes = data
+                .iter()
+                .flat_map(|word| word.to_bytes_be()[1..].to_vec())
+                .collect::<Vec<_>>();
+            bytes.extend(&pending_word.to_bytes_be()[32 - pending_word_len as usize..]);
+            bytes
+        }
+        [] => return Err(AMMError::PoolDataError),
+    };
+
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Returns the bytes of a short string, dropping the leading zero padding.
+fn short_string_bytes(value: Felt) -> Vec<u8> {
+    value
+        .to_bytes_be()
+        .into_iter()
+        .skip_while(|byte| *byte == 0)
+        .collect()
+}
+
+/// Calls every method of `methods` on every token, decoding the results as strings.
+///
+/// A batch fails as a whole when one token does not implement a method, in which case only the
+/// calls of that batch are retried on their own, leaving the ones the contract rejects empty.
+/// Any other error is returned.
+async fn fetch_strings<P, const N: usize>(
+    tokens: &[Felt],
+    methods: [&str; N],
+    block_id: BlockId,
+    batch_size: usize,
+    provider: Arc<P>,
+) -> Result<Vec<[String; N]>, AMMError>
+where
+    P: Provider + Send + Sync,
+{
+    let calls = tokens
+        .iter()
+        .flat_map(|token| methods.map(|method| function_call(*token, method, vec![])))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let batch_size = batch_size.max(1);
+    let mut results = Vec::with_capacity(calls.len());
+    for (chunk_idx, chunk) in calls.chunks(batch_size).enumerate() {
+        match batch_call(provider.clone(), chunk.to_vec(), block_id, batch_size).await {
+            Ok(chunk_results) => results.extend(chunk_results.into_iter().map(Some)),
+            Err(AMMError::ProviderError(ProviderError::StarknetError(_))) => {
+                for (idx, call) in chunk.iter().enumerate() {
+                    match provider.call(call, block_id).await {
+                        Ok(result) => results.push(Some(result)),
+                        Err(ProviderError::StarknetError(_)) => results.push(None),
+                        Err(err) => {
+                            return Err(AMMError::CallError {
+                                address: call.contract_address,
+                                method: methods[(chunk_idx * batch_size + idx) % N].to_string(),
+                                block_id,
+                                source: Box::new(err),
+                            })
+                        }
+                    }
+                }
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(results
+        .chunks(N)
+        .map(|results| {
+            std::array::from_fn(|idx| {
+                results[idx]
+                    .as_deref()
+                    .and_then(|result| parse_token_string(result).ok())
+                    .unwrap_or_default()
+            })
+        })
+        .collect())
+}
@@ -1,36 +1,232 @@
-use color_eyre::{eyre::eyre, Result};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
 use starknet::{
     accounts::SingleOwnerAccount,
     core::{
-        types::{BlockId, BlockTag, Felt, FunctionCall},
+        types::{requests::CallRequest, BlockId, EmittedEvent, EventFilter, Felt, FunctionCall},
         utils::get_selector_from_name,
     },
-    providers::{jsonrpc::HttpTransport, JsonRpcClient, Provider},
+    providers::{
+        jsonrpc::HttpTransport, JsonRpcClient, Provider, ProviderError, ProviderRequestData,
+        ProviderResponseData,
+    },
     signers::LocalWallet,
 };
 use std::sync::Arc;
 
+use crate::errors::AMMError;
+
 pub type LocalWalletSignerMiddleware =
     Arc<SingleOwnerAccount<Arc<JsonRpcClient<HttpTransport>>, LocalWallet>>;
 
+/// Calls `method` of the contract at `address` against the state at `block_id`.
+///
+/// Failed calls are reported with the contract, method and block they were made for.
 pub async fn call_contract<P>(
     provider: Arc<P>,
     address: Felt,
     method: &str,
     calldata: Vec<Felt>,
-) -> Result<Vec<Felt>>
+    block_id: BlockId,
+) -> Result<Vec<Felt>, AMMError>
 where
     P: Provider + Sync + Send,
 {
+    let call = function_call(address, method, calldata)?;
+    provider
+        .call(call, block_id)
+        .await
+        .map_err(|source| AMMError::CallError {
+            address,
+            method: method.to_string(),
+            block_id,
+            source: Box::new(source),
+        })
+}
+
+/// Returns the first `N` felts of the result of `method` on `address`, or an
+/// `UnexpectedCallResult` error when it is shorter.
+pub fn read_result<const N: usize>(
+    result: &[Felt],
+    address: Felt,
+    method: &str,
+    block_id: BlockId,
+) -> Result<[Felt; N], AMMError> {
+    result
+        .get(..N)
+        .and_then(|felts| felts.try_into().ok())
+        .ok_or_else(|| AMMError::UnexpectedCallResult {
+            address,
+            method: method.to_string(),
+            block_id,
+        })
+}
+
+/// Decodes the first felt of the result of `method` on `address` as an integer, or returns an
+/// `UnexpectedCallResult` error when it is missing or does not fit in `T`.
+pub fn read_integer<T: TryFrom<u128>>(
+    result: &[Felt],
+    address: Felt,
+    method: &str,
+    block_id: BlockId,
+) -> Result<T, AMMError> {
+    result
+        .first()
+        .and_then(|value| value.to_biguint().to_u128())
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| AMMError::UnexpectedCallResult {
+            address,
+            method: method.to_string(),
+            block_id,
+        })
+}
+
+/// Fetches every event matching `filter`, following continuation tokens `chunk_size` events at a time.
+pub async fn get_events<P>(
+    provider: Arc<P>,
+    filter: EventFilter,
+    chunk_size: u64,
+) -> Result<Vec<EmittedEvent>, ProviderError>
+where
+    P: Provider + Sync + Send,
+{
+    let mut events = vec![];
+    let mut continuation_token = None;
+
+    loop {
+        let page = provider
+            .get_events(filter.clone(), continuation_token, chunk_size)
+            .await?;
+        events.extend(page.events);
+
+        match page.continuation_token {
+            Some(token) => continuation_token = Some(token),
+            None => break,
+        }
+    }
+
+    Ok(events)
+}
+
+/// Default number of calls sent in a single JSON-RPC batch request.
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// Serde default for the `batch_size` field of factories.
+pub(crate) fn default_batch_size() -> usize {
+    DEFAULT_BATCH_SIZE
+}
+
+/// Builds a `FunctionCall` to `method` of the contract at `address`.
+pub fn function_call(
+    address: Felt,
+    method: &str,
+    calldata: Vec<Felt>,
+) -> Result<FunctionCall, AMMError> {
     let entry_point_selector = get_selector_from_name(method)
-        .map_err(|e| eyre!("Invalid selector for {}: {}", method, e))?;
-    let function_call = FunctionCall {
+        .map_err(|_| AMMError::InvalidMethodName(method.to_string()))?;
+
+    Ok(FunctionCall {
         contract_address: address,
         entry_point_selector,
         calldata,
-    };
-    provider
-        .call(function_call, BlockId::Tag(BlockTag::Latest))
-        .await
-        .map_err(|e| eyre!("Provider error: {}", e))
+    })
+}
+
+/// Executes `calls` at `block_id` through JSON-RPC batch requests of at most `batch_size` calls.
+///
+/// Results are returned in the order of `calls`. As a `FunctionCall` only holds the selector of
+/// its method, unexpected responses are reported with the selector as the method.
+pub async fn batch_call<P>(
+    provider: Arc<P>,
+    calls: Vec<FunctionCall>,
+    block_id: BlockId,
+    batch_size: usize,
+) -> Result<Vec<Vec<Felt>>, AMMError>
+where
+    P: Provider + Sync + Send,
+{
+    let mut results = Vec::with_capacity(calls.len());
+
+    for chunk in calls.chunks(batch_size.max(1)) {
+        let requests = chunk
+            .iter()
+            .map(|call| {
+                ProviderRequestData::Call(CallRequest {
+                    request: call.clone(),
+                    block_id,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let responses = provider.batch_requests(requests).await?;
+        if responses.len() < chunk.len() {
+            // Report the first call left without a response.
+            return Err(unexpected_call_result(&chunk[responses.len()], block_id));
+        }
+
+        for (call, response) in chunk.iter().zip(responses) {
+            match response {
+                ProviderResponseData::Call(result) => results.push(result),
+                _ => return Err(unexpected_call_result(call, block_id)),
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+fn unexpected_call_result(call: &FunctionCall, block_id: BlockId) -> AMMError {
+    AMMError::UnexpectedCallResult {
+        address: call.contract_address,
+        method: format!("{:#x}", call.entry_point_selector),
+        block_id,
+    }
+}
+
+/// Reads `token0` and `token1` of every pair in `pairs` through batched calls.
+pub async fn batch_get_pair_tokens<P>(
+    provider: Arc<P>,
+    pairs: &[Felt],
+    block_id: BlockId,
+    batch_size: usize,
+) -> Result<Vec<(Felt, Felt)>, AMMError>
+where
+    P: Provider + Sync + Send,
+{
+    let calls = pairs
+        .iter()
+        .flat_map(|pair| {
+            [
+                function_call(*pair, "token0", vec![]),
+                function_call(*pair, "token1", vec![]),
+            ]
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let results = batch_call(provider, calls, block_id, batch_size).await?;
+
+    pairs
+        .iter()
+        .zip(results.chunks(2))
+        .map(|(pair, tokens)| {
+            let [token_a] = read_result(&tokens[0], *pair, "token0", block_id)?;
+            let [token_b] = read_result(&tokens[1], *pair, "token1", block_id)?;
+            Ok((token_a, token_b))
+        })
+        .collect()
+}
+
+/// Decodes a Cairo `u256` from its `low` and `high` felts.
+pub fn parse_u256(low: Felt, high: Felt) -> BigUint {
+    (high.to_biguint() << 128) + low.to_biguint()
+}
+
+/// Encodes `value` as the `low` and `high` felts of a Cairo `u256`.
+pub fn encode_u256(value: &BigUint) -> [Felt; 2] {
+    let mask = (BigUint::from(1u32) << 128) - 1u32;
+    [Felt::from(value & &mask), Felt::from(value >> 128)]
+}
+
+/// Decodes a `u128` carried by an event, use `read_integer` for call results.
+pub fn parse_u128(value: Felt) -> Result<u128, AMMError> {
+    value.to_biguint().to_u128().ok_or(AMMError::PoolDataError)
 }
@@ -0,0 +1,373 @@
+use std::{collections::HashMap, sync::Arc};
+
+use mev_engine::{
+    amm::{
+        jediswap::pool::JediswapPool,
+        pool::{AutomatedMarketMaker, AMM},
+        sithswap::pool::SithSwapPool,
+    },
+    arbitrage::{
+        graph::{Cycle, Hop, TokenGraph},
+        negative_cycles::find_negative_cycles,
+        profit::{HopFeeModel, ProfitEvaluator},
+        simulate_cycle,
+        sizing::optimal_trade_size,
+        ArbitrageScanner, Opportunity, StartToken,
+    },
+    errors::ArbitrageError,
+};
+use starknet::{
+    core::types::Felt,
+    providers::{jsonrpc::HttpTransport, JsonRpcClient, Url},
+};
+
+const E18: u128 = 1_000_000_000_000_000_000;
+
+fn provider() -> Arc<JsonRpcClient<HttpTransport>> {
+    Arc::new(JsonRpcClient::new(HttpTransport::new(
+        Url::parse("http://localhost:5050").unwrap(),
+    )))
+}
+
+fn token(id: u32) -> Felt {
+    Felt::from(0xa0 + id)
+}
+
+fn pool(address: u32, token_a: Felt, token_b: Felt, reserve_a: u128, reserve_b: u128) -> AMM {
+    AMM::JediswapPool(JediswapPool::new(
+        Felt::from(address),
+        token_a,
+        token_b,
+        18,
+        18,
+        Felt::from(reserve_a),
+        Felt::from(reserve_b),
+        3000,
+    ))
+}
+
+fn state(amms: Vec<AMM>) -> HashMap<Felt, AMM> {
+    amms.into_iter().map(|amm| (amm.address(), amm)).collect()
+}
+
+#[test]
+fn token_graph_enumerates_two_and_three_hop_cycles() {
+    // Two pools for (0, 1), and a triangle through token 2.
+    let amms = [
+        pool(1, token(0), token(1), E18, E18),
+        pool(2, token(0), token(1), E18, E18),
+        pool(3, token(1), token(2), E18, E18),
+        pool(4, token(2), token(0), E18, E18),
+    ];
+    let graph = TokenGraph::new(&amms);
+
+    assert_eq!(graph.hops_from(token(0)).len(), 3);
+
+    let cycles = graph.cycles(token(0), 3);
+    let two_hops = cycles.iter().filter(|cycle| cycle.hops.len() == 2).count();
+    let three_hops = cycles.iter().filter(|cycle| cycle.hops.len() == 3).count();
+    // 1 -> 2 and 2 -> 1 through the parallel pools, and the triangle in both directions
+    // entered through either parallel pool.
+    assert_eq!(two_hops, 2);
+    assert_eq!(three_hops, 4);
+
+    for cycle in &cycles {
+        assert_eq!(cycle.start_token(), token(0));
+        assert_eq!(cycle.hops.last().unwrap().token_out, token(0));
+        for pair in cycle.hops.windows(2) {
+            assert_eq!(pair[0].token_out, pair[1].token_in);
+        }
+    }
+
+    assert_eq!(graph.cycles(token(0), 2).len(), 2);
+}
+
+#[tokio::test]
+async fn scanner_ranks_profitable_cycles() {
+    // Pool 1 prices token 1 at 1 token 0, pool 2 at 2 and pool 3 at 1.5.
+    let amms = state(vec![
+        pool(1, token(0), token(1), 1000 * E18, 1000 * E18),
+        pool(2, token(0), token(1), 1000 * E18, 500 * E18),
+        pool(3, token(0), token(1), 1000 * E18, 666 * E18),
+    ]);
+    let scanner = ArbitrageScanner::new(vec![StartToken {
+        token: token(0),
+        amount_in: Felt::from(E18),
+    }]);
+
+    let opportunities = scanner.scan(&amms, provider()).await.unwrap();
+    assert!(!opportunities.is_empty());
+
+    // Buying token 1 where it is cheapest and selling it where it is dearest comes first.
+    let best = &opportunities[0];
+    assert_eq!(
+        best.cycle.hops[0],
+        Hop {
+            pool: Felt::from(1u32),
+            token_in: token(0),
+            token_out: token(1),
+        }
+    );
+    assert_eq!(best.cycle.hops[1].pool, Felt::from(2u32));
+    for pair in opportunities.windows(2) {
+        assert!(pair[0].profit() >= pair[1].profit());
+    }
+    assert!(opportunities.iter().all(
+        |opportunity| opportunity.amount_out.to_biguint() > opportunity.amount_in.to_biguint()
+    ));
+
+    let changed = scanner
+        .scan_changed(&amms, &[Felt::from(3u32)], provider())
+        .await
+        .unwrap();
+    assert!(changed.iter().all(|opportunity| opportunity
+        .cycle
+        .pools()
+        .any(|pool| pool == Felt::from(3u32))));
+    assert!(changed.len() < opportunities.len());
+}
+
+/// Returns the cycle that buys token 1 in pool `buy` and sells it back in pool `sell`.
+fn round_trip(amms: &HashMap<Felt, AMM>, buy: u32, sell: u32) -> Cycle {
+    TokenGraph::new(amms.values())
+        .cycles(token(0), 2)
+        .into_iter()
+        .find(|cycle| cycle.pools().collect::<Vec<_>>() == [Felt::from(buy), Felt::from(sell)])
+        .unwrap()
+}
+
+async fn profit_at(cycle: &Cycle, amms: &HashMap<Felt, AMM>, amount_in: u128) -> i128 {
+    let amount_out = simulate_cycle(cycle, amms, Felt::from(amount_in), provider())
+        .await
+        .unwrap();
+    u128::try_from(amount_out.to_biguint()).unwrap() as i128 - amount_in as i128
+}
+
+#[tokio::test]
+async fn constant_product_cycles_are_sized_in_closed_form() {
+    let amms = state(vec![
+        pool(1, token(0), token(1), 1000 * E18, 1000 * E18),
+        pool(2, token(0), token(1), 1000 * E18, 500 * E18),
+    ]);
+    let cycle = round_trip(&amms, 1, 2);
+
+    let best = optimal_trade_size(&cycle, &amms, Felt::from(1000 * E18))
+        .unwrap()
+        .unwrap();
+    let amount_in = u128::try_from(best.amount_in.to_biguint()).unwrap();
+    let profit = profit_at(&cycle, &amms, amount_in).await;
+    assert_eq!(
+        profit as u128,
+        u128::try_from(best.profit().unwrap()).unwrap()
+    );
+
+    for amount in [
+        amount_in / 2,
+        amount_in * 99 / 100,
+        amount_in * 101 / 100,
+        amount_in * 2,
+    ] {
+        assert!(profit_at(&cycle, &amms, amount).await <= profit);
+    }
+
+    // The optimum is capped to the available amount.
+    let capped = optimal_trade_size(&cycle, &amms, Felt::from(E18))
+        .unwrap()
+        .unwrap();
+    assert_eq!(capped.amount_in, Felt::from(E18));
+
+    // Trading the other way round only loses.
+    let reverse = round_trip(&amms, 2, 1);
+    assert!(optimal_trade_size(&reverse, &amms, Felt::from(1000 * E18))
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn other_curves_are_sized_by_search() {
+    // Token 1 is worth half a token 0 in the Jediswap pool and about one on the stable curve.
+    let amms = state(vec![
+        pool(1, token(0), token(1), 1000 * E18, 2000 * E18),
+        AMM::SithSwapPool(SithSwapPool::new(
+            Felt::from(2u32),
+            token(0),
+            token(1),
+            18,
+            18,
+            Felt::from(1000 * E18),
+            Felt::from(1000 * E18),
+            true,
+            5,
+        )),
+    ]);
+    let cycle = round_trip(&amms, 1, 2);
+
+    let best = optimal_trade_size(&cycle, &amms, Felt::from(1000 * E18))
+        .unwrap()
+        .unwrap();
+    let amount_in = u128::try_from(best.amount_in.to_biguint()).unwrap();
+    let profit = profit_at(&cycle, &amms, amount_in).await;
+    assert!(profit > 0);
+
+    for amount in [
+        amount_in / 2,
+        amount_in * 99 / 100,
+        amount_in * 101 / 100,
+        amount_in * 2,
+    ] {
+        assert!(profit_at(&cycle, &amms, amount).await <= profit);
+    }
+}
+
+#[tokio::test]
+async fn bellman_ford_finds_mispriced_triangle() {
+    // Token 0 buys token 1, which buys token 2, which buys back twice as much token 0.
+    let amms = state(vec![
+        pool(1, token(0), token(1), 1000 * E18, 1000 * E18),
+        pool(2, token(1), token(2), 1000 * E18, 1000 * E18),
+        pool(3, token(2), token(0), 1000 * E18, 2000 * E18),
+        pool(4, token(0), token(3), 1000 * E18, 1000 * E18),
+    ]);
+
+    let cycles = find_negative_cycles(&amms);
+    assert_eq!(cycles.len(), 1);
+
+    let cycle = cycles[0].rotated_to(token(0)).unwrap();
+    assert_eq!(
+        cycle.pools().collect::<Vec<_>>(),
+        [Felt::from(1u32), Felt::from(2u32), Felt::from(3u32)]
+    );
+    assert_eq!(cycle.hops[0].token_out, token(1));
+    for pair in cycle.hops.windows(2) {
+        assert_eq!(pair[0].token_out, pair[1].token_in);
+    }
+    assert!(cycle.rotated_to(token(3)).is_none());
+
+    let scanner = ArbitrageScanner::new(vec![StartToken {
+        token: token(0),
+        amount_in: Felt::from(E18),
+    }]);
+    let opportunities = scanner
+        .scan_negative_cycles(&amms, provider())
+        .await
+        .unwrap();
+    assert_eq!(opportunities.len(), 1);
+    assert_eq!(opportunities[0].cycle, cycle);
+
+    // Fairly priced pools only lose their fees around any loop.
+    let fair = state(vec![
+        pool(1, token(0), token(1), 1000 * E18, 1000 * E18),
+        pool(2, token(1), token(2), 1000 * E18, 1000 * E18),
+        pool(3, token(2), token(0), 1000 * E18, 1000 * E18),
+        pool(4, token(0), token(1), 500 * E18, 500 * E18),
+    ]);
+    assert!(find_negative_cycles(&fair).is_empty());
+}
+
+#[test]
+fn profit_evaluator_nets_fees_in_numeraire() {
+    // Token 2 is a 6 decimals fee token, worth half a token 0.
+    let amms = state(vec![
+        pool(1, token(0), token(1), 1000 * E18, 1000 * E18),
+        pool(2, token(0), token(1), 1000 * E18, 500 * E18),
+        AMM::JediswapPool(JediswapPool::new(
+            Felt::from(3u32),
+            token(0),
+            token(2),
+            18,
+            6,
+            Felt::from(1000 * E18),
+            Felt::from(500_000_000u128),
+            3000,
+        )),
+    ]);
+    let opportunity = Opportunity {
+        cycle: round_trip(&amms, 1, 2),
+        amount_in: Felt::from(E18),
+        amount_out: Felt::from(E18 + E18 / 10),
+    };
+
+    let mut fee_model = HopFeeModel::new(Felt::from(10_000u32), Felt::from(5_000u32));
+    let evaluator = ProfitEvaluator::new(token(2), token(2), fee_model.clone());
+
+    let evaluated = evaluator
+        .evaluate(opportunity.clone(), &amms)
+        .unwrap()
+        .unwrap();
+    assert_eq!(evaluated.gross_profit, 50_000u32.into());
+    assert_eq!(evaluated.fee, 20_000u32.into());
+    assert_eq!(evaluated.net_profit(), Some(30_000u32.into()));
+
+    // Fees are converted too when the numeraire is not the fee token.
+    let in_token_0 = ProfitEvaluator::new(token(0), token(2), fee_model.clone());
+    let evaluated = in_token_0
+        .evaluate(opportunity.clone(), &amms)
+        .unwrap()
+        .unwrap();
+    assert_eq!(evaluated.gross_profit, (E18 / 10).into());
+    assert_eq!(evaluated.fee, (E18 / 25).into());
+
+    let demanding = evaluator.clone().with_min_net_profit(Felt::from(30_001u32));
+    assert!(demanding
+        .evaluate(opportunity.clone(), &amms)
+        .unwrap()
+        .is_none());
+    assert!(evaluator
+        .evaluate_with_fee(opportunity.clone(), Felt::from(50_000u32), &amms)
+        .unwrap()
+        .is_none());
+
+    let losing = Opportunity {
+        amount_out: Felt::from(E18 - 1),
+        ..opportunity.clone()
+    };
+    assert_eq!(losing.profit(), None);
+    assert!(evaluator.evaluate(losing, &amms).unwrap().is_none());
+
+    fee_model.calibrate(2, Felt::from(31_000u32));
+    assert_eq!(fee_model.fee_per_hop, 10_500u32.into());
+
+    let unpriced = ProfitEvaluator::new(token(3), token(3), fee_model);
+    assert!(matches!(
+        unpriced.evaluate(opportunity, &amms),
+        Err(ArbitrageError::NoPricePath(unpriced)) if unpriced == token(0)
+    ));
+}
+
+#[test]
+fn profit_evaluator_converts_through_the_deepest_pool() {
+    // Token 2 is worth half a token 0 in the deep pool, and a tenth in the dust pool.
+    let deep = pool(5, token(0), token(2), 1000 * E18, 2000 * E18);
+    let dust = pool(4, token(0), token(2), 1_000_000, 10_000_000);
+    let amms = state(vec![
+        pool(1, token(0), token(1), 1000 * E18, 1000 * E18),
+        pool(2, token(0), token(1), 1000 * E18, 500 * E18),
+        deep.clone(),
+        dust,
+    ]);
+    let opportunity = Opportunity {
+        cycle: round_trip(&amms, 1, 2),
+        amount_in: Felt::from(E18),
+        amount_out: Felt::from(E18 + E18 / 10),
+    };
+    let evaluator =
+        ProfitEvaluator::new(token(2), token(2), HopFeeModel::new(Felt::ZERO, Felt::ZERO));
+
+    let evaluated = evaluator
+        .evaluate(opportunity.clone(), &amms)
+        .unwrap()
+        .unwrap();
+    assert_eq!(evaluated.gross_profit, (E18 / 5).into());
+
+    // Equally deep pools are picked by address rather than by the order of the map.
+    let mut twin = deep;
+    if let AMM::JediswapPool(pool) = &mut twin {
+        pool.pool_address = Felt::from(6u32);
+        pool.reserve_a = Felt::from(500 * E18);
+    }
+    let mut amms = amms;
+    amms.insert(Felt::from(6u32), twin);
+
+    let evaluated = evaluator.evaluate(opportunity, &amms).unwrap().unwrap();
+    assert_eq!(evaluated.gross_profit, (E18 / 5).into());
+}
@@ -0,0 +1,249 @@
+use std::sync::Arc;
+
+use mev_engine::{
+    amm::{
+        ekubo::{
+            get_data::{POSITION_UPDATED_EVENT, SWAPPED_EVENT},
+            math::{
+                max_sqrt_ratio, min_sqrt_ratio, sqrt_ratio_to_tick, tick_to_sqrt_ratio, MAX_TICK,
+                MIN_TICK,
+            },
+            pool::{EkuboPool, PoolKey},
+        },
+        pool::AutomatedMarketMaker,
+    },
+    errors::{AMMError, ArithmeticError, EventLogError},
+};
+use num_bigint::BigUint;
+use starknet::{
+    core::types::{EmittedEvent, Felt},
+    providers::{jsonrpc::HttpTransport, JsonRpcClient, Url},
+};
+
+const E18: u128 = 1_000_000_000_000_000_000;
+
+fn big(value: &str) -> BigUint {
+    BigUint::parse_bytes(value.as_bytes(), 10).unwrap()
+}
+
+fn one() -> BigUint {
+    BigUint::from(1u32) << 128
+}
+
+fn token0() -> Felt {
+    Felt::from(0xaau32)
+}
+
+fn token1() -> Felt {
+    Felt::from(0xbbu32)
+}
+
+// Price 1:1, 0.3% fee, with 1e18 liquidity on [-2000000, 2000000] and 2e18 on
+// [-100000, 100000], which is roughly a 10% price move either way..
+fn pool() -> EkuboPool {
+    let key = PoolKey {
+        token0: token0(),
+        token1: token1(),
+        fee: 1020847100762815390390123822295304634,
+        tick_spacing: 1000,
+        extension: Felt::ZERO,
+    };
+    let mut pool = EkuboPool::new(Felt::ONE, key, 18, 18, one(), 0, 3 * E18);
+    pool.update_position(-2_000_000, 2_000_000, E18 as i128);
+    pool.update_position(-100_000, 100_000, 2 * E18 as i128);
+    pool
+}
+
+#[test]
+fn tick_math_matches_reference_values() {
+    assert_eq!(tick_to_sqrt_ratio(0).unwrap(), one());
+    assert_eq!(tick_to_sqrt_ratio(MIN_TICK).unwrap(), min_sqrt_ratio());
+    assert_eq!(tick_to_sqrt_ratio(MAX_TICK).unwrap(), max_sqrt_ratio());
+    assert_eq!(
+        max_sqrt_ratio(),
+        big("6277100250585753475930931601400621808602321654880405518632")
+    );
+    assert!(tick_to_sqrt_ratio(MAX_TICK + 1).is_err());
+
+    assert_eq!(sqrt_ratio_to_tick(&min_sqrt_ratio()).unwrap(), MIN_TICK);
+    for tick in [-1_000_000, -1, 0, 1, 1_000_000, MAX_TICK - 1] {
+        let sqrt_ratio = tick_to_sqrt_ratio(tick).unwrap();
+        assert_eq!(sqrt_ratio_to_tick(&sqrt_ratio).unwrap(), tick);
+        assert_eq!(sqrt_ratio_to_tick(&(sqrt_ratio - 1u32)).unwrap(), tick - 1);
+    }
+}
+
+#[test]
+fn simulate_swap_mut_crosses_initialized_ticks() {
+    let mut pool = pool();
+
+    // Enough token0 to push the price below the inner range.
+    let amount_in = Felt::from(400_000_000_000_000_000u128);
+    let amount_out = pool
+        .simulate_swap_mut(token0(), token1(), amount_in)
+        .unwrap();
+
+    assert!(amount_out < amount_in);
+    assert!(pool.tick < -100_000);
+    assert_eq!(pool.liquidity, E18);
+    assert_eq!(pool.tick, sqrt_ratio_to_tick(&pool.sqrt_ratio).unwrap());
+
+    // Swapping back crosses the inner range again and restores its liquidity.
+    pool.simulate_swap_mut(token1(), token0(), amount_out)
+        .unwrap();
+    assert!(pool.tick > -100_000);
+    assert_eq!(pool.liquidity, 3 * E18);
+}
+
+#[test]
+fn simulate_swap_exact_out_inverts_exact_in() {
+    let mut pool = pool();
+    let amount_in = Felt::from(10_000_000_000_000_000u128);
+
+    let amount_out = pool
+        .clone()
+        .simulate_swap_mut(token1(), token0(), amount_in)
+        .unwrap();
+    let required_in = pool
+        .simulate_swap_exact_out(token1(), token0(), amount_out)
+        .unwrap();
+
+    // Rounding always favours the pool, by at most a few units.
+    assert!(required_in <= amount_in);
+    assert!(amount_in.to_biguint() - required_in.to_biguint() <= BigUint::from(2u32));
+
+    let required_in_mut = pool
+        .simulate_swap_exact_out_mut(token1(), token0(), amount_out)
+        .unwrap();
+    assert_eq!(required_in_mut, required_in);
+    assert!(pool.sqrt_ratio > one());
+}
+
+#[tokio::test]
+async fn rejects_tokens_outside_the_pool() {
+    let mut pool = pool();
+    let provider = Arc::new(JsonRpcClient::new(HttpTransport::new(
+        Url::parse("http://localhost:5050").unwrap(),
+    )));
+
+    assert!(matches!(
+        pool.calculate_price(Felt::THREE, token1()),
+        Err(AMMError::ArithmeticError(
+            ArithmeticError::BaseTokenDoesNotExist
+        ))
+    ));
+    assert!(pool
+        .simulate_swap_mut(token0(), Felt::THREE, Felt::ONE)
+        .is_err());
+    assert!(matches!(
+        pool.simulate_swap(Felt::THREE, Felt::ONE, provider).await,
+        Err(AMMError::ArithmeticError(
+            ArithmeticError::BaseTokenDoesNotExist
+        ))
+    ));
+}
+
+#[test]
+fn calculate_price_from_sqrt_ratio() {
+    let mut pool = pool();
+    assert_eq!(pool.calculate_price(token0(), token1()).unwrap(), 1.0);
+
+    pool.sqrt_ratio = one() * 2u32;
+    pool.token1_decimals = 6;
+    assert_eq!(pool.calculate_price(token0(), token1()).unwrap(), 4e12);
+    assert_eq!(pool.calculate_price(token1(), token0()).unwrap(), 0.25e-12);
+}
+
+fn event(selector: Felt, data: Vec<Felt>) -> EmittedEvent {
+    EmittedEvent {
+        from_address: Felt::ONE,
+        keys: vec![selector],
+        data,
+        block_hash: None,
+        block_number: Some(1),
+        transaction_hash: Felt::ZERO,
+    }
+}
+
+// Swapped { locker, pool_key, params, delta, sqrt_ratio_after, tick_after, liquidity_after }
+fn swapped(key: &PoolKey, sqrt_ratio: BigUint, tick: i32, liquidity: u128) -> Vec<Felt> {
+    let mut data = vec![Felt::TWO];
+    data.extend(key.to_calldata());
+    data.extend([Felt::ZERO; 10]);
+    data.extend([Felt::from(sqrt_ratio), Felt::ZERO]);
+    data.extend([
+        Felt::from(tick.unsigned_abs()),
+        Felt::from(u8::from(tick < 0)),
+        Felt::from(liquidity),
+    ]);
+    data
+}
+
+#[test]
+fn sync_from_event_only_applies_events_of_its_key() {
+    let mut pool = pool();
+    let sqrt_ratio = one() * 2u32;
+
+    let mut other_key = pool.key.clone();
+    other_key.tick_spacing = 200;
+    let err = pool
+        .sync_from_event(event(
+            SWAPPED_EVENT,
+            swapped(&other_key, sqrt_ratio.clone(), 693147, E18),
+        ))
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        AMMError::EventLogError(EventLogError::UnrelatedEvent)
+    ));
+    assert_eq!(pool.sqrt_ratio, one());
+
+    let key = pool.key.clone();
+    pool.sync_from_event(event(
+        SWAPPED_EVENT,
+        swapped(&key, sqrt_ratio.clone(), -693147, E18),
+    ))
+    .unwrap();
+    assert_eq!(pool.sqrt_ratio, sqrt_ratio);
+    assert_eq!(pool.tick, -693147);
+    assert_eq!(pool.liquidity, E18);
+}
+
+#[test]
+fn sync_from_event_updates_in_range_liquidity() {
+    let mut pool = pool();
+
+    // PositionUpdated { locker, pool_key, params: { salt, bounds, liquidity_delta }, delta }
+    let mut data = vec![Felt::TWO];
+    data.extend(pool.key.to_calldata());
+    data.extend([
+        Felt::ZERO,
+        Felt::from(1000u32),
+        Felt::ONE,
+        Felt::from(1000u32),
+        Felt::ZERO,
+        Felt::from(E18),
+        Felt::ONE,
+    ]);
+    pool.sync_from_event(event(POSITION_UPDATED_EVENT, data))
+        .unwrap();
+
+    assert_eq!(pool.liquidity, 2 * E18);
+    assert_eq!(pool.ticks[&-1000], -(E18 as i128));
+}
+
+#[test]
+fn sync_from_event_rejects_ticks_out_of_range() {
+    let mut pool = pool();
+    let key = pool.key.clone();
+
+    let mut data = swapped(&key, one() * 2u32, 0, E18);
+    let tick_mag = data.len() - 3;
+    data[tick_mag] = Felt::from(1u64 << 40);
+    let err = pool
+        .sync_from_event(event(SWAPPED_EVENT, data))
+        .unwrap_err();
+
+    assert!(matches!(err, AMMError::PoolDataError));
+    assert_eq!(pool.sqrt_ratio, one());
+}
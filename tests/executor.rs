@@ -0,0 +1,466 @@
+use std::{collections::HashMap, sync::Arc};
+
+use mev_engine::{
+    amm::{
+        jediswap::pool::JediswapPool,
+        pool::{AutomatedMarketMaker, AMM},
+        tenkswap::pool::TenkSwapPool,
+    },
+    arbitrage::{
+        graph::{Cycle, Hop},
+        Opportunity,
+    },
+    errors::ExecutorError,
+    executor::{
+        calls::approve_call,
+        flash::{FlashExecutor, FlashSwapOpportunity},
+        nonce::{is_nonce_error, NonceManager},
+        preflight::{check_received, predicted_amounts, received_amounts},
+        Executor, PreparedInvoke,
+    },
+    utils::{encode_u256, parse_u256, LocalWalletSignerMiddleware},
+};
+use num_bigint::{BigInt, BigUint};
+use starknet::{
+    accounts::{ExecutionEncoding, SingleOwnerAccount},
+    core::{
+        chain_id,
+        types::{Felt, FunctionInvocation, StarknetError},
+    },
+    macros::selector,
+    providers::{jsonrpc::HttpTransport, JsonRpcClient, ProviderError, Url},
+    signers::{LocalWallet, SigningKey},
+};
+
+const E18: u128 = 1_000_000_000_000_000_000;
+const ACCOUNT: Felt = Felt::from_hex_unchecked("0xacc");
+const JEDISWAP_ROUTER: Felt = Felt::from_hex_unchecked("0x1e1");
+const TENKSWAP_ROUTER: Felt = Felt::from_hex_unchecked("0x10c");
+
+fn account() -> LocalWalletSignerMiddleware {
+    let provider = Arc::new(JsonRpcClient::new(HttpTransport::new(
+        Url::parse("http://localhost:5050").unwrap(),
+    )));
+    let signer = LocalWallet::from(SigningKey::from_secret_scalar(Felt::ONE));
+
+    Arc::new(SingleOwnerAccount::new(
+        provider,
+        signer,
+        ACCOUNT,
+        chain_id::SEPOLIA,
+        ExecutionEncoding::New,
+    ))
+}
+
+fn token(id: u32) -> Felt {
+    Felt::from(0xa0 + id)
+}
+
+/// Buys token 1 on Jediswap and sells it back on 10kSwap, where it is dearer.
+fn opportunity() -> (Opportunity, HashMap<Felt, AMM>) {
+    let amms = [
+        AMM::JediswapPool(JediswapPool::new(
+            Felt::from(1u32),
+            token(0),
+            token(1),
+            18,
+            18,
+            Felt::from(1000 * E18),
+            Felt::from(1000 * E18),
+            3000,
+        )),
+        AMM::TenkSwapPool(TenkSwapPool::new(
+            Felt::from(2u32),
+            token(0),
+            token(1),
+            18,
+            18,
+            Felt::from(1000 * E18),
+            Felt::from(500 * E18),
+            3000,
+        )),
+    ];
+    let cycle = Cycle {
+        hops: vec![
+            Hop {
+                pool: Felt::from(1u32),
+                token_in: token(0),
+                token_out: token(1),
+            },
+            Hop {
+                pool: Felt::from(2u32),
+                token_in: token(1),
+                token_out: token(0),
+            },
+        ],
+    };
+    let opportunity = Opportunity {
+        cycle,
+        amount_in: Felt::from(E18),
+        amount_out: Felt::from(E18),
+    };
+
+    (
+        opportunity,
+        amms.into_iter().map(|amm| (amm.address(), amm)).collect(),
+    )
+}
+
+#[test]
+fn executor_plans_legs_with_slippage_guards() {
+    let (opportunity, amms) = opportunity();
+    let executor = Executor::new(account())
+        .with_jediswap_router(JEDISWAP_ROUTER)
+        .with_tenkswap_router(TENKSWAP_ROUTER)
+        .with_slippage_bps(100);
+
+    let legs = executor.plan(&opportunity, &amms).unwrap();
+    assert_eq!(legs.len(), 2);
+    assert_eq!(legs[0].router.address, JEDISWAP_ROUTER);
+    assert_eq!(legs[1].router.address, TENKSWAP_ROUTER);
+    assert_eq!(legs[0].amount_in, E18.into());
+
+    let mut jediswap = amms[&Felt::from(1u32)].clone();
+    let expected = jediswap
+        .simulate_swap_mut(token(0), token(1), Felt::from(E18))
+        .unwrap()
+        .to_biguint();
+    assert_eq!(legs[0].amount_out, expected);
+    // The first hop has to deliver what the second one spends, the slippage tolerance only
+    // applies to what the cycle returns.
+    assert_eq!(legs[0].min_amount_out, expected);
+    assert_eq!(legs[1].amount_in, legs[0].amount_out);
+    assert_eq!(
+        legs[1].min_amount_out,
+        opportunity.amount_out.to_biguint() * 99u32 / 100u32
+    );
+
+    let calls = executor.swap_calls(&legs, 1_700_000_000);
+    assert_eq!(calls[0].to, JEDISWAP_ROUTER);
+    assert_eq!(calls[0].selector, selector!("swap_exact_tokens_for_tokens"));
+    assert_eq!(calls[1].selector, selector!("swapExactTokensForTokens"));
+
+    let calldata = &calls[1].calldata;
+    assert_eq!(parse_u256(calldata[0], calldata[1]), legs[1].amount_in);
+    assert_eq!(parse_u256(calldata[2], calldata[3]), legs[1].min_amount_out);
+    assert_eq!(
+        calldata[4..],
+        [
+            Felt::TWO,
+            token(1),
+            token(0),
+            ACCOUNT,
+            Felt::from(1_700_000_000u64)
+        ]
+    );
+
+    let without_tenkswap = Executor::new(account()).with_jediswap_router(JEDISWAP_ROUTER);
+    assert!(matches!(
+        without_tenkswap.plan(&opportunity, &amms),
+        Err(ExecutorError::NoRouter(pool)) if pool == Felt::from(2u32)
+    ));
+}
+
+#[test]
+fn three_hop_cycles_trade_the_simulated_output_of_every_hop() {
+    let pool = |address: u32, token_a: Felt, token_b: Felt, reserve_b: u128| {
+        AMM::JediswapPool(JediswapPool::new(
+            Felt::from(address),
+            token_a,
+            token_b,
+            18,
+            18,
+            Felt::from(1000 * E18),
+            Felt::from(reserve_b * E18),
+            3000,
+        ))
+    };
+    let amms: HashMap<Felt, AMM> = [
+        pool(1, token(0), token(1), 1100),
+        pool(2, token(1), token(2), 1100),
+        pool(3, token(2), token(0), 1100),
+    ]
+    .into_iter()
+    .map(|amm| (amm.address(), amm))
+    .collect();
+    let hop = |pool: u32, token_in: u32, token_out: u32| Hop {
+        pool: Felt::from(pool),
+        token_in: token(token_in),
+        token_out: token(token_out),
+    };
+    let cycle = Cycle {
+        hops: vec![hop(1, 0, 1), hop(2, 1, 2), hop(3, 2, 0)],
+    };
+
+    let mut amount = Felt::from(E18);
+    for hop in &cycle.hops {
+        amount = amms[&hop.pool]
+            .clone()
+            .simulate_swap_mut(hop.token_in, hop.token_out, amount)
+            .unwrap();
+    }
+    let opportunity = Opportunity {
+        cycle,
+        amount_in: Felt::from(E18),
+        amount_out: amount,
+    };
+
+    let executor = Executor::new(account())
+        .with_jediswap_router(JEDISWAP_ROUTER)
+        .with_slippage_bps(100);
+    let legs = executor.plan(&opportunity, &amms).unwrap();
+
+    assert_eq!(legs.len(), 3);
+    assert_eq!(legs[0].amount_in, E18.into());
+    for (leg, next) in legs.iter().zip(&legs[1..]) {
+        assert_eq!(next.amount_in, leg.amount_out);
+        assert_eq!(leg.min_amount_out, leg.amount_out);
+    }
+    assert_eq!(legs[2].amount_out, amount.to_biguint());
+    assert_eq!(legs[2].min_amount_out, amount.to_biguint() * 99u32 / 100u32);
+}
+
+#[test]
+fn approve_call_encodes_u256_amount() {
+    let amount = (BigUint::from(3u32) << 128) + 7u32;
+    let call = approve_call(token(0), JEDISWAP_ROUTER, &amount);
+
+    assert_eq!(call.to, token(0));
+    assert_eq!(call.selector, selector!("approve"));
+    assert_eq!(
+        call.calldata,
+        [JEDISWAP_ROUTER, Felt::from(7u32), Felt::THREE]
+    );
+    assert_eq!(encode_u256(&amount), [Felt::from(7u32), Felt::THREE]);
+}
+
+/// An invocation of `contract_address` emitting `events`, each given as keys and data.
+fn invocation(
+    contract_address: Felt,
+    events: Vec<(Vec<Felt>, Vec<Felt>)>,
+    calls: Vec<FunctionInvocation>,
+) -> FunctionInvocation {
+    FunctionInvocation {
+        contract_address,
+        entry_point_selector: Felt::ZERO,
+        calldata: vec![],
+        caller_address: Felt::ZERO,
+        class_hash: Felt::ZERO,
+        entry_point_type: serde_json::from_str("\"EXTERNAL\"").unwrap(),
+        call_type: serde_json::from_str("\"CALL\"").unwrap(),
+        result: vec![],
+        calls,
+        events: events
+            .into_iter()
+            .enumerate()
+            .map(|(order, (keys, data))| {
+                serde_json::from_value(serde_json::json!({
+                    "order": order,
+                    "keys": keys,
+                    "data": data,
+                }))
+                .unwrap()
+            })
+            .collect(),
+        messages: vec![],
+        execution_resources: serde_json::from_str(r#"{"steps": 0}"#).unwrap(),
+    }
+}
+
+#[test]
+fn preflight_compares_simulated_transfers_with_prediction() {
+    let (opportunity, amms) = opportunity();
+    let executor = Executor::new(account())
+        .with_jediswap_router(JEDISWAP_ROUTER)
+        .with_tenkswap_router(TENKSWAP_ROUTER);
+    let legs = executor.plan(&opportunity, &amms).unwrap();
+    let predicted = predicted_amounts(&legs);
+    // Token 1 is passed on as received, and token 0 returns the profit.
+    assert_eq!(predicted[&token(1)], BigInt::ZERO);
+    assert_eq!(
+        predicted[&token(0)],
+        BigInt::from(legs[1].amount_out.clone()) - BigInt::from(legs[0].amount_in.clone())
+    );
+
+    let transfer = selector!("Transfer");
+    let u256 = |amount: &BigUint| encode_u256(amount).to_vec();
+    // Token 1 uses Cairo 1 events, with the addresses as keys, and token 0 Cairo 0 ones.
+    let simulate = |out_0: &BigUint, out_1: &BigUint| {
+        let swap_1 = invocation(
+            token(1),
+            vec![
+                (vec![transfer, Felt::from(1u32), ACCOUNT], u256(out_1)),
+                (vec![transfer, ACCOUNT, Felt::from(2u32)], u256(out_1)),
+            ],
+            vec![],
+        );
+        let swap_0 = invocation(
+            token(0),
+            vec![
+                (
+                    vec![transfer],
+                    [vec![ACCOUNT, Felt::from(1u32)], u256(&legs[0].amount_in)].concat(),
+                ),
+                (
+                    vec![transfer],
+                    [vec![Felt::from(2u32), ACCOUNT], u256(out_0)].concat(),
+                ),
+            ],
+            vec![],
+        );
+        invocation(ACCOUNT, vec![], vec![swap_1, swap_0])
+    };
+
+    let received = received_amounts(&simulate(&legs[1].amount_out, &legs[0].amount_out), ACCOUNT);
+    assert_eq!(received, predicted);
+    assert!(check_received(&predicted, &received, 10).is_ok());
+
+    // Missing 1% of the profit is beyond a 0.1% tolerance, but not a 2% one.
+    let profit = &legs[1].amount_out - &legs[0].amount_in;
+    let short = &legs[1].amount_out - profit / 100u32;
+    let received = received_amounts(&simulate(&short, &legs[0].amount_out), ACCOUNT);
+    assert!(matches!(
+        check_received(&predicted, &received, 10),
+        Err(ExecutorError::PreflightMismatch(mismatched)) if mismatched == token(0)
+    ));
+    assert!(check_received(&predicted, &received, 200).is_ok());
+
+    // Tokens sent out of the account without being predicted fail the check.
+    let drained = invocation(
+        token(3),
+        vec![(
+            vec![transfer, ACCOUNT, Felt::from(9u32)],
+            u256(&BigUint::from(1u32)),
+        )],
+        vec![],
+    );
+    let swaps = simulate(&legs[1].amount_out, &legs[0].amount_out);
+    let received = received_amounts(&invocation(ACCOUNT, vec![], vec![swaps, drained]), ACCOUNT);
+    assert_eq!(received[&token(3)], BigInt::from(-1));
+    assert!(matches!(
+        check_received(&predicted, &received, 200),
+        Err(ExecutorError::PreflightMismatch(mismatched)) if mismatched == token(3)
+    ));
+}
+
+#[tokio::test]
+async fn nonce_manager_hands_out_sequential_nonces() {
+    let nonces = Arc::new(NonceManager::new(account()));
+    nonces.set(Felt::from(5u32)).await;
+
+    let handles = (0..10)
+        .map(|_| {
+            let nonces = nonces.clone();
+            tokio::spawn(async move { nonces.next().await.unwrap() })
+        })
+        .collect::<Vec<_>>();
+    let mut handed_out = vec![];
+    for handle in handles {
+        handed_out.push(handle.await.unwrap());
+    }
+    handed_out.sort();
+    assert_eq!(handed_out, (5u32..15).map(Felt::from).collect::<Vec<_>>());
+
+    // The latest nonce can be given back, and is handed out again.
+    nonces.release(Felt::from(14u32)).await;
+    assert_eq!(nonces.next().await.unwrap(), Felt::from(14u32));
+
+    let nonce_error =
+        |err: StarknetError| ExecutorError::ProviderError(ProviderError::StarknetError(err));
+    assert!(is_nonce_error(&nonce_error(
+        StarknetError::InvalidTransactionNonce
+    )));
+    assert!(is_nonce_error(&nonce_error(
+        StarknetError::ValidationFailure(
+            "Invalid transaction nonce. Expected: 3, got: 2.".to_string()
+        )
+    )));
+    assert!(!is_nonce_error(&nonce_error(
+        StarknetError::InsufficientMaxFee
+    )));
+}
+
+#[test]
+fn replacement_raises_gas_price_bound() {
+    let invoke = PreparedInvoke {
+        calls: vec![],
+        nonce: Felt::from(7u32),
+        gas: 1000,
+        gas_price: 1000,
+    };
+
+    let replacement = invoke.with_bumped_fee(20);
+    assert_eq!(replacement.nonce, invoke.nonce);
+    assert_eq!(replacement.gas, invoke.gas);
+    assert_eq!(replacement.gas_price, 1200);
+
+    // Even a rounded down bump raises the bound.
+    let cheap = PreparedInvoke {
+        gas_price: 1,
+        ..invoke
+    };
+    assert_eq!(cheap.with_bumped_fee(20).gas_price, 2);
+
+    let expensive = PreparedInvoke {
+        gas_price: u128::MAX - 1,
+        ..cheap
+    };
+    assert_eq!(expensive.with_bumped_fee(20).gas_price, u128::MAX);
+}
+
+#[tokio::test]
+async fn flash_swap_borrows_first_leg_and_repays_pair() {
+    let (opportunity, amms) = opportunity();
+    let executor = Executor::new(account()).with_slippage_bps(100);
+    let flash = executor.plan_flash(&opportunity, &amms).unwrap();
+
+    // Token 1 is the pair's token1, borrowed against token 0.
+    assert_eq!(flash.pair, Felt::from(1u32));
+    assert_eq!(flash.amount0_out, BigUint::default());
+    let borrowed = amms[&Felt::from(1u32)]
+        .clone()
+        .simulate_swap_mut(token(0), token(1), Felt::from(E18))
+        .unwrap();
+    assert_eq!(flash.amount1_out, borrowed.to_biguint());
+    assert!(flash.repay_amount <= E18.into());
+
+    assert_eq!(flash.hops.len(), 1);
+    let amount_out = amms[&Felt::from(2u32)]
+        .clone()
+        .simulate_swap_mut(token(1), token(0), borrowed)
+        .unwrap()
+        .to_biguint();
+    assert_eq!(flash.amount_out(), amount_out);
+    assert_eq!(flash.hops[0].min_amount_out, &amount_out * 99u32 / 100u32);
+    assert_eq!(flash.profit(), &amount_out - &flash.repay_amount);
+
+    let contract = FlashExecutor::new(Felt::from(0xf1a5u32));
+    let call = contract.flash_arbitrage_call(&flash, &BigUint::from(1000u32));
+    assert_eq!(call.to, Felt::from(0xf1a5u32));
+    assert_eq!(call.selector, selector!("flash_arbitrage"));
+    assert_eq!(
+        call.calldata,
+        [
+            vec![Felt::from(1u32), Felt::ZERO, Felt::ZERO],
+            encode_u256(&flash.amount1_out).to_vec(),
+            vec![token(0)],
+            encode_u256(&flash.repay_amount).to_vec(),
+            vec![Felt::ONE, Felt::from(2u32), token(1), token(0)],
+            encode_u256(&flash.hops[0].min_amount_out).to_vec(),
+            vec![Felt::from(1000u32), Felt::ZERO],
+        ]
+        .concat()
+    );
+
+    assert!(matches!(
+        executor.execute_flash(&flash, Felt::ONE).await,
+        Err(ExecutorError::NoFlashExecutor)
+    ));
+
+    let mut one_hop = opportunity.clone();
+    one_hop.cycle.hops.truncate(1);
+    assert!(matches!(
+        FlashSwapOpportunity::new(&one_hop, &amms, 0),
+        Err(ExecutorError::CycleTooShort)
+    ));
+}
@@ -0,0 +1,500 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use mev_engine::{
+    amm::{
+        jediswap_v2::{
+            factory::JediswapV2Factory,
+            get_data::{BURN_EVENT, MINT_EVENT, SWAP_EVENT},
+            math::{
+                compute_swap_step, get_sqrt_ratio_at_tick, get_tick_at_sqrt_ratio, max_sqrt_ratio,
+                min_sqrt_ratio, MAX_TICK, MIN_TICK,
+            },
+            pool::JediswapV2Pool,
+        },
+        pool::{AutomatedMarketMaker, AMM},
+    },
+    cache::{save_checkpoint, sync_amms_from_checkpoint},
+    errors::{AMMError, ArithmeticError, SwapSimulationError},
+    token_registry::{TokenMetadata, TokenRegistry},
+};
+use num_bigint::BigUint;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+use starknet::{
+    core::{
+        types::{BlockId, EmittedEvent, Felt},
+        utils::get_selector_from_name,
+    },
+    providers::{
+        jsonrpc::{JsonRpcMethod, JsonRpcResponse, JsonRpcTransport},
+        JsonRpcClient, ProviderRequestData,
+    },
+};
+
+const E18: u128 = 1_000_000_000_000_000_000;
+
+fn big(value: &str) -> BigUint {
+    BigUint::parse_bytes(value.as_bytes(), 10).unwrap()
+}
+
+fn q96() -> BigUint {
+    BigUint::from(1u32) << 96
+}
+
+fn token_a() -> Felt {
+    Felt::from(0xaau32)
+}
+
+fn token_b() -> Felt {
+    Felt::from(0xbbu32)
+}
+
+// Price 1:1 with 1e18 liquidity on [-120, 120] and 2e18 on [-60, 60].
+fn pool() -> JediswapV2Pool {
+    let mut pool = JediswapV2Pool::new(
+        Felt::ONE,
+        token_a(),
+        token_b(),
+        18,
+        18,
+        3 * E18,
+        q96(),
+        3000,
+        0,
+        60,
+    );
+    pool.modify_position(-120, 120, E18 as i128).unwrap();
+    pool.modify_position(-60, 60, 2 * E18 as i128).unwrap();
+    pool
+}
+
+#[test]
+fn tick_math_matches_reference_values() {
+    assert_eq!(get_sqrt_ratio_at_tick(MIN_TICK).unwrap(), min_sqrt_ratio());
+    assert_eq!(get_sqrt_ratio_at_tick(MAX_TICK).unwrap(), max_sqrt_ratio());
+    assert_eq!(get_sqrt_ratio_at_tick(0).unwrap(), q96());
+    assert_eq!(
+        get_sqrt_ratio_at_tick(1).unwrap(),
+        big("79232123823359799118286999568")
+    );
+    assert_eq!(
+        get_sqrt_ratio_at_tick(-1).unwrap(),
+        big("79224201403219477170569942574")
+    );
+    assert!(get_sqrt_ratio_at_tick(MAX_TICK + 1).is_err());
+
+    assert_eq!(get_tick_at_sqrt_ratio(&min_sqrt_ratio()).unwrap(), MIN_TICK);
+    assert_eq!(
+        get_tick_at_sqrt_ratio(&(max_sqrt_ratio() - 1u32)).unwrap(),
+        MAX_TICK - 1
+    );
+    assert_eq!(get_tick_at_sqrt_ratio(&q96()).unwrap(), 0);
+    assert_eq!(get_tick_at_sqrt_ratio(&(q96() - 1u32)).unwrap(), -1);
+}
+
+#[test]
+fn compute_swap_step_matches_reference_values() {
+    // (price, target, liquidity, amount, exact input, fee) -> (next price, in, out, fee)
+    // from the Uniswap V3 SwapMath test suite.
+    let price_101_100 = big("79623317895830914510639640423");
+
+    let step = compute_swap_step(
+        &q96(),
+        &price_101_100,
+        2 * E18,
+        &BigUint::from(E18),
+        true,
+        600,
+    )
+    .unwrap();
+    assert_eq!(step.sqrt_price_next, price_101_100);
+    assert_eq!(step.amount_in, BigUint::from(9975124224178055u64));
+    assert_eq!(step.amount_out, BigUint::from(9925619580021728u64));
+    assert_eq!(step.fee_amount, BigUint::from(5988667735148u64));
+
+    let step = compute_swap_step(
+        &q96(),
+        &big("250541448375047931186413801569"),
+        2 * E18,
+        &BigUint::from(E18),
+        true,
+        600,
+    )
+    .unwrap();
+    assert_eq!(step.amount_in, BigUint::from(999400000000000000u64));
+    assert_eq!(step.amount_out, BigUint::from(666399946655997866u64));
+    assert_eq!(step.fee_amount, BigUint::from(600000000000000u64));
+
+    let step = compute_swap_step(
+        &q96(),
+        &big("792281625142643375935439503360"),
+        2 * E18,
+        &BigUint::from(E18),
+        false,
+        600,
+    )
+    .unwrap();
+    assert_eq!(step.amount_in, BigUint::from(2 * E18));
+    assert_eq!(step.amount_out, BigUint::from(E18));
+    assert_eq!(step.fee_amount, BigUint::from(1200720432259356u64));
+
+    let step = compute_swap_step(
+        &big("417332158212080721273783715441582"),
+        &big("1452870262520218020823638996"),
+        159344665391607089467575320103,
+        &BigUint::from(1u32),
+        false,
+        1,
+    )
+    .unwrap();
+    assert_eq!(
+        step.sqrt_price_next,
+        big("417332158212080721273783715441581")
+    );
+    assert_eq!(step.amount_in, BigUint::from(1u32));
+    assert_eq!(step.amount_out, BigUint::from(1u32));
+    assert_eq!(step.fee_amount, BigUint::from(1u32));
+
+    let step = compute_swap_step(
+        &BigUint::from(2413u32),
+        &BigUint::from(79887613182836312u64),
+        1985041575832132834610021537970,
+        &BigUint::from(10u32),
+        true,
+        1872,
+    )
+    .unwrap();
+    assert_eq!(step.sqrt_price_next, BigUint::from(2413u32));
+    assert_eq!(step.amount_in, BigUint::from(0u32));
+    assert_eq!(step.amount_out, BigUint::from(0u32));
+    assert_eq!(step.fee_amount, BigUint::from(10u32));
+}
+
+#[test]
+fn compute_swap_step_rejects_fees_of_the_whole_amount() {
+    for fee in [1_000_000, u32::MAX] {
+        assert!(matches!(
+            compute_swap_step(&q96(), &(q96() * 2u32), E18, &BigUint::from(E18), true, fee),
+            Err(SwapSimulationError::InvalidFee(invalid)) if invalid == fee
+        ));
+    }
+}
+
+#[test]
+fn simulate_swap_mut_crosses_initialized_ticks() {
+    let mut pool = pool();
+
+    let amount_out = pool
+        .simulate_swap_mut(token_a(), token_b(), Felt::from(10_000_000_000_000_000u128))
+        .unwrap();
+
+    assert_eq!(amount_out, Felt::from(9936371867692330u128));
+    assert_eq!(pool.sqrt_price, big("78915554967598258201474945644"));
+    assert_eq!(pool.tick, -80);
+    assert_eq!(pool.liquidity, E18);
+}
+
+#[test]
+fn simulate_swap_exact_out_within_one_range() {
+    let mut pool = pool();
+
+    let amount_in = pool
+        .simulate_swap_exact_out(token_a(), token_b(), Felt::from(5_000_000_000_000_000u128))
+        .unwrap();
+    assert_eq!(amount_in, Felt::from(5023417497902725u128));
+
+    let amount_in_mut = pool
+        .simulate_swap_exact_out_mut(token_a(), token_b(), Felt::from(5_000_000_000_000_000u128))
+        .unwrap();
+    assert_eq!(amount_in_mut, amount_in);
+    assert_eq!(pool.sqrt_price, big("79096115576740563697554710418"));
+    assert_eq!(pool.liquidity, 3 * E18);
+}
+
+#[test]
+fn swaps_beyond_the_pool_liquidity_are_rejected() {
+    let mut pool = pool();
+
+    assert!(matches!(
+        pool.simulate_swap_mut(token_a(), token_b(), Felt::from(E18)),
+        Err(AMMError::SwapSimulationError(
+            SwapSimulationError::LiquidityUnderflow
+        ))
+    ));
+    assert!(matches!(
+        pool.simulate_swap_exact_out(token_a(), token_b(), Felt::from(E18)),
+        Err(AMMError::SwapSimulationError(
+            SwapSimulationError::LiquidityUnderflow
+        ))
+    ));
+    assert_eq!(pool.sqrt_price, q96());
+}
+
+#[tokio::test]
+async fn simulate_swap_rejects_unknown_tokens() {
+    let node = Arc::new(JsonRpcClient::new(Node {
+        events: vec![],
+        filters: Arc::default(),
+    }));
+
+    assert!(matches!(
+        pool()
+            .simulate_swap(Felt::THREE, Felt::from(E18), node)
+            .await,
+        Err(AMMError::ArithmeticError(
+            ArithmeticError::BaseTokenDoesNotExist
+        ))
+    ));
+}
+
+#[test]
+fn calculate_price_from_sqrt_price() {
+    let mut pool = pool();
+    assert_eq!(pool.calculate_price(token_a(), token_b()).unwrap(), 1.0);
+
+    pool.sqrt_price = q96() * 2u32;
+    pool.token_b_decimals = 6;
+    assert_eq!(pool.calculate_price(token_a(), token_b()).unwrap(), 4e12);
+    assert_eq!(
+        pool.calculate_price(token_b(), token_a()).unwrap(),
+        0.25e-12
+    );
+}
+
+fn event(selector: Felt, data: Vec<Felt>) -> EmittedEvent {
+    EmittedEvent {
+        from_address: Felt::ONE,
+        keys: vec![selector],
+        data,
+        block_hash: None,
+        block_number: Some(1),
+        transaction_hash: Felt::ZERO,
+    }
+}
+
+#[test]
+fn sync_from_event_takes_state_from_swap() {
+    let mut pool = pool();
+    let sqrt_price = big("79096115576740563697554710418");
+
+    // Swap { sender, recipient, amount0: i256, amount1: i256, sqrt_price_X96, liquidity, tick }
+    let data = vec![
+        Felt::TWO,
+        Felt::TWO,
+        Felt::from(5023417497902725u128),
+        Felt::ZERO,
+        Felt::ZERO,
+        Felt::from(5_000_000_000_000_000u128),
+        Felt::ZERO,
+        Felt::ONE,
+        Felt::from(sqrt_price.clone()),
+        Felt::ZERO,
+        Felt::from(2 * E18),
+        Felt::from(34u32),
+        Felt::ONE,
+    ];
+    pool.sync_from_event(event(SWAP_EVENT, data)).unwrap();
+
+    assert_eq!(pool.sqrt_price, sqrt_price);
+    assert_eq!(pool.tick, -34);
+    assert_eq!(pool.liquidity, 2 * E18);
+}
+
+#[test]
+fn sync_from_event_applies_mint_and_burn() {
+    let mut pool = pool();
+
+    // Mint { sender, owner, tick_lower, tick_upper, amount, amount0, amount1 }, in range.
+    let mint = vec![
+        Felt::TWO,
+        Felt::TWO,
+        Felt::from(60u32),
+        Felt::ONE,
+        Felt::from(60u32),
+        Felt::ZERO,
+        Felt::from(E18),
+        Felt::ZERO,
+        Felt::ZERO,
+    ];
+    pool.sync_from_event(event(MINT_EVENT, mint)).unwrap();
+    assert_eq!(pool.liquidity, 4 * E18);
+    assert_eq!(pool.ticks[&-60].liquidity_net, 3 * E18 as i128);
+
+    // Burn { owner, tick_lower, tick_upper, amount, amount0, amount1 }, out of range.
+    let burn = vec![
+        Felt::TWO,
+        Felt::from(120u32),
+        Felt::ZERO,
+        Felt::from(240u32),
+        Felt::ZERO,
+        Felt::ZERO,
+        Felt::ZERO,
+        Felt::ZERO,
+    ];
+    pool.sync_from_event(event(BURN_EVENT, burn)).unwrap();
+    assert_eq!(pool.liquidity, 4 * E18);
+}
+
+/// Node at block 110, returning the `events` of the queried address and a fixed slot state to
+/// pool calls.
+struct Node {
+    events: Vec<EmittedEvent>,
+    filters: Arc<Mutex<Vec<serde_json::Value>>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unexpected request")]
+struct UnexpectedRequest;
+
+#[async_trait]
+impl JsonRpcTransport for Node {
+    type Error = UnexpectedRequest;
+
+    async fn send_request<P, R>(
+        &self,
+        method: JsonRpcMethod,
+        params: P,
+    ) -> Result<JsonRpcResponse<R>, UnexpectedRequest>
+    where
+        P: Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let params = serde_json::to_value(params).unwrap();
+        let result = match method {
+            JsonRpcMethod::BlockNumber => json!(110),
+            JsonRpcMethod::GetEvents => {
+                let address = serde_json::from_value::<Felt>(params[0]["address"].clone()).ok();
+                self.filters.lock().unwrap().push(params[0].clone());
+                let events: Vec<_> = self
+                    .events
+                    .iter()
+                    .filter(|event| address.is_none_or(|address| event.from_address == address))
+                    .collect();
+                json!({ "events": events })
+            }
+            JsonRpcMethod::Call => {
+                let selector = params[0]["entry_point_selector"].clone();
+                let result = match serde_json::from_value::<Felt>(selector).unwrap() {
+                    s if s == get_selector_from_name("get_sqrt_price_X96").unwrap() => {
+                        vec![Felt::from(q96() * 2u32), Felt::ZERO]
+                    }
+                    s if s == get_selector_from_name("get_tick").unwrap() => {
+                        vec![Felt::from(13863u32), Felt::ZERO]
+                    }
+                    s if s == get_selector_from_name("get_liquidity").unwrap() => {
+                        vec![Felt::from(E18)]
+                    }
+                    _ => return Err(UnexpectedRequest),
+                };
+                json!(result)
+            }
+            _ => return Err(UnexpectedRequest),
+        };
+
+        Ok(serde_json::from_value(json!({ "id": 1, "result": result })).unwrap())
+    }
+
+    async fn send_requests<R>(
+        &self,
+        _requests: R,
+    ) -> Result<Vec<JsonRpcResponse<serde_json::Value>>, UnexpectedRequest>
+    where
+        R: AsRef<[ProviderRequestData]> + Send + Sync,
+    {
+        Err(UnexpectedRequest)
+    }
+}
+
+#[tokio::test]
+async fn checkpoint_sync_only_applies_new_position_events() {
+    // Mint { sender, owner, tick_lower, tick_upper, amount, amount0, amount1 }
+    let mint = vec![
+        Felt::TWO,
+        Felt::TWO,
+        Felt::from(60u32),
+        Felt::ONE,
+        Felt::from(60u32),
+        Felt::ZERO,
+        Felt::from(E18),
+        Felt::ZERO,
+        Felt::ZERO,
+    ];
+    // Events from other contracts are not queried, so they are never decoded.
+    let other = EmittedEvent {
+        from_address: Felt::TWO,
+        data: vec![],
+        ..event(MINT_EVENT, vec![])
+    };
+    let filters = Arc::new(Mutex::new(vec![]));
+    let node = Arc::new(JsonRpcClient::new(Node {
+        events: vec![event(MINT_EVENT, mint), other],
+        filters: filters.clone(),
+    }));
+
+    let idle = JediswapV2Pool {
+        pool_address: Felt::THREE,
+        ..pool()
+    };
+    let mut amms = [AMM::JediswapV2Pool(pool()), AMM::JediswapV2Pool(idle)];
+    JediswapV2Factory::new(Felt::ZERO, 0)
+        .sync_amm_data(&mut amms, 100, BlockId::Number(110), node)
+        .await
+        .unwrap();
+
+    let AMM::JediswapV2Pool(pool) = &amms[0] else {
+        unreachable!()
+    };
+    assert_eq!(pool.sqrt_price, q96() * 2u32);
+    assert_eq!(pool.tick, 13863);
+    assert_eq!(pool.liquidity, E18);
+    assert_eq!(pool.ticks[&-60].liquidity_net, 3 * E18 as i128);
+    assert_eq!(pool.ticks[&-120].liquidity_net, E18 as i128);
+
+    let AMM::JediswapV2Pool(idle) = &amms[1] else {
+        unreachable!()
+    };
+    assert_eq!(idle.ticks[&-60].liquidity_net, 2 * E18 as i128);
+
+    let filters = filters.lock().unwrap();
+    let mut addresses: Vec<_> = filters.iter().map(|filter| &filter["address"]).collect();
+    addresses.sort_by_key(|address| address.to_string());
+    assert_eq!(addresses, [&json!("0x1"), &json!("0x3")]);
+    assert!(filters
+        .iter()
+        .all(|filter| filter["from_block"] == json!({ "block_number": 100 })));
+}
+
+#[tokio::test]
+async fn pools_synced_from_a_checkpoint_keep_their_decimals() {
+    let registry = TokenRegistry::new();
+    for (token, decimals) in [(token_a(), 18), (token_b(), 6)] {
+        registry.insert(
+            token,
+            TokenMetadata {
+                decimals,
+                ..Default::default()
+            },
+        );
+    }
+    let mut pool = pool();
+    pool.token_b_decimals = 6;
+
+    let path = std::env::temp_dir().join(format!("v3-checkpoint-{}.json", std::process::id()));
+    save_checkpoint(vec![], &[AMM::JediswapV2Pool(pool)], &registry, 100, &path).unwrap();
+    let node = Arc::new(JsonRpcClient::new(Node {
+        events: vec![],
+        filters: Arc::new(Mutex::new(vec![])),
+    }));
+    let (_, amms) = sync_amms_from_checkpoint(&path, 10, node).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let AMM::JediswapV2Pool(pool) = &amms[0] else {
+        unreachable!()
+    };
+    assert_eq!((pool.token_a_decimals, pool.token_b_decimals), (18, 6));
+    assert_eq!(pool.sqrt_price, q96() * 2u32);
+    assert_eq!(pool.ticks[&-60].liquidity_net, 2 * E18 as i128);
+    // A price of 4 raw units, scaled by the decimals of both tokens.
+    assert_eq!(pool.calculate_price(token_a(), token_b()).unwrap(), 4e12);
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use mev_engine::{
+    amm::{
+        myswap::{get_data::parse_pool_struct, pool::MySwapPool},
+        pool::AutomatedMarketMaker,
+    },
+    errors::{AMMError, ArithmeticError},
+};
+use starknet::{
+    core::types::Felt,
+    providers::{jsonrpc::HttpTransport, JsonRpcClient, Url},
+};
+
+const E18: u128 = 1_000_000_000_000_000_000;
+
+fn token_a() -> Felt {
+    Felt::from(0xaau32)
+}
+
+fn token_b() -> Felt {
+    Felt::from(0xbbu32)
+}
+
+fn pool() -> MySwapPool {
+    MySwapPool::new(
+        Felt::ONE,
+        4,
+        token_a(),
+        token_b(),
+        18,
+        18,
+        Felt::from(100 * E18),
+        Felt::from(200 * E18),
+        300,
+    )
+}
+
+#[test]
+fn parse_pool_struct_reads_uint256_reserves() {
+    let data = [
+        Felt::from_hex_unchecked("0x4554482f55534443"),
+        token_a(),
+        Felt::from(5u32),
+        Felt::ONE,
+        token_b(),
+        Felt::from(7u32),
+        Felt::ZERO,
+        Felt::from(300u32),
+        Felt::ONE,
+        Felt::from(0xccu32),
+    ];
+
+    let pool = parse_pool_struct(&data).unwrap();
+    assert_eq!(pool.token_a, token_a());
+    assert_eq!(pool.reserve_a, Felt::from(u128::MAX) + Felt::from(6u32));
+    assert_eq!(pool.token_b, token_b());
+    assert_eq!(pool.reserve_b, Felt::from(7u32));
+    assert_eq!(pool.fee, 300);
+
+    assert!(parse_pool_struct(&data[..5]).is_err());
+}
+
+#[test]
+fn simulate_swap_mut_takes_fee_from_input() {
+    let mut pool = pool();
+
+    let amount_out = pool
+        .simulate_swap_mut(token_a(), token_b(), Felt::from(E18))
+        .unwrap();
+
+    assert_eq!(amount_out, Felt::from(1974316068794122597u128));
+    // The fee stays in the pool.
+    assert_eq!(pool.reserve_a, Felt::from(101 * E18));
+    assert_eq!(pool.reserve_b, Felt::from(200 * E18) - amount_out);
+}
+
+#[test]
+fn simulate_swap_exact_out_returns_smallest_input() {
+    let pool = pool();
+    let amount_out = Felt::from(1974316068794122597u128);
+
+    let amount_in = pool
+        .simulate_swap_exact_out(token_a(), token_b(), amount_out)
+        .unwrap();
+
+    assert!(amount_in <= Felt::from(E18));
+    assert_eq!(
+        pool.get_amount_out(amount_in, pool.reserve_a, pool.reserve_b),
+        amount_out
+    );
+    assert!(
+        pool.get_amount_out(amount_in - Felt::ONE, pool.reserve_a, pool.reserve_b) < amount_out
+    );
+
+    assert!(pool
+        .simulate_swap_exact_out(token_a(), token_b(), pool.reserve_b)
+        .is_err());
+}
+
+#[tokio::test]
+async fn simulate_swap_rejects_unknown_tokens() {
+    let provider = Arc::new(JsonRpcClient::new(HttpTransport::new(
+        Url::parse("http://localhost:5050").unwrap(),
+    )));
+
+    assert!(matches!(
+        pool()
+            .simulate_swap(Felt::THREE, Felt::from(E18), provider)
+            .await,
+        Err(AMMError::ArithmeticError(
+            ArithmeticError::BaseTokenDoesNotExist
+        ))
+    ));
+}
+
+#[test]
+fn address_is_unique_per_pool_id() {
+    let mut other = pool();
+    other.pool_id = 5;
+
+    assert_ne!(pool().address(), other.address());
+    assert!(pool()
+        .simulate_swap_mut(Felt::THREE, token_b(), Felt::ONE)
+        .is_err());
+}
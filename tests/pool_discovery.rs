@@ -0,0 +1,180 @@
+use mev_engine::{
+    amm::{
+        ekubo::{factory::EkuboFactory, get_data::POOL_INITIALIZED_EVENT},
+        factory::{AutomatedMarketMakerFactory, Factory},
+        jediswap::{factory::JediswapFactory, get_data::PAIR_CREATED_EVENT},
+        jediswap_v2::{factory::JediswapV2Factory, get_data::POOL_CREATED_EVENT},
+        myswap::factory::MySwapFactory,
+        pool::{AutomatedMarketMaker, AMM},
+    },
+    cache::read_checkpoint,
+    errors::{AMMError, CheckpointError, EventLogError},
+    utils::{read_integer, read_result, DEFAULT_BATCH_SIZE},
+};
+use starknet::core::types::{BlockId, EmittedEvent, Felt};
+
+fn log(from_address: Felt, selector: Felt, data: Vec<Felt>) -> EmittedEvent {
+    EmittedEvent {
+        from_address,
+        keys: vec![selector],
+        data,
+        block_hash: None,
+        block_number: Some(1),
+        transaction_hash: Felt::ZERO,
+    }
+}
+
+#[test]
+fn decodes_v2_pair_created() {
+    let factory = Factory::JediswapFactory(JediswapFactory::new(Felt::ONE));
+    let amm = factory
+        .new_empty_amm_from_log(log(
+            Felt::ONE,
+            PAIR_CREATED_EVENT,
+            vec![
+                Felt::from(0xaau32),
+                Felt::from(0xbbu32),
+                Felt::from(0xccu32),
+                Felt::TWO,
+            ],
+        ))
+        .unwrap();
+
+    assert!(matches!(amm, AMM::JediswapPool(_)));
+    assert_eq!(amm.address(), Felt::from(0xccu32));
+    assert_eq!(amm.tokens(), vec![Felt::from(0xaau32), Felt::from(0xbbu32)]);
+}
+
+#[test]
+fn decodes_v3_pool_created() {
+    let factory = Factory::JediswapV2Factory(JediswapV2Factory::new(Felt::ONE, 0));
+    let amm = factory
+        .new_empty_amm_from_log(log(
+            Felt::ONE,
+            POOL_CREATED_EVENT,
+            vec![
+                Felt::from(0xaau32),
+                Felt::from(0xbbu32),
+                Felt::from(3000u32),
+                Felt::from(60u32),
+                Felt::from(0xccu32),
+            ],
+        ))
+        .unwrap();
+
+    let AMM::JediswapV2Pool(pool) = amm else {
+        panic!("expected a Jediswap V2 pool");
+    };
+    assert_eq!(pool.pool_address, Felt::from(0xccu32));
+    assert_eq!(pool.fee, 3000);
+    assert_eq!(pool.tick_spacing, 60);
+}
+
+#[test]
+fn decodes_ekubo_pool_initialized() {
+    let core = Felt::from(0xc0u32);
+    let factory = Factory::EkuboFactory(EkuboFactory::new(core, 0));
+    let amm = factory
+        .new_empty_amm_from_log(log(
+            core,
+            POOL_INITIALIZED_EVENT,
+            vec![
+                Felt::from(0xaau32),
+                Felt::from(0xbbu32),
+                Felt::from(1u32 << 20),
+                Felt::from(100u32),
+                Felt::ZERO,
+                Felt::ZERO,
+                Felt::ZERO,
+                Felt::ONE,
+                Felt::ZERO,
+            ],
+        ))
+        .unwrap();
+
+    let AMM::EkuboPool(pool) = amm else {
+        panic!("expected an Ekubo pool");
+    };
+    assert_eq!(pool.core_address, core);
+    assert_eq!(pool.key.fee, 1 << 20);
+    assert_eq!(pool.key.tick_spacing, 100);
+}
+
+#[test]
+fn rejects_unexpected_logs() {
+    let factory = Factory::JediswapFactory(JediswapFactory::new(Felt::ONE));
+
+    assert!(matches!(
+        factory.new_empty_amm_from_log(log(Felt::ONE, POOL_CREATED_EVENT, vec![Felt::ONE; 4])),
+        Err(AMMError::EventLogError(
+            EventLogError::InvalidEventSignature
+        ))
+    ));
+    assert!(matches!(
+        factory.new_empty_amm_from_log(log(Felt::ONE, PAIR_CREATED_EVENT, vec![Felt::ONE])),
+        Err(AMMError::UnrecognizedPoolCreatedEventLog)
+    ));
+
+    let myswap = Factory::MySwapFactory(MySwapFactory::new(Felt::ONE));
+    assert!(myswap.amm_created_event_signature().is_empty());
+}
+
+#[test]
+fn factories_from_older_checkpoints_use_default_batch_size() {
+    let factory: Factory =
+        serde_json::from_str(r#"{"JediswapFactory":{"factory_address":"0x1"}}"#).unwrap();
+
+    let Factory::JediswapFactory(factory) = factory else {
+        panic!("expected a Jediswap factory");
+    };
+    assert_eq!(factory.batch_size, DEFAULT_BATCH_SIZE);
+    assert_eq!(factory.with_batch_size(25).batch_size, 25);
+}
+
+#[test]
+fn short_call_results_report_the_call() {
+    let block_id = BlockId::Number(42);
+    let result = [Felt::ONE, Felt::TWO];
+
+    let [reserve_a, _] = read_result(&result, Felt::THREE, "getReserves", block_id).unwrap();
+    assert_eq!(reserve_a, Felt::ONE);
+
+    let err = read_result::<3>(&result, Felt::THREE, "get_reserves", block_id).unwrap_err();
+    assert!(matches!(
+        &err,
+        AMMError::UnexpectedCallResult { address, method, block_id: BlockId::Number(42) }
+            if *address == Felt::THREE && method == "get_reserves"
+    ));
+    assert_eq!(
+        err.to_string(),
+        "Unexpected result of get_reserves on 0x3 at Number(42)"
+    );
+}
+
+#[test]
+fn out_of_range_call_results_report_the_call() {
+    let block_id = BlockId::Number(42);
+
+    assert_eq!(
+        read_integer::<u32>(&[Felt::from(3000u32)], Felt::THREE, "get_fee", block_id).unwrap(),
+        3000
+    );
+    for result in [vec![], vec![Felt::from(u64::MAX)], vec![Felt::MAX]] {
+        let err = read_integer::<u32>(&result, Felt::THREE, "get_fee", block_id).unwrap_err();
+        assert!(matches!(
+            &err,
+            AMMError::UnexpectedCallResult { address, method, .. }
+                if *address == Felt::THREE && method == "get_fee"
+        ));
+    }
+}
+
+#[test]
+fn missing_checkpoint_is_an_error() {
+    let path = std::env::temp_dir().join(format!("missing-checkpoint-{}.json", std::process::id()));
+
+    assert!(matches!(
+        read_checkpoint(path),
+        Err(CheckpointError::IOError(_))
+    ));
+}
@@ -0,0 +1,448 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use mev_engine::provider::{
+    fallback::FallbackProvider,
+    retry::{ProviderStats, RetryProvider},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+use starknet::{
+    core::types::{BlockId, BlockTag, Felt, FunctionCall, StarknetError},
+    providers::{
+        jsonrpc::{HttpTransport, JsonRpcMethod, JsonRpcResponse, JsonRpcTransport},
+        JsonRpcClient, Provider, ProviderError, ProviderRequestData, Url,
+    },
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    time::Instant,
+};
+
+#[derive(Debug, thiserror::Error)]
+#[error("Connection reset")]
+struct ConnectionReset;
+
+/// Transport failing its first requests, then answering every request with `response`.
+struct FlakyTransport {
+    failures: AtomicUsize,
+    delay: Duration,
+    response: serde_json::Value,
+}
+
+impl FlakyTransport {
+    fn new(failures: usize, response: serde_json::Value) -> FlakyTransport {
+        FlakyTransport {
+            failures: AtomicUsize::new(failures),
+            delay: Duration::ZERO,
+            response,
+        }
+    }
+}
+
+#[async_trait]
+impl JsonRpcTransport for FlakyTransport {
+    type Error = ConnectionReset;
+
+    async fn send_request<P, R>(
+        &self,
+        _method: JsonRpcMethod,
+        _params: P,
+    ) -> Result<JsonRpcResponse<R>, ConnectionReset>
+    where
+        P: Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        tokio::time::sleep(self.delay).await;
+        let failing = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| {
+                failures.checked_sub(1)
+            })
+            .is_ok();
+        if failing {
+            return Err(ConnectionReset);
+        }
+
+        Ok(serde_json::from_value(self.response.clone()).unwrap())
+    }
+
+    async fn send_requests<R>(
+        &self,
+        _requests: R,
+    ) -> Result<Vec<JsonRpcResponse<serde_json::Value>>, ConnectionReset>
+    where
+        R: AsRef<[ProviderRequestData]> + Send + Sync,
+    {
+        Err(ConnectionReset)
+    }
+}
+
+/// Transport of a node synced up to `block_number`, answering every call with `call_result`.
+struct Node {
+    block_number: u64,
+    call_result: u64,
+    delay: Duration,
+    down: bool,
+    requests: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl JsonRpcTransport for Node {
+    type Error = ConnectionReset;
+
+    async fn send_request<P, R>(
+        &self,
+        method: JsonRpcMethod,
+        _params: P,
+    ) -> Result<JsonRpcResponse<R>, ConnectionReset>
+    where
+        P: Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+        if self.down {
+            return Err(ConnectionReset);
+        }
+
+        let result = match method {
+            JsonRpcMethod::BlockNumber => json!(self.block_number),
+            JsonRpcMethod::Call => json!([Felt::from(self.call_result)]),
+            method => panic!("unexpected request {method:?}"),
+        };
+        Ok(serde_json::from_value(json!({ "id": 1, "result": result })).unwrap())
+    }
+
+    async fn send_requests<R>(
+        &self,
+        _requests: R,
+    ) -> Result<Vec<JsonRpcResponse<serde_json::Value>>, ConnectionReset>
+    where
+        R: AsRef<[ProviderRequestData]> + Send + Sync,
+    {
+        Err(ConnectionReset)
+    }
+}
+
+fn node(block_number: u64, call_result: u64) -> Node {
+    Node {
+        block_number,
+        call_result,
+        delay: Duration::ZERO,
+        down: false,
+        requests: Arc::new(AtomicUsize::new(0)),
+    }
+}
+
+fn fallback(nodes: Vec<Node>) -> FallbackProvider<Node> {
+    FallbackProvider::new(nodes.into_iter().map(JsonRpcClient::new).collect())
+}
+
+fn function_call() -> FunctionCall {
+    FunctionCall {
+        contract_address: Felt::ONE,
+        entry_point_selector: Felt::TWO,
+        calldata: vec![],
+    }
+}
+
+fn provider(transport: FlakyTransport) -> RetryProvider<JsonRpcClient<FlakyTransport>> {
+    RetryProvider::new(JsonRpcClient::new(transport))
+        .with_backoff(Duration::from_millis(1), Duration::from_millis(5))
+}
+
+fn block_number() -> serde_json::Value {
+    json!({ "id": 1, "result": 42 })
+}
+
+#[tokio::test]
+async fn retries_transient_errors() {
+    let provider = provider(FlakyTransport::new(2, block_number()));
+
+    assert_eq!(provider.block_number().await.unwrap(), 42);
+    assert_eq!(
+        provider.stats(),
+        ProviderStats {
+            requests: 3,
+            retries: 2,
+            ..Default::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn gives_up_after_max_retries() {
+    let provider = provider(FlakyTransport::new(10, block_number())).with_max_retries(2);
+
+    assert!(matches!(
+        provider.block_number().await,
+        Err(ProviderError::Other(_))
+    ));
+    assert_eq!(
+        provider.stats(),
+        ProviderStats {
+            requests: 3,
+            retries: 2,
+            failures: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn starknet_errors_are_not_retried() {
+    let transport = FlakyTransport::new(
+        0,
+        json!({ "id": 1, "error": { "code": 20, "message": "Contract not found" } }),
+    );
+    let provider = provider(transport);
+
+    assert!(matches!(
+        provider
+            .call(function_call(), BlockId::Tag(BlockTag::Latest))
+            .await,
+        Err(ProviderError::StarknetError(
+            StarknetError::ContractNotFound
+        ))
+    ));
+    assert_eq!(provider.stats().requests, 1);
+    assert_eq!(provider.stats().retries, 0);
+}
+
+#[tokio::test]
+async fn times_out_slow_requests() {
+    let transport = FlakyTransport {
+        delay: Duration::from_secs(5),
+        ..FlakyTransport::new(0, block_number())
+    };
+    let provider = provider(transport)
+        .with_timeout(Duration::from_millis(20))
+        .with_max_retries(1);
+
+    let err = provider.block_number().await.unwrap_err();
+    assert_eq!(err.to_string(), "Request timed out after 20ms");
+    assert_eq!(provider.stats().timeouts, 2);
+    assert_eq!(provider.stats().failures, 1);
+}
+
+// The clock only moves when every task waits, so the refills are not skewed by oversleeping.
+#[tokio::test(start_paused = true)]
+async fn rate_limit_spreads_bursts() {
+    let provider = provider(FlakyTransport::new(0, block_number())).with_rate_limit(50.0, 2);
+
+    let start = Instant::now();
+    for _ in 0..6 {
+        provider.block_number().await.unwrap();
+    }
+
+    // The first two requests use the burst, the next four wait 20ms each for a token.
+    assert!(start.elapsed() >= Duration::from_millis(75));
+    assert_eq!(provider.stats().throttled, 4);
+    assert_eq!(provider.stats().requests, 6);
+}
+
+#[tokio::test]
+async fn fallback_fails_over_to_the_next_endpoint() {
+    let down = Node {
+        down: true,
+        ..node(100, 1)
+    };
+    let requests = down.requests.clone();
+    let provider = fallback(vec![down, node(100, 2)]);
+
+    let block_id = BlockId::Number(100);
+    assert_eq!(
+        provider.call(function_call(), block_id).await.unwrap(),
+        vec![Felt::TWO]
+    );
+    assert_eq!(provider.health()[0].consecutive_failures, 1);
+
+    // The failing endpoint is now ranked last.
+    provider.call(function_call(), block_id).await.unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
+
+// On a paused clock, the measured latencies are the node delays whatever the load.
+#[tokio::test(start_paused = true)]
+async fn fallback_prefers_synced_and_fast_endpoints() {
+    let slow = Node {
+        delay: Duration::from_millis(30),
+        ..node(100, 2)
+    };
+    let fast = Node {
+        delay: Duration::from_millis(5),
+        ..node(100, 3)
+    };
+    let provider = fallback(vec![node(90, 1), slow, fast]);
+
+    provider.refresh().await;
+    let health = provider.health();
+    assert_eq!(health[0].block_number, Some(90));
+    assert!(health[1].latency > health[2].latency);
+
+    assert_eq!(
+        provider
+            .call(function_call(), BlockId::Number(100))
+            .await
+            .unwrap(),
+        vec![Felt::THREE]
+    );
+}
+
+#[tokio::test]
+async fn quorum_block_number_is_reached_by_enough_endpoints() {
+    let nodes = || vec![node(100, 1), node(98, 1), node(101, 1)];
+
+    let provider = fallback(nodes()).with_quorum(2);
+    assert_eq!(provider.block_number().await.unwrap(), 100);
+    let provider = fallback(nodes()).with_quorum(3);
+    assert_eq!(provider.block_number().await.unwrap(), 98);
+
+    let down = Node {
+        down: true,
+        ..node(101, 1)
+    };
+    let provider = fallback(vec![node(100, 1), node(98, 1), down]).with_quorum(3);
+    assert_eq!(
+        provider.block_number().await.unwrap_err().to_string(),
+        "2 of the 3 endpoints needed agreed on the result"
+    );
+}
+
+#[tokio::test]
+async fn quorum_call_needs_agreeing_endpoints() {
+    let block_id = BlockId::Number(100);
+
+    let provider = fallback(vec![node(100, 5), node(100, 6), node(100, 5)]).with_quorum(2);
+    assert_eq!(
+        provider.call(function_call(), block_id).await.unwrap(),
+        vec![Felt::from(5u32)]
+    );
+
+    let provider = fallback(vec![node(100, 5), node(100, 6), node(100, 7)]).with_quorum(2);
+    assert_eq!(
+        provider
+            .call(function_call(), block_id)
+            .await
+            .unwrap_err()
+            .to_string(),
+        "1 of the 2 endpoints needed agreed on the result"
+    );
+
+    // Without a quorum, reads only go to one endpoint.
+    let provider = fallback(vec![node(100, 5), node(100, 6)]);
+    provider.call(function_call(), block_id).await.unwrap();
+    assert_eq!(
+        provider
+            .health()
+            .iter()
+            .filter(|health| health.latency.is_some())
+            .count(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn fallback_without_endpoints_fails() {
+    let provider = fallback(vec![]);
+
+    assert_eq!(
+        provider.block_number().await.unwrap_err().to_string(),
+        "No RPC endpoint configured"
+    );
+}
+
+/// Serves `body` to every request on a local port, counting the requests in the returned counter.
+async fn http_node(body: serde_json::Value) -> (Url, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+    let requests = Arc::new(AtomicUsize::new(0));
+
+    let counter = requests.clone();
+    tokio::spawn(async move {
+        let body = body.to_string();
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![];
+            let mut buf = [0; 4096];
+            // Read the headers, then as much of the body as they announce.
+            let body_len = loop {
+                let read = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..read]);
+                let request = String::from_utf8_lossy(&request);
+                if let Some((headers, body)) = request.split_once("\r\n\r\n") {
+                    let length = headers
+                        .lines()
+                        .find_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length: ")?
+                                .parse()
+                                .ok()
+                        })
+                        .unwrap_or(0usize);
+                    break length.saturating_sub(body.len());
+                }
+            };
+            let mut rest = vec![0; body_len];
+            socket.read_exact(&mut rest).await.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\n\
+                 Content-Type: application/json\r\n\
+                 Content-Length: {}\r\n\
+                 Connection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+
+    (url, requests)
+}
+
+fn http_provider(url: Url) -> RetryProvider<JsonRpcClient<HttpTransport>> {
+    RetryProvider::new(JsonRpcClient::new(HttpTransport::new(url)))
+        .with_backoff(Duration::from_millis(1), Duration::from_millis(5))
+        .with_max_retries(2)
+}
+
+#[tokio::test]
+async fn malformed_responses_are_not_retried() {
+    let (url, requests) = http_node(json!({ "id": 1, "result": "forty-two" })).await;
+    let provider = http_provider(url);
+
+    assert!(provider.block_number().await.is_err());
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    assert_eq!(provider.stats().retries, 0);
+}
+
+#[tokio::test]
+async fn rate_limited_and_unreachable_endpoints_are_retried() {
+    let (url, requests) = http_node(json!({
+        "id": 1,
+        "error": { "code": -32005, "message": "Limit exceeded" }
+    }))
+    .await;
+    let provider = http_provider(url);
+
+    assert!(provider.block_number().await.is_err());
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+    // Nothing listens on the port of a dropped listener.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+    drop(listener);
+    let provider = http_provider(url);
+
+    assert!(provider.block_number().await.is_err());
+    assert_eq!(provider.stats().retries, 2);
+}
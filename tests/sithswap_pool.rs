@@ -0,0 +1,112 @@
+use mev_engine::amm::{pool::AutomatedMarketMaker, sithswap::pool::SithSwapPool};
+use starknet::core::types::Felt;
+
+const E6: u128 = 1_000_000;
+const E18: u128 = 1_000_000_000_000_000_000;
+
+fn token_a() -> Felt {
+    Felt::from(0xaau32)
+}
+
+fn token_b() -> Felt {
+    Felt::from(0xbbu32)
+}
+
+fn pool(
+    decimals_a: u8,
+    decimals_b: u8,
+    reserve_a: u128,
+    reserve_b: u128,
+    stable: bool,
+    fee: u32,
+) -> SithSwapPool {
+    SithSwapPool::new(
+        Felt::ONE,
+        token_a(),
+        token_b(),
+        decimals_a,
+        decimals_b,
+        Felt::from(reserve_a),
+        Felt::from(reserve_b),
+        stable,
+        fee,
+    )
+}
+
+#[test]
+fn stable_swap_matches_pair_contract() {
+    // Values from the Solidly pair's `getAmountOut`.
+    let mut pool = pool(6, 6, 1_000_000 * E6, 1_000_000 * E6, true, 2);
+    let amount_out = pool
+        .simulate_swap_mut(token_a(), token_b(), Felt::from(10_000 * E6))
+        .unwrap();
+    assert_eq!(amount_out, Felt::from(9997995004u128));
+    assert_eq!(pool.reserve_a, Felt::from(1_010_000 * E6));
+
+    let pool = pool_with_mixed_decimals();
+    let amount_out = pool
+        .clone()
+        .simulate_swap_mut(token_a(), token_b(), Felt::from(10_000 * E18))
+        .unwrap();
+    assert_eq!(amount_out, Felt::from(10010923859u128));
+}
+
+fn pool_with_mixed_decimals() -> SithSwapPool {
+    pool(18, 6, 1_000_000 * E18, 1_200_000 * E6, true, 2)
+}
+
+#[test]
+fn stable_curve_beats_constant_product_near_peg() {
+    let amount_in = Felt::from(10_000 * E6);
+    let stable = pool(6, 6, 1_000_000 * E6, 1_000_000 * E6, true, 2)
+        .simulate_swap_exact_out(token_a(), token_b(), amount_in)
+        .unwrap();
+    let volatile = pool(6, 6, 1_000_000 * E6, 1_000_000 * E6, false, 2)
+        .simulate_swap_exact_out(token_a(), token_b(), amount_in)
+        .unwrap();
+
+    assert!(stable < volatile);
+}
+
+#[test]
+fn volatile_swap_is_constant_product() {
+    let mut pool = pool(18, 18, 100 * E18, 200 * E18, false, 30);
+    let amount_out = pool
+        .simulate_swap_mut(token_a(), token_b(), Felt::from(E18))
+        .unwrap();
+
+    assert_eq!(amount_out, Felt::from(1974316068794122597u128));
+}
+
+#[test]
+fn simulate_swap_exact_out_returns_smallest_input() {
+    for pool in [
+        pool_with_mixed_decimals(),
+        pool(18, 18, 100 * E18, 200 * E18, false, 30),
+    ] {
+        for (base, quote) in [(token_a(), token_b()), (token_b(), token_a())] {
+            let amount_out = Felt::from(E6);
+            let amount_in = pool
+                .simulate_swap_exact_out(base, quote, amount_out)
+                .unwrap();
+
+            let mut exact = pool.clone();
+            assert!(exact.simulate_swap_mut(base, quote, amount_in).unwrap() >= amount_out);
+            let mut short = pool.clone();
+            assert!(
+                short
+                    .simulate_swap_mut(base, quote, amount_in - Felt::ONE)
+                    .unwrap()
+                    < amount_out
+            );
+        }
+    }
+
+    let pool = pool_with_mixed_decimals();
+    assert!(pool
+        .simulate_swap_exact_out(token_a(), token_b(), pool.reserve_b)
+        .is_err());
+    assert!(pool
+        .simulate_swap_exact_out(Felt::THREE, token_b(), Felt::ONE)
+        .is_err());
+}
@@ -0,0 +1,315 @@
+use std::sync::Arc;
+
+use mev_engine::{
+    amm::{
+        ekubo::{
+            get_data::SWAPPED_EVENT,
+            pool::{EkuboPool, PoolKey},
+        },
+        jediswap::{
+            get_data::{SWAP_EVENT, SYNC_EVENT},
+            pool::JediswapPool,
+        },
+        pool::{
+            apply_events, apply_events_skipping_failures, apply_events_with_snapshots,
+            sync_on_event_signatures, AutomatedMarketMaker, AMM,
+        },
+    },
+    errors::{AMMError, StateSpaceError},
+    state_space::{
+        cache::{StateChange, StateChangeCache},
+        is_fatal, StateSpaceManager,
+    },
+};
+use num_bigint::BigUint;
+use starknet::{
+    core::types::{EmittedEvent, Felt, StarknetError},
+    providers::{
+        jsonrpc::{HttpTransport, HttpTransportError, JsonRpcClientError},
+        JsonRpcClient, ProviderError, Url,
+    },
+};
+
+const E18: u128 = 1_000_000_000_000_000_000;
+
+fn jediswap_pool(address: Felt) -> AMM {
+    AMM::JediswapPool(JediswapPool::new(
+        address,
+        Felt::from(0xaau32),
+        Felt::from(0xbbu32),
+        18,
+        18,
+        Felt::from(5 * E18),
+        Felt::from(10 * E18),
+        300,
+    ))
+}
+
+fn ekubo_pool(tick_spacing: u128) -> AMM {
+    let key = PoolKey {
+        token0: Felt::from(0xaau32),
+        token1: Felt::from(0xbbu32),
+        fee: 0,
+        tick_spacing,
+        extension: Felt::ZERO,
+    };
+    AMM::EkuboPool(EkuboPool::new(
+        Felt::from(0xc0u32),
+        key,
+        18,
+        18,
+        BigUint::from(1u32) << 128,
+        0,
+        E18,
+    ))
+}
+
+fn event(from_address: Felt, selector: Felt, data: Vec<Felt>) -> EmittedEvent {
+    EmittedEvent {
+        from_address,
+        keys: vec![selector],
+        data,
+        block_hash: None,
+        block_number: Some(1),
+        transaction_hash: Felt::ZERO,
+    }
+}
+
+fn sync(from_address: Felt, reserve_a: u128, reserve_b: u128) -> EmittedEvent {
+    event(
+        from_address,
+        SYNC_EVENT,
+        vec![
+            Felt::from(reserve_a),
+            Felt::ZERO,
+            Felt::from(reserve_b),
+            Felt::ZERO,
+        ],
+    )
+}
+
+fn swapped(pool: &AMM, liquidity: u128) -> EmittedEvent {
+    let AMM::EkuboPool(pool) = pool else {
+        unreachable!("not an Ekubo pool")
+    };
+
+    let mut data = vec![Felt::TWO];
+    data.extend(pool.key.to_calldata());
+    data.extend([Felt::ZERO; 10]);
+    data.extend([
+        Felt::ONE,
+        Felt::ONE,
+        Felt::ZERO,
+        Felt::ZERO,
+        Felt::from(liquidity),
+    ]);
+    event(pool.core_address, SWAPPED_EVENT, data)
+}
+
+#[test]
+fn apply_events_routes_events_to_their_pool() {
+    let pools = [
+        jediswap_pool(Felt::ONE),
+        jediswap_pool(Felt::TWO),
+        ekubo_pool(100),
+        ekubo_pool(200),
+    ];
+    let (jediswap, ekubo) = (pools[1].address(), pools[3].address());
+    let events = vec![
+        sync(Felt::TWO, E18, 2 * E18),
+        sync(Felt::THREE, E18, 2 * E18),
+        swapped(&pools[3], 7 * E18),
+        sync(Felt::TWO, 3 * E18, 4 * E18),
+    ];
+
+    let mut amms = pools.to_vec();
+    let updated = apply_events(amms.iter_mut(), &events).unwrap();
+    assert_eq!(updated, vec![jediswap, ekubo]);
+
+    let AMM::JediswapPool(pool) = &amms[1] else {
+        unreachable!()
+    };
+    assert_eq!(
+        (pool.reserve_a, pool.reserve_b),
+        (Felt::from(3 * E18), Felt::from(4 * E18))
+    );
+    let AMM::JediswapPool(pool) = &amms[0] else {
+        unreachable!()
+    };
+    assert_eq!(pool.reserve_a, Felt::from(5 * E18));
+
+    let AMM::EkuboPool(pool) = &amms[3] else {
+        unreachable!()
+    };
+    assert_eq!(pool.liquidity, 7 * E18);
+    let AMM::EkuboPool(pool) = &amms[2] else {
+        unreachable!()
+    };
+    assert_eq!(pool.liquidity, E18);
+}
+
+#[test]
+fn apply_events_skips_events_the_pool_does_not_sync_on() {
+    let mut amms = vec![jediswap_pool(Felt::ONE)];
+    assert_eq!(sync_on_event_signatures(&amms), vec![SYNC_EVENT]);
+
+    // Swap is only applied as a delta when consumed on its own, never next to Sync.
+    let swap = event(Felt::ONE, SWAP_EVENT, vec![Felt::ONE; 10]);
+    let updated = apply_events(amms.iter_mut(), &[swap]).unwrap();
+    assert!(updated.is_empty());
+}
+
+#[tokio::test]
+async fn state_space_is_keyed_by_pool_address() {
+    let provider = Arc::new(JsonRpcClient::new(HttpTransport::new(
+        Url::parse("http://localhost:5050").unwrap(),
+    )));
+    let manager = StateSpaceManager::new(
+        vec![jediswap_pool(Felt::ONE), ekubo_pool(100)],
+        vec![],
+        42,
+        provider,
+    );
+
+    let state = manager.state();
+    let state = state.read().await;
+    assert_eq!(state.len(), 2);
+    assert!(state.contains_key(&Felt::ONE));
+    assert!(state.contains_key(&ekubo_pool(100).address()));
+    assert_eq!(manager.latest_synced_block(), 42);
+}
+
+#[tokio::test]
+async fn sync_block_rejects_synced_blocks() {
+    let provider = Arc::new(JsonRpcClient::new(HttpTransport::new(
+        Url::parse("http://localhost:5050").unwrap(),
+    )));
+    let mut manager = StateSpaceManager::new(vec![], vec![], 42, provider);
+
+    assert!(matches!(
+        manager.sync_block(42).await,
+        Err(StateSpaceError::BlockAlreadySynced(42))
+    ));
+    assert!(matches!(
+        manager.sync_block(7).await,
+        Err(StateSpaceError::BlockAlreadySynced(7))
+    ));
+}
+
+fn state_change(block_number: u64) -> StateChange {
+    StateChange {
+        block_number,
+        block_hash: Felt::from(block_number),
+        previous_amms: vec![jediswap_pool(Felt::ONE)],
+        added_amms: vec![],
+    }
+}
+
+#[test]
+fn state_change_cache_evicts_the_oldest_block() {
+    let mut cache = StateChangeCache::new(3);
+    for block_number in 1..=5 {
+        cache.push(state_change(block_number));
+    }
+    assert_eq!(cache.len(), 3);
+    assert_eq!(cache.latest().unwrap().block_number, 5);
+
+    // Rolling back walks the blocks from the newest to the oldest still cached.
+    let unwound = std::iter::from_fn(|| cache.pop_latest())
+        .map(|change| change.block_number)
+        .collect::<Vec<_>>();
+    assert_eq!(unwound, vec![5, 4, 3]);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn apply_events_with_snapshots_keeps_the_state_before_the_block() {
+    let mut amms = [jediswap_pool(Felt::ONE), jediswap_pool(Felt::TWO)];
+    let events = vec![
+        sync(Felt::ONE, E18, 2 * E18),
+        sync(Felt::ONE, 3 * E18, 4 * E18),
+    ];
+
+    let snapshots = apply_events_with_snapshots(amms.iter_mut(), &events).unwrap();
+    assert_eq!(snapshots.len(), 1);
+    let AMM::JediswapPool(pool) = &snapshots[0] else {
+        unreachable!()
+    };
+    assert_eq!(pool.pool_address, Felt::ONE);
+    assert_eq!(
+        (pool.reserve_a, pool.reserve_b),
+        (Felt::from(5 * E18), Felt::from(10 * E18))
+    );
+}
+
+#[test]
+fn apply_events_with_snapshots_restores_pools_on_error() {
+    let mut amms = [jediswap_pool(Felt::ONE), jediswap_pool(Felt::TWO)];
+    let events = vec![
+        sync(Felt::ONE, E18, 2 * E18),
+        sync(Felt::TWO, 3 * E18, 4 * E18),
+        event(Felt::TWO, SYNC_EVENT, vec![Felt::ONE]),
+    ];
+
+    assert!(apply_events_with_snapshots(amms.iter_mut(), &events).is_err());
+    for amm in &amms {
+        let AMM::JediswapPool(pool) = amm else {
+            unreachable!()
+        };
+        assert_eq!(
+            (pool.reserve_a, pool.reserve_b),
+            (Felt::from(5 * E18), Felt::from(10 * E18))
+        );
+    }
+}
+
+#[test]
+fn apply_events_skipping_failures_keeps_the_other_events() {
+    let mut amms = [jediswap_pool(Felt::ONE), jediswap_pool(Felt::TWO)];
+    let events = vec![
+        sync(Felt::ONE, E18, 2 * E18),
+        sync(Felt::TWO, 3 * E18, 4 * E18),
+        event(Felt::TWO, SYNC_EVENT, vec![Felt::ONE]),
+        sync(Felt::ONE, 5 * E18, 6 * E18),
+    ];
+
+    let (snapshots, errors) = apply_events_skipping_failures(amms.iter_mut(), &events);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(snapshots.len(), 2);
+
+    let reserves = |amm: &AMM| {
+        let AMM::JediswapPool(pool) = amm else {
+            unreachable!()
+        };
+        (pool.reserve_a, pool.reserve_b)
+    };
+    assert_eq!(
+        reserves(&amms[0]),
+        (Felt::from(5 * E18), Felt::from(6 * E18))
+    );
+    assert_eq!(
+        reserves(&amms[1]),
+        (Felt::from(3 * E18), Felt::from(4 * E18))
+    );
+}
+
+#[test]
+fn only_unrecoverable_errors_stop_the_sync_loop() {
+    let malformed: ProviderError = JsonRpcClientError::<HttpTransportError>::JsonError(
+        serde_json::from_str::<u64>("block").unwrap_err(),
+    )
+    .into();
+    assert!(is_fatal(&StateSpaceError::ReorgBeyondCache));
+    assert!(is_fatal(&StateSpaceError::ProviderError(malformed)));
+
+    assert!(!is_fatal(&StateSpaceError::BlockNotFound(42)));
+    assert!(!is_fatal(&StateSpaceError::ProviderError(
+        ProviderError::RateLimited
+    )));
+    assert!(!is_fatal(&StateSpaceError::ProviderError(
+        ProviderError::StarknetError(StarknetError::BlockNotFound)
+    )));
+    assert!(!is_fatal(&StateSpaceError::AMMError(
+        AMMError::PoolDataError
+    )));
+}
@@ -0,0 +1,254 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use async_trait::async_trait;
+use mev_engine::{
+    amm::{
+        factory::Factory,
+        jediswap::{factory::JediswapFactory, pool::JediswapPool},
+        pool::AMM,
+    },
+    cache::{read_checkpoint, save_checkpoint},
+    token_registry::{parse_token_string, TokenMetadata, TokenRegistry},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+use starknet::{
+    core::{
+        types::{BlockId, BlockTag, Felt, FunctionCall},
+        utils::{cairo_short_string_to_felt, get_selector_from_name},
+    },
+    providers::{
+        jsonrpc::{JsonRpcMethod, JsonRpcResponse, JsonRpcTransport},
+        JsonRpcClient, ProviderRequestData,
+    },
+};
+
+fn metadata(decimals: u8, symbol: &str, name: &str) -> TokenMetadata {
+    TokenMetadata {
+        decimals,
+        symbol: symbol.to_string(),
+        name: name.to_string(),
+    }
+}
+
+#[test]
+fn parses_short_string_and_byte_array() {
+    let short = cairo_short_string_to_felt("ETH").unwrap();
+    assert_eq!(parse_token_string(&[short]).unwrap(), "ETH");
+
+    // "Wrapped liquid staked Ether 2.0" is 31 bytes, a full `bytes31` word, followed by a
+    // pending word holding " (wstETH)".
+    let word = cairo_short_string_to_felt("Wrapped liquid staked Ether 2.0").unwrap();
+    let pending = cairo_short_string_to_felt(" (wstETH)").unwrap();
+    let byte_array = [Felt::ONE, word, pending, Felt::from(9u32)];
+    assert_eq!(
+        parse_token_string(&byte_array).unwrap(),
+        "Wrapped liquid staked Ether 2.0 (wstETH)"
+    );
+
+    // A short name fits in the pending word alone.
+    let usdc = cairo_short_string_to_felt("USDC").unwrap();
+    assert_eq!(
+        parse_token_string(&[Felt::ZERO, usdc, Felt::from(4u32)]).unwrap(),
+        "USDC"
+    );
+
+    assert!(parse_token_string(&[]).is_err());
+    assert!(parse_token_string(&[Felt::TWO, word, pending, Felt::from(9u32)]).is_err());
+}
+
+#[test]
+fn clones_share_the_cache() {
+    let registry = TokenRegistry::new();
+    let factory = Factory::JediswapFactory(JediswapFactory::new(Felt::ONE))
+        .with_token_registry(registry.clone());
+
+    registry.insert(Felt::TWO, metadata(6, "USDC", "USD Coin"));
+    let Factory::JediswapFactory(factory) = factory else {
+        unreachable!()
+    };
+    assert_eq!(factory.token_registry.decimals(Felt::TWO), Some(6));
+    assert_eq!(
+        factory.token_registry.get(Felt::TWO),
+        Some(metadata(6, "USDC", "USD Coin"))
+    );
+}
+
+#[test]
+fn checkpoint_keeps_token_metadata_out_of_pools() {
+    let (token_a, token_b) = (Felt::from(0xaau32), Felt::from(0xbbu32));
+    let registry = TokenRegistry::new();
+    registry.insert(token_a, metadata(18, "ETH", "Ether"));
+    registry.insert(token_b, metadata(6, "USDC", "USD Coin"));
+
+    let pool = JediswapPool::new(
+        Felt::ONE,
+        token_a,
+        token_b,
+        18,
+        6,
+        Felt::from(1000u32),
+        Felt::from(2000u32),
+        3000,
+    );
+    let amms = vec![AMM::JediswapPool(pool)];
+
+    let path = std::env::temp_dir().join(format!("token-registry-{}.json", std::process::id()));
+    save_checkpoint(vec![], &amms, &registry, 42, &path).unwrap();
+    let json = std::fs::read_to_string(&path).unwrap();
+    let (amms, block_number) = read_checkpoint(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let checkpoint: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert!(checkpoint["amms"][0]["JediswapPool"]
+        .get("token_a_decimals")
+        .is_none());
+    assert_eq!(checkpoint["tokens"].as_object().unwrap().len(), 2);
+
+    assert_eq!(block_number, 42);
+    let AMM::JediswapPool(pool) = &amms[0] else {
+        panic!("expected a Jediswap pool");
+    };
+    assert_eq!((pool.token_a_decimals, pool.token_b_decimals), (18, 6));
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Connection reset")]
+struct ConnectionReset;
+
+/// Node of tokens returning their address as decimals, symbol and name, except for `unnamed`
+/// which does not implement `name`.
+///
+/// Calls sent on their own fail when the node is `down`, batches are still answered.
+struct Node {
+    unnamed: Felt,
+    down: bool,
+    calls: Arc<AtomicUsize>,
+}
+
+impl Node {
+    fn response(&self, call: &FunctionCall) -> serde_json::Value {
+        let token = call.contract_address;
+        if token == self.unnamed
+            && call.entry_point_selector == get_selector_from_name("name").unwrap()
+        {
+            json!({ "id": 1, "error": { "code": 20, "message": "Contract not found" } })
+        } else {
+            json!({ "id": 1, "result": [token] })
+        }
+    }
+}
+
+#[async_trait]
+impl JsonRpcTransport for Node {
+    type Error = ConnectionReset;
+
+    async fn send_request<P, R>(
+        &self,
+        _method: JsonRpcMethod,
+        params: P,
+    ) -> Result<JsonRpcResponse<R>, ConnectionReset>
+    where
+        P: Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if self.down {
+            return Err(ConnectionReset);
+        }
+
+        let params = serde_json::to_value(params).unwrap();
+        let call = serde_json::from_value(params[0].clone()).unwrap();
+        Ok(serde_json::from_value(self.response(&call)).unwrap())
+    }
+
+    async fn send_requests<R>(
+        &self,
+        requests: R,
+    ) -> Result<Vec<JsonRpcResponse<serde_json::Value>>, ConnectionReset>
+    where
+        R: AsRef<[ProviderRequestData]> + Send + Sync,
+    {
+        Ok(requests
+            .as_ref()
+            .iter()
+            .map(|request| match request {
+                ProviderRequestData::Call(request) => {
+                    serde_json::from_value(self.response(&request.request)).unwrap()
+                }
+                request => panic!("unexpected request {request:?}"),
+            })
+            .collect())
+    }
+}
+
+fn node(unnamed: Felt, down: bool) -> (Arc<JsonRpcClient<Node>>, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let node = Node {
+        unnamed,
+        down,
+        calls: calls.clone(),
+    };
+    (Arc::new(JsonRpcClient::new(node)), calls)
+}
+
+fn short_string(token: Felt) -> String {
+    String::from_utf8(vec![token.to_bytes_be()[31]]).unwrap()
+}
+
+#[tokio::test]
+async fn only_the_failing_batch_is_retried_call_by_call() {
+    let (token_a, token_b) = (Felt::from(6u32), Felt::from(18u32));
+    let (provider, calls) = node(token_a, false);
+    let registry = TokenRegistry::new();
+
+    // Batches of two string calls hold the `symbol` and `name` of a single token.
+    registry
+        .fetch(
+            [token_a, token_b],
+            BlockId::Tag(BlockTag::Latest),
+            2,
+            provider,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        registry.get(token_a),
+        Some(TokenMetadata {
+            decimals: 6,
+            symbol: short_string(token_a),
+            name: String::new(),
+        })
+    );
+    assert_eq!(
+        registry.get(token_b),
+        Some(TokenMetadata {
+            decimals: 18,
+            symbol: short_string(token_b),
+            name: short_string(token_b),
+        })
+    );
+}
+
+#[tokio::test]
+async fn transport_errors_are_returned_without_caching() {
+    let (token_a, token_b) = (Felt::from(6u32), Felt::from(18u32));
+    let (provider, _) = node(token_b, true);
+    let registry = TokenRegistry::new();
+
+    assert!(registry
+        .fetch(
+            [token_a, token_b],
+            BlockId::Tag(BlockTag::Latest),
+            2,
+            provider
+        )
+        .await
+        .is_err());
+    assert!(registry.is_empty());
+}
@@ -0,0 +1,491 @@
+use std::sync::Arc;
+
+use mev_engine::{
+    amm::{
+        factory::{AutomatedMarketMakerFactory, Factory},
+        jediswap::{factory::JediswapFactory, get_data as jediswap_events, pool::JediswapPool},
+        pool::{AutomatedMarketMaker, AMM},
+        tenkswap::{factory::TenKFactory, get_data as tenkswap_events, pool::TenkSwapPool},
+        types::{fee_factor, Price},
+    },
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+use num_bigint::BigUint;
+use starknet::{
+    core::types::{EmittedEvent, Felt},
+    providers::{jsonrpc::HttpTransport, JsonRpcClient, Url},
+};
+
+const E18: u128 = 1_000_000_000_000_000_000;
+
+fn token_a() -> Felt {
+    Felt::from(0xaau32)
+}
+
+fn token_b() -> Felt {
+    Felt::from(0xbbu32)
+}
+
+fn jediswap_pool(reserve_a: u128, reserve_b: u128) -> AMM {
+    AMM::JediswapPool(JediswapPool::new(
+        Felt::ONE,
+        token_a(),
+        token_b(),
+        18,
+        18,
+        Felt::from(reserve_a),
+        Felt::from(reserve_b),
+        300,
+    ))
+}
+
+fn tenkswap_pool(reserve_a: u128, reserve_b: u128) -> AMM {
+    AMM::TenkSwapPool(TenkSwapPool::new(
+        Felt::TWO,
+        token_a(),
+        token_b(),
+        18,
+        18,
+        Felt::from(reserve_a),
+        Felt::from(reserve_b),
+        300,
+    ))
+}
+
+// (amount_in, reserve_in, reserve_out, expected amount_out) from the Uniswap V2 pair test suite.
+const SWAP_VECTORS: [(u128, u128, u128, u128); 7] = [
+    (E18, 5 * E18, 10 * E18, 1662497915624478906),
+    (E18, 10 * E18, 5 * E18, 453305446940074565),
+    (2 * E18, 5 * E18, 10 * E18, 2851015155847869602),
+    (2 * E18, 10 * E18, 5 * E18, 831248957812239453),
+    (E18, 10 * E18, 10 * E18, 906610893880149131),
+    (E18, 100 * E18, 100 * E18, 987158034397061298),
+    (E18, 1000 * E18, 1000 * E18, 996006981039903216),
+];
+
+fn reserves(amm: &AMM) -> (Felt, Felt) {
+    match amm {
+        AMM::JediswapPool(pool) => (pool.reserve_a, pool.reserve_b),
+        AMM::TenkSwapPool(pool) => (pool.reserve_a, pool.reserve_b),
+        _ => unreachable!("not a V2 pool"),
+    }
+}
+
+#[test]
+fn simulate_swap_mut_matches_known_vectors() {
+    for new_pool in [jediswap_pool, tenkswap_pool] {
+        for (amount_in, reserve_in, reserve_out, expected) in SWAP_VECTORS {
+            let mut amm = new_pool(reserve_in, reserve_out);
+            let amount_out = amm
+                .simulate_swap_mut(token_a(), token_b(), Felt::from(amount_in))
+                .unwrap();
+            assert_eq!(amount_out, Felt::from(expected));
+            assert_eq!(
+                reserves(&amm),
+                (
+                    Felt::from(reserve_in + amount_in),
+                    Felt::from(reserve_out - expected)
+                )
+            );
+
+            let mut amm = new_pool(reserve_out, reserve_in);
+            let amount_out = amm
+                .simulate_swap_mut(token_b(), token_a(), Felt::from(amount_in))
+                .unwrap();
+            assert_eq!(amount_out, Felt::from(expected));
+            assert_eq!(
+                reserves(&amm),
+                (
+                    Felt::from(reserve_out - expected),
+                    Felt::from(reserve_in + amount_in)
+                )
+            );
+        }
+    }
+}
+
+#[test]
+fn fee_factor_is_in_thousandths_of_the_input() {
+    assert_eq!(fee_factor(300), BigUint::from(997u32));
+    assert_eq!(fee_factor(0), BigUint::from(1000u32));
+    assert_eq!(fee_factor(u32::MAX), BigUint::from(0u32));
+}
+
+#[test]
+fn get_amount_in_rejects_fees_that_take_the_whole_input() {
+    let jediswap = JediswapPool {
+        fee: 100_000,
+        ..Default::default()
+    };
+    let tenkswap = TenkSwapPool {
+        fee: u32::MAX,
+        ..Default::default()
+    };
+    let (amount_out, reserve_in, reserve_out) =
+        (Felt::from(E18), Felt::from(5 * E18), Felt::from(10 * E18));
+
+    assert!(matches!(
+        jediswap.get_amount_in(amount_out, reserve_in, reserve_out),
+        Err(SwapSimulationError::InvalidFee(100_000))
+    ));
+    assert!(matches!(
+        tenkswap.get_amount_in(amount_out, reserve_in, reserve_out),
+        Err(SwapSimulationError::InvalidFee(u32::MAX))
+    ));
+}
+
+#[test]
+fn simulate_swap_mut_chains_on_updated_reserves() {
+    for new_pool in [jediswap_pool, tenkswap_pool] {
+        let mut amm = new_pool(5 * E18, 10 * E18);
+        amm.simulate_swap_mut(token_a(), token_b(), Felt::from(E18))
+            .unwrap();
+        let amount_out = amm
+            .simulate_swap_mut(token_a(), token_b(), Felt::from(E18))
+            .unwrap();
+
+        let fresh = new_pool(6 * E18, 10 * E18 - 1662497915624478906);
+        let (reserve_in, reserve_out) = reserves(&fresh);
+        let expected = match &fresh {
+            AMM::JediswapPool(pool) => {
+                pool.get_amount_out(Felt::from(E18), reserve_in, reserve_out)
+            }
+            AMM::TenkSwapPool(pool) => {
+                pool.get_amount_out(Felt::from(E18), reserve_in, reserve_out)
+            }
+            _ => unreachable!("not a V2 pool"),
+        };
+        assert_eq!(amount_out, expected);
+    }
+}
+
+#[test]
+fn simulate_swap_mut_rejects_unknown_tokens() {
+    for new_pool in [jediswap_pool, tenkswap_pool] {
+        let mut amm = new_pool(5 * E18, 10 * E18);
+
+        let err = amm
+            .simulate_swap_mut(Felt::THREE, token_b(), Felt::from(E18))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AMMError::ArithmeticError(ArithmeticError::BaseTokenDoesNotExist)
+        ));
+
+        let err = amm
+            .simulate_swap_mut(token_a(), Felt::THREE, Felt::from(E18))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AMMError::ArithmeticError(ArithmeticError::QuoteTokenDoesNotExist)
+        ));
+
+        assert_eq!(reserves(&amm), (Felt::from(5 * E18), Felt::from(10 * E18)));
+    }
+}
+
+#[tokio::test]
+async fn simulate_swap_rejects_unknown_tokens() {
+    let provider = Arc::new(JsonRpcClient::new(HttpTransport::new(
+        Url::parse("http://localhost:5050").unwrap(),
+    )));
+
+    for new_pool in [jediswap_pool, tenkswap_pool] {
+        let amm = new_pool(5 * E18, 10 * E18);
+
+        let err = amm
+            .simulate_swap(Felt::THREE, Felt::from(E18), provider.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AMMError::ArithmeticError(ArithmeticError::BaseTokenDoesNotExist)
+        ));
+    }
+}
+
+#[test]
+fn calculate_price_normalizes_decimals() {
+    // 100 token_a with 18 decimals against 250_000 token_b with 6 decimals.
+    let pools = [
+        AMM::JediswapPool(JediswapPool::new(
+            Felt::ONE,
+            token_a(),
+            token_b(),
+            18,
+            6,
+            Felt::from(100 * E18),
+            Felt::from(250_000_000_000u128),
+            300,
+        )),
+        AMM::TenkSwapPool(TenkSwapPool::new(
+            Felt::TWO,
+            token_a(),
+            token_b(),
+            18,
+            6,
+            Felt::from(100 * E18),
+            Felt::from(250_000_000_000u128),
+            300,
+        )),
+    ];
+
+    for amm in pools {
+        let price = amm.calculate_price_exact(token_a(), token_b()).unwrap();
+        assert_eq!(
+            price,
+            Price {
+                numerator: BigUint::from(2500u32),
+                denominator: BigUint::from(1u32),
+            }
+        );
+        assert_eq!(amm.calculate_price(token_a(), token_b()).unwrap(), 2500.0);
+        assert_eq!(amm.calculate_price(token_b(), token_a()).unwrap(), 0.0004);
+
+        assert!(matches!(
+            amm.calculate_price(Felt::THREE, token_b()),
+            Err(AMMError::ArithmeticError(
+                ArithmeticError::BaseTokenDoesNotExist
+            ))
+        ));
+        assert!(matches!(
+            amm.calculate_price(token_a(), Felt::THREE),
+            Err(AMMError::ArithmeticError(
+                ArithmeticError::QuoteTokenDoesNotExist
+            ))
+        ));
+    }
+}
+
+#[test]
+fn simulate_swap_exact_out_rounds_up() {
+    for new_pool in [jediswap_pool, tenkswap_pool] {
+        // getAmountIn(1, 100, 100) from the Uniswap V2 router test suite.
+        let amm = new_pool(100, 100);
+        let amount_in = amm
+            .simulate_swap_exact_out(token_a(), token_b(), Felt::ONE)
+            .unwrap();
+        assert_eq!(amount_in, Felt::TWO);
+
+        for (_, reserve_in, reserve_out, amount_out) in SWAP_VECTORS {
+            let amm = new_pool(reserve_in, reserve_out);
+            let amount_in = amm
+                .simulate_swap_exact_out(token_a(), token_b(), Felt::from(amount_out))
+                .unwrap();
+
+            let mut swapped = amm.clone();
+            let received = swapped
+                .simulate_swap_mut(token_a(), token_b(), amount_in)
+                .unwrap();
+            assert!(received >= Felt::from(amount_out));
+
+            let mut swapped = amm.clone();
+            let received = swapped
+                .simulate_swap_mut(token_a(), token_b(), amount_in - Felt::ONE)
+                .unwrap();
+            assert!(received < Felt::from(amount_out));
+        }
+    }
+}
+
+#[test]
+fn simulate_swap_exact_out_mut_updates_reserves() {
+    for new_pool in [jediswap_pool, tenkswap_pool] {
+        let mut amm = new_pool(5 * E18, 10 * E18);
+        let amount_out = Felt::from(E18);
+        let amount_in = amm
+            .simulate_swap_exact_out_mut(token_b(), token_a(), amount_out)
+            .unwrap();
+        assert_eq!(
+            reserves(&amm),
+            (
+                Felt::from(5 * E18) - amount_out,
+                Felt::from(10 * E18) + amount_in
+            )
+        );
+
+        assert!(matches!(
+            amm.simulate_swap_exact_out(token_b(), token_a(), Felt::from(4 * E18)),
+            Err(AMMError::SwapSimulationError(
+                SwapSimulationError::LiquidityUnderflow
+            ))
+        ));
+    }
+}
+
+fn event(from_address: Felt, selector: Felt, data: Vec<Felt>) -> EmittedEvent {
+    EmittedEvent {
+        from_address,
+        keys: vec![selector],
+        data,
+        block_hash: None,
+        block_number: Some(1),
+        transaction_hash: Felt::ZERO,
+    }
+}
+
+#[test]
+fn sync_from_event_sets_reserves_from_sync() {
+    let mut jediswap = jediswap_pool(5 * E18, 10 * E18);
+    // Sync { reserve0: Uint256, reserve1: Uint256 }
+    jediswap
+        .sync_from_event(event(
+            Felt::ONE,
+            jediswap_events::SYNC_EVENT,
+            vec![
+                Felt::from(7 * E18),
+                Felt::ZERO,
+                Felt::from(8 * E18),
+                Felt::ZERO,
+            ],
+        ))
+        .unwrap();
+    assert_eq!(
+        reserves(&jediswap),
+        (Felt::from(7 * E18), Felt::from(8 * E18))
+    );
+
+    let mut tenkswap = tenkswap_pool(5 * E18, 10 * E18);
+    // Sync { reserve0, reserve1 }
+    tenkswap
+        .sync_from_event(event(
+            Felt::TWO,
+            tenkswap_events::SYNC_EVENT,
+            vec![Felt::from(7 * E18), Felt::from(8 * E18)],
+        ))
+        .unwrap();
+    assert_eq!(
+        reserves(&tenkswap),
+        (Felt::from(7 * E18), Felt::from(8 * E18))
+    );
+}
+
+#[test]
+fn sync_from_event_applies_swap_mint_and_burn_deltas() {
+    let sender = Felt::from(0x5e4du32);
+
+    let mut jediswap = jediswap_pool(5 * E18, 10 * E18);
+    let u256 = |amount: u128| [Felt::from(amount), Felt::ZERO];
+    let swap = [sender]
+        .into_iter()
+        .chain(u256(E18))
+        .chain(u256(0))
+        .chain(u256(0))
+        .chain(u256(1662497915624478906))
+        .chain([sender])
+        .collect();
+    jediswap
+        .sync_from_event(event(Felt::ONE, jediswap_events::SWAP_EVENT, swap))
+        .unwrap();
+    assert_eq!(
+        reserves(&jediswap),
+        (
+            Felt::from(6 * E18),
+            Felt::from(10 * E18 - 1662497915624478906)
+        )
+    );
+
+    let mut tenkswap = tenkswap_pool(5 * E18, 10 * E18);
+    tenkswap
+        .sync_from_event(event(
+            Felt::TWO,
+            tenkswap_events::MINT_EVENT,
+            vec![sender, Felt::from(E18), Felt::from(2 * E18)],
+        ))
+        .unwrap();
+    tenkswap
+        .sync_from_event(event(
+            Felt::TWO,
+            tenkswap_events::BURN_EVENT,
+            vec![sender, Felt::from(3 * E18), Felt::from(6 * E18), sender],
+        ))
+        .unwrap();
+    assert_eq!(
+        reserves(&tenkswap),
+        (Felt::from(3 * E18), Felt::from(6 * E18))
+    );
+}
+
+#[test]
+fn sync_from_event_rejects_foreign_and_malformed_events() {
+    for mut amm in [
+        jediswap_pool(5 * E18, 10 * E18),
+        tenkswap_pool(5 * E18, 10 * E18),
+    ] {
+        let err = amm
+            .sync_from_event(event(
+                Felt::THREE,
+                jediswap_events::SYNC_EVENT,
+                vec![Felt::ONE; 4],
+            ))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AMMError::EventLogError(EventLogError::UnrelatedEvent)
+        ));
+
+        let address = amm.address();
+        let err = amm
+            .sync_from_event(event(address, jediswap_events::PAIR_CREATED_EVENT, vec![]))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AMMError::EventLogError(EventLogError::InvalidEventSignature)
+        ));
+
+        let err = amm
+            .sync_from_event(event(address, jediswap_events::SYNC_EVENT, vec![Felt::ONE]))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AMMError::EventLogError(EventLogError::InvalidEventData)
+        ));
+
+        assert_eq!(reserves(&amm), (Felt::from(5 * E18), Felt::from(10 * E18)));
+    }
+}
+
+#[test]
+fn pools_discovered_from_logs_take_the_pair_fee() {
+    let factories = [
+        (
+            Factory::JediswapFactory(JediswapFactory::new(Felt::ONE)),
+            jediswap_events::PAIR_CREATED_EVENT,
+            jediswap_events::SYNC_EVENT,
+        ),
+        (
+            Factory::TenKFactory(TenKFactory::new(Felt::ONE)),
+            tenkswap_events::PAIR_CREATED_EVENT,
+            tenkswap_events::SYNC_EVENT,
+        ),
+    ];
+
+    for (factory, pair_created, sync) in factories {
+        let (amount_in, reserve_in, reserve_out, expected) = SWAP_VECTORS[0];
+        let mut amm = factory
+            .new_empty_amm_from_log(event(
+                Felt::ONE,
+                pair_created,
+                vec![token_a(), token_b(), Felt::THREE, Felt::ONE],
+            ))
+            .unwrap();
+
+        let reserves = match amm {
+            AMM::JediswapPool(_) => vec![
+                Felt::from(reserve_in),
+                Felt::ZERO,
+                Felt::from(reserve_out),
+                Felt::ZERO,
+            ],
+            _ => vec![Felt::from(reserve_in), Felt::from(reserve_out)],
+        };
+        amm.sync_from_event(event(Felt::THREE, sync, reserves))
+            .unwrap();
+
+        assert_eq!(
+            amm.simulate_swap_mut(token_a(), token_b(), Felt::from(amount_in))
+                .unwrap(),
+            Felt::from(expected)
+        );
+    }
+}